@@ -1,7 +1,7 @@
 mod steam;
 use std::env;
 
-use steam::router;
+use steam::{config, output, registry::Registry, router};
 mod util;
 use util::async_help::get_blocking_runtime;
 
@@ -9,16 +9,20 @@ fn main() {
     let args = env::args_os()
         .map(|s| s.into_string().unwrap())
         .collect::<Vec<_>>();
-    let user_steam_id: Option<u64> = match env::var("USER_STEAM_ID") {
-        Ok(value) => Some(
-            value
-                .parse::<u64>()
-                .expect("USER_STEAM_ID needs to be a u64"),
-        ),
-        Err(_) => None,
-    };
+    // a missing .env is fine, everything can come from the environment or the config file
+    let _ = dotenvy::dotenv();
+    for problem in config::init_settings() {
+        eprintln!("{problem}");
+    }
+    let user_steam_id = config::settings().values.user_steam_id;
     let rt = get_blocking_runtime();
-    let _ = rt.block_on(router::route_arguments(args, user_steam_id, &StdLogger {}));
+    let _ = rt.block_on(router::route_arguments(
+        &Registry::with_local_files(true),
+        args,
+        user_steam_id,
+        &StdLogger {},
+        output::render,
+    ));
 }
 
 struct StdLogger {}
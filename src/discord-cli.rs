@@ -1,6 +1,4 @@
-use std::env::{self, VarError};
 use std::fmt::Display;
-use std::num::ParseIntError;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
 use std::thread;
@@ -11,13 +9,17 @@ use serenity::model::channel::Message;
 use serenity::prelude::*;
 
 mod steam;
+use steam::config::{self, settings};
 use steam::logger::Logger;
+use steam::output::{self, CommandOutput, OutputFormat};
+use steam::registry::Registry;
 use steam::router;
 mod util;
 use util::{async_help::get_blocking_runtime, string_parser};
 
 struct Handler {
     tx: Sender<DiscordMessage>,
+    registry: Registry,
 }
 
 #[async_trait]
@@ -30,39 +32,89 @@ impl EventHandler for Handler {
             tx: self.tx.clone(),
         };
         if msg.as_ref().content.starts_with("steam-cli") {
-            handle_steam_cli_request(&msg, logger).await;
+            handle_steam_cli_request(&self.registry, &msg, logger).await;
         }
     }
 
     async fn ready(&self, _ctx: Context, _ready: Ready) {}
 }
 
-async fn handle_steam_cli_request(msg: &Message, logger: DiscordLogger) {
-    if let Err(err) = route_steam_cli_request(msg, logger).await {
+async fn handle_steam_cli_request(registry: &Registry, msg: &Message, logger: DiscordLogger) {
+    if let Err(err) = route_steam_cli_request(registry, msg, logger).await {
         eprintln!("{}", err);
     }
 }
 
-async fn route_steam_cli_request<'a>(msg: &Message, logger: DiscordLogger) -> Result<(), Error> {
+async fn route_steam_cli_request(
+    registry: &Registry,
+    msg: &Message,
+    logger: DiscordLogger,
+) -> Result<(), Error> {
     let args = msg
         .content
         .split(' ')
         .map(|s| s.to_owned())
         .collect::<Vec<_>>();
 
-    steam::router::route_arguments(
+    let user_steam_id = settings()
+        .values
+        .user_steam_id
+        .ok_or(Error::MissingConfig("user_steam_id"))?;
+    router::route_arguments(
+        registry,
         args,
-        Some(env::var("USER_STEAM_ID")?.parse::<u64>()?),
+        Some(user_steam_id),
         &logger,
+        render_for_discord,
     )
     .await?;
     Ok(())
 }
 
+/// Players are listed by name instead of as the json the terminal gets, since that's too long to
+/// read in a chat. Everything else, and any other --output, is rendered like on the terminal
+fn render_for_discord(
+    output: CommandOutput,
+    format: OutputFormat,
+) -> Result<String, serde_json::Error> {
+    let players = match (&output, format) {
+        (CommandOutput::Users(users), OutputFormat::Text) => users
+            .iter()
+            .map(|user| format!("{} ({})", user.personaname, user.steamid))
+            .collect::<Vec<_>>(),
+        (CommandOutput::FriendsWithGame(friends), OutputFormat::Text) => friends
+            .iter()
+            .map(|friend| {
+                format!(
+                    "{} ({}){}",
+                    friend.summary.personaname,
+                    friend.summary.steamid,
+                    if friend.borrowed { ", borrowed" } else { "" }
+                )
+            })
+            .collect::<Vec<_>>(),
+        _ => return output::render(output, format),
+    };
+    Ok(format!(
+        "{players}\n\tTotal: {total}\n",
+        total = players.len(),
+        players = players.join("\n"),
+    ))
+}
+
 #[tokio::main]
 async fn main() {
-    // Login with a bot token from the environment
-    let token = env::var("DISCORD_TOKEN").expect("Expected a token in the environment");
+    // a missing .env is fine, everything can come from the environment or the config file
+    let _ = dotenvy::dotenv();
+    for problem in config::init_settings() {
+        eprintln!("{problem}");
+    }
+    // Login with a bot token from the config or the environment
+    let token = settings()
+        .values
+        .discord_token
+        .clone()
+        .expect("Expected discord_token in the config or DISCORD_TOKEN in the environment");
     // Set gateway intents, which decides what events the bot will be notified about
     let intents = GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::DIRECT_MESSAGES
@@ -97,9 +149,11 @@ async fn main() {
         }
     });
 
+    // the bot runs on someone else's machine, so chat users shouldn't be able to touch its files
+    let registry = Registry::with_local_files(false);
     // Create a new instance of the Client, logging in as a bot.
     let mut client = Client::builder(&token, intents)
-        .event_handler(Handler { tx })
+        .event_handler(Handler { tx, registry })
         .await
         .expect("Err creating client");
 
@@ -112,40 +166,26 @@ async fn main() {
 
 #[derive(Debug)]
 enum Error {
-    EnvVarMissing(VarError),
-    Parse(ParseIntError),
+    MissingConfig(&'static str),
     Execution(router::Error),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match &self {
-            Error::EnvVarMissing(err) => write!(f, "EnvVarMissing: {}", err),
-            Error::Parse(err) => write!(f, "Parse: {}", err),
+            Error::MissingConfig(key) => write!(f, "MissingConfig: {} is not set", key),
             Error::Execution(err) => write!(f, "{}", err),
         }
     }
 }
 
-impl From<VarError> for Error {
-    fn from(value: VarError) -> Self {
-        Error::EnvVarMissing(value)
-    }
-}
-
-impl From<ParseIntError> for Error {
-    fn from(value: ParseIntError) -> Self {
-        Error::Parse(value)
-    }
-}
-
 impl From<router::Error> for Error {
     fn from(value: router::Error) -> Self {
         Error::Execution(value)
     }
 }
 
-async fn send_message<'a>(discord_message: DiscordMessage) -> Result<(), DiscordSendError> {
+async fn send_message(discord_message: DiscordMessage) -> Result<(), DiscordSendError> {
     // NOTE: I don't know what the actual size limit is on discord messages. There webiste says
     // 4000 chars; however, it doesn't end up working for me at that size, but 2000 - 8 generally
     // seems to work. The 8 comes from the 4 markdown characters that are used for formatting
@@ -0,0 +1,162 @@
+use clap::{ArgMatches, Command};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use super::{
+    aliases::{load_aliases, remember_persona_names, AliasBook},
+    arg_matcher::{self, SharedArgs},
+    client::UserSummary,
+    games_router::persona_name,
+    logger::FilteringLogger,
+    output::CommandOutput,
+    registry::SteamCommand,
+    router::{get_steam_id, unknown_subcommand, Error},
+    service::get_user_summaries_in_batches,
+};
+
+pub struct AliasCommand;
+
+impl SteamCommand for AliasCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::alias_command(args)
+    }
+
+    fn uses_local_files(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_alias_command(arguments, user_steam_id, logger))
+    }
+}
+
+pub async fn run_alias_command<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    match arguments.subcommand() {
+        Some(("add", arguments)) => {
+            let alias = arguments
+                .get_one::<String>("alias")
+                .ok_or(Error::Argument("alias is required".to_string()))?;
+            let steam_id = get_steam_id(arguments, user_steam_id, "steam_ids", logger)
+                .await?
+                .ok_or(Error::Argument("a steam id is required".to_string()))?;
+            let mut aliases = load_aliases()?;
+            aliases.add(alias, steam_id)?;
+            let summaries = get_user_summaries_in_batches(&[steam_id], logger).await?;
+            aliases.remember_names(&summaries);
+            aliases.save()?;
+            Ok(CommandOutput::Alias {
+                alias: alias.to_string(),
+                steam_id,
+                aliases,
+                summaries,
+            })
+        }
+        Some(("remove", arguments)) => {
+            let alias = arguments
+                .get_one::<String>("alias")
+                .ok_or(Error::Argument("alias is required".to_string()))?;
+            let mut aliases = load_aliases()?;
+            let steam_id = aliases.remove(alias)?;
+            aliases.save()?;
+            Ok(CommandOutput::Message(format!(
+                "removed {alias}, which pointed at {steam_id}"
+            )))
+        }
+        Some(("list", _)) => {
+            let steam_ids = load_aliases()?.aliases.into_values().collect::<Vec<_>>();
+            let summaries = get_user_summaries_in_batches(&steam_ids, logger).await?;
+            remember_persona_names(&summaries, logger);
+            Ok(CommandOutput::Aliases {
+                aliases: load_aliases()?,
+                summaries,
+            })
+        }
+        _ => Err(unknown_subcommand(arguments)),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AliasRecord<'a> {
+    alias: &'a str,
+    steamid: u64,
+    name: String,
+    past_names: Vec<&'a str>,
+}
+
+/// The names the player went by before, other than their current one
+fn past_names<'a>(aliases: &'a AliasBook, steam_id: u64, name: &str) -> Vec<&'a str> {
+    aliases
+        .past_names
+        .get(&steam_id)
+        .into_iter()
+        .flatten()
+        .filter(|past_name| *past_name != name)
+        .map(String::as_str)
+        .collect()
+}
+
+pub fn compute_alias_record<'a>(
+    alias: &'a str,
+    steam_id: u64,
+    aliases: &'a AliasBook,
+    summaries: &[UserSummary],
+) -> AliasRecord<'a> {
+    let name = persona_name(summaries, steam_id);
+    AliasRecord {
+        alias,
+        steamid: steam_id,
+        past_names: past_names(aliases, steam_id, &name),
+        name,
+    }
+}
+
+pub fn compute_alias_records<'a>(
+    aliases: &'a AliasBook,
+    summaries: &[UserSummary],
+) -> Vec<AliasRecord<'a>> {
+    aliases
+        .aliases
+        .iter()
+        .map(|(alias, steam_id)| compute_alias_record(alias, *steam_id, aliases, summaries))
+        .collect()
+}
+
+pub fn compute_alias_string(alias: &str, steam_id: u64, summaries: &[UserSummary]) -> String {
+    format!(
+        "{alias} -> {name} ({steam_id})",
+        name = persona_name(summaries, steam_id)
+    )
+}
+
+pub fn compute_aliases_string(aliases: &AliasBook, summaries: &[UserSummary]) -> String {
+    format!(
+        "{aliases}\n\tTotal: {total}\n",
+        aliases = aliases
+            .aliases
+            .iter()
+            .map(|(alias, steam_id)| {
+                let name = persona_name(summaries, *steam_id);
+                let past_names = past_names(aliases, *steam_id, &name);
+                format!(
+                    "{alias} -> {name} ({steam_id}){past}",
+                    past = if past_names.is_empty() {
+                        String::new()
+                    } else {
+                        format!(", previously {}", past_names.join(", "))
+                    }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = aliases.aliases.len(),
+    )
+}
@@ -0,0 +1,196 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{client::UserSummary, groups::GROUP_PREFIX, logger::FilteringLogger};
+use crate::util::storage;
+
+const ALIASES_FILE: &str = "aliases.json";
+
+/// Short names for friends that keep working when they change their persona name, e.g., dave. The
+/// persona names seen for each aliased friend are remembered too, so their old names still match
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AliasBook {
+    /// lowercased aliases and the steam id each one refers to
+    pub aliases: BTreeMap<String, u64>,
+    pub past_names: BTreeMap<u64, BTreeSet<String>>,
+}
+
+pub fn load_aliases() -> Result<AliasBook, Error> {
+    Ok(storage::load_json(ALIASES_FILE)?)
+}
+
+impl AliasBook {
+    pub fn save(&self) -> Result<(), Error> {
+        Ok(storage::save_json(ALIASES_FILE, self)?)
+    }
+
+    /// Point the alias at the steam id, replacing whoever it pointed at before
+    pub fn add(&mut self, alias: &str, steam_id: u64) -> Result<(), Error> {
+        let alias = alias.trim();
+        if alias.is_empty() || alias.starts_with(GROUP_PREFIX) || alias.parse::<u64>().is_ok() {
+            return Err(Error::Invalid(alias.to_string()));
+        }
+        self.aliases.insert(alias.to_lowercase(), steam_id);
+        Ok(())
+    }
+
+    /// Remove the alias, forgetting the past names of its friend if no other alias points at them
+    pub fn remove(&mut self, alias: &str) -> Result<u64, Error> {
+        let steam_id = self
+            .aliases
+            .remove(&alias.trim().to_lowercase())
+            .ok_or(Error::NotFound(alias.to_string()))?;
+        if !self.aliases.values().any(|id| *id == steam_id) {
+            self.past_names.remove(&steam_id);
+        }
+        Ok(steam_id)
+    }
+
+    /// Look the name up as an alias, ignoring case
+    pub fn lookup_alias(&self, name: &str) -> Option<u64> {
+        self.aliases.get(&name.trim().to_lowercase()).copied()
+    }
+
+    /// Look the name up as an alias and then as a past persona name of an aliased friend, ignoring
+    /// case. Past names only match when none of the current summaries go by the name, so a friend
+    /// who has since taken an old name keeps it. A past name shared by several friends doesn't
+    /// match anyone
+    pub fn lookup(&self, name: &str, current: &[UserSummary]) -> Option<u64> {
+        if let Some(steam_id) = self.lookup_alias(name) {
+            return Some(steam_id);
+        }
+        let name = name.trim().to_lowercase();
+        if current
+            .iter()
+            .any(|summary| summary.personaname.to_lowercase() == name)
+        {
+            return None;
+        }
+        let mut matches = self
+            .past_names
+            .iter()
+            .filter(|(_, names)| names.iter().any(|past| past.to_lowercase() == name))
+            .map(|(steam_id, _)| *steam_id);
+        match (matches.next(), matches.next()) {
+            (Some(steam_id), None) => Some(steam_id),
+            _ => None,
+        }
+    }
+
+    /// Record the persona names of any aliased friends in the summaries. Returns whether any new
+    /// names were seen
+    pub fn remember_names(&mut self, summaries: &[UserSummary]) -> bool {
+        let aliased = self.aliases.values().copied().collect::<BTreeSet<_>>();
+        let mut changed = false;
+        for summary in summaries {
+            let Ok(steam_id) = summary.steamid.parse::<u64>() else {
+                continue;
+            };
+            if aliased.contains(&steam_id) {
+                changed |= self
+                    .past_names
+                    .entry(steam_id)
+                    .or_default()
+                    .insert(summary.personaname.clone());
+            }
+        }
+        changed
+    }
+}
+
+/// Remember the persona names of any aliased friends in the summaries. Failing to read or write the
+/// alias file only logs, since this is never what the user asked for, and nothing is remembered
+/// when local files can't be written
+pub fn remember_persona_names<'a>(summaries: &[UserSummary], logger: &'a FilteringLogger<'a>) {
+    if !storage::writes_allowed() {
+        return;
+    }
+    let mut aliases = match load_aliases() {
+        Ok(aliases) => aliases,
+        Err(err) => {
+            logger.trace(format!("failed to load aliases: {err}"));
+            return;
+        }
+    };
+    if aliases.remember_names(summaries) {
+        if let Err(err) = aliases.save() {
+            logger.trace(format!("failed to save aliases: {err}"));
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Storage(storage::Error),
+    NotFound(String),
+    Invalid(String),
+}
+
+impl From<storage::Error> for Error {
+    fn from(value: storage::Error) -> Self {
+        Error::Storage(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Storage(err) => write!(f, "StorageError: {}", err),
+            Error::NotFound(alias) => write!(f, "there is no alias named {alias}"),
+            Error::Invalid(alias) => write!(
+                f,
+                "{alias} can't be used as an alias, aliases can't be empty, numbers or start with {GROUP_PREFIX}"
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AliasBook;
+    use crate::steam::client::UserSummary;
+
+    fn summary(steamid: &str, personaname: &str) -> UserSummary {
+        UserSummary {
+            steamid: steamid.to_string(),
+            personaname: personaname.to_string(),
+            realname: None,
+            lastlogoff: None,
+            communityvisibilitystate: None,
+        }
+    }
+
+    #[test]
+    fn looks_up_aliases_and_past_names() {
+        let mut aliases = AliasBook::default();
+        aliases.add("Dave", 1).unwrap();
+        aliases.add("bob", 2).unwrap();
+        assert!(aliases.add("@group", 3).is_err());
+        assert!(aliases.remember_names(&[
+            summary("1", "xXDaveXx"),
+            summary("2", "Robert"),
+            summary("3", "not aliased"),
+        ]));
+        assert!(aliases.remember_names(&[summary("1", "DaveTheBrave")]));
+        assert!(!aliases.remember_names(&[summary("1", "DaveTheBrave")]));
+
+        assert_eq!(aliases.lookup("dave", &[]), Some(1));
+        assert_eq!(aliases.lookup("xxdavexx", &[]), Some(1));
+        assert_eq!(aliases.lookup("DaveTheBrave", &[]), Some(1));
+        assert_eq!(aliases.lookup("robert", &[]), Some(2));
+        assert_eq!(aliases.lookup("not aliased", &[]), None);
+
+        // someone else going by an old name now wins over the old name
+        let current = [summary("1", "DaveTheBrave"), summary("4", "Robert")];
+        assert_eq!(aliases.lookup("robert", &current), None);
+        assert_eq!(aliases.lookup("xxdavexx", &current), Some(1));
+        assert_eq!(aliases.lookup("bob", &current), Some(2));
+
+        assert_eq!(aliases.remove("DAVE").unwrap(), 1);
+        assert_eq!(aliases.lookup("xXDaveXx", &[]), None);
+    }
+}
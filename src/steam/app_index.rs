@@ -0,0 +1,223 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    client::{self, GetAppListRequest},
+    logger::FilteringLogger,
+    models::Game,
+};
+use crate::util::storage;
+
+const APP_INDEX_FILE: &str = "app_index.json";
+const REFRESH_INTERVAL_SECONDS: u64 = 24 * 60 * 60;
+const MAX_CANDIDATES: usize = 5;
+/// A runner up scoring at least this fraction of the best match makes the name ambiguous
+const AMBIGUITY_RATIO: f64 = 0.9;
+
+/// A local copy of the names of every game in the steam store, so that games can be looked up by
+/// name without hitting the api every time
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AppIndex {
+    /// unix timestamp of the last time the index was refreshed from the store
+    pub last_refreshed: u64,
+    pub apps: BTreeMap<u64, String>,
+}
+
+/// Load the index from disk, pulling in any games that have changed in the store if it hasn't
+/// been refreshed in a while. The refreshed index is only kept in memory when local files can't be
+/// written
+pub async fn load_app_index<'a>(logger: &'a FilteringLogger<'a>) -> Result<AppIndex, Error> {
+    let mut index: AppIndex = storage::load_json(APP_INDEX_FILE)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    if now.saturating_sub(index.last_refreshed) > REFRESH_INTERVAL_SECONDS {
+        index.refresh(now, logger).await?;
+        if storage::writes_allowed() {
+            storage::save_json(APP_INDEX_FILE, &index)?;
+        }
+    }
+    Ok(index)
+}
+
+impl AppIndex {
+    async fn refresh<'a>(
+        &mut self,
+        now: u64,
+        logger: &'a FilteringLogger<'a>,
+    ) -> Result<(), Error> {
+        logger.trace(format!(
+            "refreshing app index, last refreshed at {}",
+            self.last_refreshed
+        ));
+        let if_modified_since = match self.last_refreshed {
+            0 => None,
+            last_refreshed => Some(last_refreshed),
+        };
+        let mut last_appid = None;
+        loop {
+            let page = client::get_app_list(
+                GetAppListRequest {
+                    if_modified_since,
+                    last_appid,
+                },
+                logger,
+            )
+            .await?;
+            self.apps
+                .extend(page.apps.into_iter().map(|game| (game.appid, game.name)));
+            if !page.have_more_results || page.last_appid.is_none() {
+                break;
+            }
+            last_appid = page.last_appid;
+        }
+        self.last_refreshed = now;
+        Ok(())
+    }
+
+    /// Resolve a game name to a single game. An exact (case insensitive) name match always wins;
+    /// otherwise the best fuzzy match is used unless other games score nearly as well
+    pub fn resolve(&self, name: &str) -> Result<Game, Error> {
+        let exact_matches = self
+            .apps
+            .iter()
+            .filter(|(_, app_name)| app_name.eq_ignore_ascii_case(name))
+            .collect::<Vec<_>>();
+        if let [(appid, app_name)] = exact_matches[..] {
+            return Ok(Game {
+                name: app_name.clone(),
+                appid: *appid,
+            });
+        }
+
+        let candidates = self.search(name);
+        match &candidates[..] {
+            [] => Err(Error::NotFound(name.to_string())),
+            [(best, _)] => Ok(best.clone()),
+            [(best, best_score), (_, runner_up_score), ..] => {
+                if (*runner_up_score as f64) < *best_score as f64 * AMBIGUITY_RATIO {
+                    Ok(best.clone())
+                } else {
+                    Err(Error::Ambiguous(
+                        name.to_string(),
+                        candidates
+                            .into_iter()
+                            .take(MAX_CANDIDATES)
+                            .map(|(game, _)| game)
+                            .collect(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Fuzzy match the name against every game in the index, best matches first
+    pub fn search(&self, name: &str) -> Vec<(Game, u32)> {
+        let apps = self.apps.iter().collect::<Vec<_>>();
+        nucleo_matcher::pattern::Pattern::parse(
+            name,
+            nucleo_matcher::pattern::CaseMatching::Ignore,
+            nucleo_matcher::pattern::Normalization::Smart,
+        )
+        .match_list_with_index(
+            apps.iter().map(|(_, app_name)| app_name),
+            &mut nucleo_matcher::Matcher::new(nucleo_matcher::Config::DEFAULT),
+        )
+        .into_iter()
+        .map(|(_, score, index)| {
+            let (appid, app_name) = apps[index];
+            (
+                Game {
+                    name: app_name.clone(),
+                    appid: *appid,
+                },
+                score,
+            )
+        })
+        .collect()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Client(client::Error),
+    Storage(storage::Error),
+    NotFound(String),
+    Ambiguous(String, Vec<Game>),
+}
+
+impl From<client::Error> for Error {
+    fn from(value: client::Error) -> Self {
+        Error::Client(value)
+    }
+}
+
+impl From<storage::Error> for Error {
+    fn from(value: storage::Error) -> Self {
+        Error::Storage(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Client(err) => write!(f, "ClientError: {}", err),
+            Error::Storage(err) => write!(f, "StorageError: {}", err),
+            Error::NotFound(name) => write!(f, "could not find a game matching {name}"),
+            Error::Ambiguous(name, candidates) => write!(
+                f,
+                "{name} matches several games, try one of these names or app ids:\n{}",
+                candidates
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AppIndex, Error};
+
+    fn index() -> AppIndex {
+        AppIndex {
+            last_refreshed: 0,
+            apps: [
+                (400, "Portal"),
+                (620, "Portal 2"),
+                (105600, "Terraria"),
+                (1, "Dark Souls"),
+                (2, "Dark Souls II"),
+            ]
+            .into_iter()
+            .map(|(appid, name)| (appid, name.to_string()))
+            .collect(),
+        }
+    }
+
+    #[test]
+    fn resolve_prefers_exact_names() {
+        assert_eq!(index().resolve("portal").unwrap().appid, 400);
+        assert_eq!(index().resolve("Portal 2").unwrap().appid, 620);
+    }
+
+    #[test]
+    fn resolve_uses_clear_fuzzy_matches() {
+        assert_eq!(index().resolve("terr").unwrap().appid, 105600);
+    }
+
+    #[test]
+    fn resolve_reports_ambiguous_names() {
+        match index().resolve("dark sou") {
+            Err(Error::Ambiguous(_, candidates)) => assert_eq!(candidates.len(), 2),
+            other => panic!("expected an ambiguous match, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::{future::join_all, stream, StreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    client,
+    logger::FilteringLogger,
+    models::Game,
+    service::{APPDETAILS_CONCURRENCY, DEFAULT_COUNTRY_CODE},
+};
+use crate::util::storage;
+
+const APP_TYPES_FILE: &str = "app_types.json";
+/// The type of regular games. Everything else, e.g., dlc, demo, music, tool or video,
+/// is left out of game lists unless it's asked for
+pub const GAME_TYPE: &str = "game";
+
+/// The appdetails names of the GetItems app types, indexed by EStoreAppType
+const STORE_APP_TYPES: [&str; 15] = [
+    GAME_TYPE,
+    "demo",
+    "mod",
+    "movie",
+    "dlc",
+    "guide",
+    "application",
+    "video",
+    "series",
+    "episode",
+    "hardware",
+    "music",
+    "beta",
+    "tool",
+    "advertising",
+];
+
+/// A local copy of the type of every app that has been looked up. An app's type never changes, so
+/// entries are never refreshed. Apps that steam doesn't know the type of, e.g., delisted ones, are
+/// kept as None so that they aren't looked up again
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AppTypeCache {
+    pub types: HashMap<u64, Option<String>>,
+}
+
+/// Look up the type of each app, using the local cache where possible. Uncached apps are looked up
+/// in batches with GetItems, falling back to appdetails for the apps it doesn't have a type for.
+/// Apps that fail to be looked up are left out of the result, and failing to read or write the
+/// cache only logs
+pub async fn get_app_types<'a>(
+    appids: impl IntoIterator<Item = u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> HashMap<u64, String> {
+    let mut cache = storage::load_json::<AppTypeCache>(APP_TYPES_FILE).unwrap_or_else(|err| {
+        logger.info(format!("failed to load the app type cache: {err}"));
+        AppTypeCache::default()
+    });
+    let appids = appids.into_iter().collect::<HashSet<_>>();
+    let uncached = appids
+        .iter()
+        .filter(|appid| !cache.types.contains_key(appid))
+        .copied()
+        .collect::<Vec<_>>();
+    if !uncached.is_empty() {
+        logger.trace(format!("looking up the type of {} apps", uncached.len()));
+        cache
+            .types
+            .extend(look_up_app_types(&uncached, logger).await);
+        if storage::writes_allowed() {
+            if let Err(err) = storage::save_json(APP_TYPES_FILE, &cache) {
+                logger.info(format!("failed to save the app type cache: {err}"));
+            }
+        }
+    }
+    cache
+        .types
+        .into_iter()
+        .filter(|(appid, _)| appids.contains(appid))
+        .filter_map(|(appid, app_type)| app_type.map(|app_type| (appid, app_type)))
+        .collect()
+}
+
+/// The types of the apps that steam answered for, with None for the apps that it doesn't have a
+/// type for. Apps whose requests failed are left out so that they're tried again next time
+async fn look_up_app_types<'a>(
+    appids: &[u64],
+    logger: &'a FilteringLogger<'a>,
+) -> HashMap<u64, Option<String>> {
+    let batches = appids
+        .chunks(client::STORE_ITEMS_BATCH_SIZE)
+        .collect::<Vec<_>>();
+    let store_items = join_all(
+        batches
+            .iter()
+            .map(|batch| client::get_store_items(batch, DEFAULT_COUNTRY_CODE, logger)),
+    )
+    .await;
+    let mut types = HashMap::new();
+    for (batch, result) in batches.iter().zip(store_items) {
+        match result {
+            Ok(store_items) => types.extend(store_items.iter().filter_map(|item| {
+                let app_type = item
+                    .app_type
+                    .and_then(|app_type| STORE_APP_TYPES.get(app_type as usize));
+                match (item.appid, app_type) {
+                    (Some(appid), _) if item.success != 1 => Some((appid, None)),
+                    (Some(appid), Some(app_type)) => Some((appid, Some(app_type.to_string()))),
+                    _ => None,
+                }
+            })),
+            Err(err) => logger.trace(format!(
+                "failed to get store items for {} apps, falling back to appdetails: {err}",
+                batch.len()
+            )),
+        }
+    }
+
+    let missing_appids = appids
+        .iter()
+        .filter(|appid| !types.contains_key(*appid))
+        .copied()
+        .collect::<Vec<_>>();
+    // a failed GetItems batch leaves up to a whole batch of apps to look up in appdetails, which
+    // is rate limited per app, so they're looked up a few at a time
+    let game_infos = stream::iter(
+        missing_appids
+            .clone()
+            .into_iter()
+            .map(|appid| async move { client::get_game_info(&appid, logger).await }),
+    )
+    .buffered(APPDETAILS_CONCURRENCY)
+    .collect::<Vec<_>>()
+    .await;
+    for (appid, game_info) in missing_appids.into_iter().zip(game_infos) {
+        match game_info {
+            Err(err) => logger.trace(format!("failed to get the type of {appid}: {err}")),
+            Ok(mut response) => {
+                types.insert(
+                    appid,
+                    response
+                        .games
+                        .remove(&appid)
+                        .and_then(|info| info.data)
+                        .and_then(|data| data.app_type),
+                );
+            }
+        }
+    }
+    types
+}
+
+/// Keep the games whose type is one of included_types. Games with an unknown type are kept, since
+/// it's better to show a soundtrack than to hide a game
+pub fn filter_games_by_type(
+    games: impl IntoIterator<Item = Game>,
+    types: &HashMap<u64, String>,
+    included_types: &HashSet<String>,
+) -> Vec<Game> {
+    games
+        .into_iter()
+        .filter(|game| {
+            types
+                .get(&game.appid)
+                .is_none_or(|app_type| included_types.contains(app_type))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::{HashMap, HashSet};
+
+    use super::{filter_games_by_type, GAME_TYPE};
+    use crate::steam::models::Game;
+
+    fn game(appid: u64) -> Game {
+        Game {
+            name: format!("game {appid}"),
+            appid,
+        }
+    }
+
+    #[test]
+    fn keeps_included_and_unknown_types() {
+        let types = HashMap::from([
+            (1, GAME_TYPE.to_string()),
+            (2, "music".to_string()),
+            (3, "dlc".to_string()),
+        ]);
+        let included_types = HashSet::from([GAME_TYPE.to_string(), "dlc".to_string()]);
+        let appids = filter_games_by_type((1..=4).map(game), &types, &included_types)
+            .iter()
+            .map(|game| game.appid)
+            .collect::<Vec<_>>();
+        assert_eq!(appids, vec![1, 3, 4]);
+    }
+}
@@ -3,118 +3,741 @@
 use clap::{command, value_parser, Arg, ArgMatches, Command, Error as ClapError};
 use std::{ffi::OsString, fmt::Display};
 
+use super::{
+    config::KEYS, friend_graph::GraphFormat, output::OutputFormat,
+    purchase_optimizer::OptimizeMode, similarity::SimilarityMetric, sorting::SortKey,
+};
+use crate::util::string_parser::{parse_amount, parse_duration};
+
+/// Args that several commands share, so that they're only defined once
+pub struct SharedArgs {
+    pub self_flag: Arg,
+    pub strict_matching_flag: Arg,
+    pub use_ids_flag: Arg,
+    pub filter_flag: Arg,
+    pub info_flag: Arg,
+    pub group_name_arg: Arg,
+    pub group_members_arg: Arg,
+    pub group_filter_flag: Arg,
+    pub steam_ids_arg: Arg,
+    pub steam_id_arg: Arg,
+    pub game_id_arg: Arg,
+    pub include_family_flag: Arg,
+    pub playtime_filter_args: [Arg; 4],
+    pub country_code_arg: Arg,
+}
+
+impl Default for SharedArgs {
+    fn default() -> SharedArgs {
+        SharedArgs {
+            self_flag: Arg::new("self")
+                .help("if present, then the calling user will be included as a steam id. In the discord implemenation, then this currently is hard coded to my steam_id")
+                .long("self")
+                .short('s')
+                .alias("s")
+                .action(clap::ArgAction::SetTrue),
+            strict_matching_flag: Arg::new("strict")
+                .help("Use strict string matching against personaname")
+                .long("strict")
+                .action(clap::ArgAction::SetTrue),
+            use_ids_flag: Arg::new("use-ids")
+                .help("Use steamids directly instead of having them looked up dynamically")
+                .long("use-ids")
+                .short('i')
+                .action(clap::ArgAction::SetTrue),
+            filter_flag: Arg::new("filter").long("filter").short('f').num_args(1),
+            info_flag: Arg::new("info")
+                .help("show the platforms, review score, price and pc requirements of each game. The pc requirements are only in appdetails, so they're looked up one game at a time")
+                .long("info")
+                .short('i')
+                .action(clap::ArgAction::SetTrue),
+            group_name_arg: Arg::new("name")
+                .help("name of the group, with or without the @")
+                .num_args(1)
+                .value_parser(value_parser!(String))
+                .required(true),
+            group_members_arg: Arg::new("members")
+                .help("steam ids, aliases or names of the players in the group. Other groups can be nested by passing them as @name")
+                .num_args(1..)
+                .value_parser(value_parser!(String)),
+            group_filter_flag: Arg::new("filter")
+                .help("the games --filter to use whenever this group is passed and no --filter is given, or none to clear it")
+                .long("filter")
+                .short('f')
+                .num_args(1)
+                .value_parser(["multiplayer", "controller", "none"]),
+            steam_ids_arg: Arg::new("steam_ids")
+                .help("id(s) assoicated with steam account(s), e.g., for accounts 42 and 7: steam-cli gic 7 42. Saved groups can be passed as @name")
+                .num_args(1..)
+                .value_parser(value_parser!(String)),
+            steam_id_arg: Arg::new("steamid")
+                .help("id associated with the steam account")
+                .num_args(1)
+                .value_parser(value_parser!(u64)),
+            game_id_arg: Arg::new("gameid")
+                .help("app id or name of the game. Names are fuzzy matched against a local index of the steam store, e.g., steam-cli get-game-info \"portal 2\"")
+                .num_args(1..)
+                .value_parser(value_parser!(String)),
+            include_family_flag: Arg::new("include-family")
+                .help("count games shared through a Steam Family as available. Requires STEAM_ACCESS_TOKEN to be set")
+                .long("include-family")
+                .action(clap::ArgAction::SetTrue),
+            playtime_filter_args: [
+                Arg::new("unplayed-by-all")
+                    .help("only show games that nobody in the group has played")
+                    .long("unplayed-by-all")
+                    .action(clap::ArgAction::SetTrue),
+                Arg::new("min-hours-each")
+                    .help("only show games that everyone in the group has played for at least this many hours")
+                    .long("min-hours-each")
+                    .num_args(1)
+                    .value_parser(value_parser!(f64)),
+                Arg::new("played-by")
+                    .help("only show games that this player has played. Resolved the same way as the steam ids")
+                    .long("played-by")
+                    .num_args(1)
+                    .value_parser(value_parser!(String)),
+                Arg::new("not-played-since")
+                    .help("only show games that nobody in the group has played within this long, e.g., 90d, 2w or 1y")
+                    .long("not-played-since")
+                    .num_args(1)
+                    .value_parser(parse_duration),
+            ],
+            country_code_arg: Arg::new("country")
+                .help("country code used to look up store prices, e.g., us or de. Defaults to country in the config, or us")
+                .long("country")
+                .short('c')
+                .num_args(1)
+                .default_value("us"),
+        }
+    }
+}
+
+/// Match the arguments against the global args and the provided commands
 pub fn get_matches(
     args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    commands: impl IntoIterator<Item = Command>,
 ) -> Result<ArgMatches, Error> {
-    let self_flag = Arg::new("self")
-        .help("if present, then the calling user will be included as a steam id. In the discord implemenation, then this currently is hard coded to my steam_id")
-        .long("self")
-        .short('s')
-        .alias("s")
-        .action(clap::ArgAction::SetTrue);
-    let strict_matching_flag = Arg::new("strict")
-        .help("Use strict string matching against personaname")
-        .long("strict")
-        .action(clap::ArgAction::SetTrue);
-    let use_ids_flag = Arg::new("use-ids")
-        .help("Use steamids directly instead of having them looked up dynamically")
-        .long("use-ids")
-        .short('i')
-        .action(clap::ArgAction::SetTrue);
     let verbose_flag = Arg::new("verbose")
         .long("verbose")
         .short('v')
         .action(clap::ArgAction::SetTrue);
 
-    let filter_flag = Arg::new("filter").long("filter").short('f').num_args(1);
-
-    let info_flag = Arg::new("info")
-        .long("info")
-        .short('i')
-        .action(clap::ArgAction::SetTrue);
-
-    let steam_ids_arg = Arg::new("steam_ids")
-        .help("id(s) assoicated with steam account(s), e.g., for accounts 42 and 7: steam-cli gic 7 42")
-        .num_args(1..)
-        .value_parser(value_parser!(String));
-
-    let steam_id_arg = Arg::new("steamid")
-        .help("id associated with the steam account")
-        .num_args(1)
-        .value_parser(value_parser!(u64));
-
-    let game_id_arg = Arg::new("gameid").value_parser(value_parser!(u64));
-
     let matches = command!()
         .version(env!("CARGO_PKG_VERSION"))
         .author("Chris West")
         .about("Some utility functions to run against steam")
         .arg_required_else_help(true)
-        .arg(verbose_flag.clone())
+        .arg(verbose_flag)
+        .arg(
+            Arg::new("profile")
+                .help("use this profile from the config file, including its user_steam_id, e.g., to run as your alt account")
+                .long("profile")
+                .global(true)
+                .num_args(1)
+                .value_parser(value_parser!(String))
+        )
+        .arg(
+            Arg::new("output")
+                .help("how to write out the result. text is the default, human readable output. The others write each row of the result for other tools, e.g., steam-cli --output csv games in-common a b > games.csv")
+                .long("output")
+                .global(true)
+                .num_args(1)
+                .value_parser(value_parser!(OutputFormat))
+                .default_value("text")
+        )
+        .arg(
+            Arg::new("match-threshold")
+                .help("the minimum fuzzy match score for a name to match a friend. Raise it if names match the wrong friends, lower it if they don't match at all. Defaults to match_threshold in the config, or 50")
+                .long("match-threshold")
+                .global(true)
+                .num_args(1)
+                .value_parser(value_parser!(u32))
+        )
+        .subcommands(commands)
+        .try_get_matches_from(args)?;
+    Ok(matches)
+}
+
+pub fn games_command(args: &SharedArgs) -> Command {
+    Command::new("games")
+        .about("module for commands that return lists of games")
+        .alias("g")
+        .arg(args.filter_flag.clone())
+        .arg(args.info_flag.clone())
+        .arg(
+            Arg::new("sort")
+                .help("what to sort the games by. Sorting by release date, review score or price requires looking up the store data of every game. Metacritic scores are only in appdetails, so sorting by them looks up the games one at a time instead of in batches")
+                .long("sort")
+                .num_args(1)
+                .value_parser(value_parser!(SortKey))
+                .default_value("name")
+        )
+        .arg(
+            Arg::new("order")
+                .help("sort order. Defaults to ascending for name, appid and price and descending for everything else")
+                .long("order")
+                .num_args(1)
+                .value_parser(["asc", "desc"])
+        )
+        .arg(
+            Arg::new("include-type")
+                .help("also list apps of these types, e.g., dlc, demo, music, tool or video, or all to skip looking up types. Only games are listed by default")
+                .long("include-type")
+                .num_args(1..)
+                .value_delimiter(',')
+                .value_parser(value_parser!(String))
+        )
+        .arg(
+            Arg::new("limit")
+                .help("only show this many games after sorting")
+                .long("limit")
+                .short('l')
+                .num_args(1)
+                .value_parser(value_parser!(usize))
+        )
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("in-common")
+                .about("find the intersection of games owned by provided steam accounts")
+                .alias("ic")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.include_family_flag.clone())
+                .args(args.playtime_filter_args.clone())
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true),)
+        .subcommand(
+            Command::new("missing-from-group")
+                .about("find the games owned by everyone in the group except for the focused steam account")
+                .alias("mfg")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.include_family_flag.clone())
+                .args(args.playtime_filter_args.clone())
+                .arg(
+                    Arg::new("focus_steam_id")
+                        .help("id associated with the focus steam account")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                )
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("matrix")
+                .about("print a table of the union of the games owned by the provided steam accounts, marking who owns what")
+                .alias("m")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.include_family_flag.clone())
+                .arg(
+                    Arg::new("hours")
+                        .help("show hours played instead of just marking ownership")
+                        .long("hours")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("cost-to-complete")
+                .about("for each member of the group, total up the price of the games that everyone else in the group owns but they don't")
+                .alias("ctc")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.include_family_flag.clone())
+                .arg(args.country_code_arg.clone())
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("optimize-purchases")
+                .about("find which purchases by which members would add the most games to the group's common library")
+                .alias("op")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.country_code_arg.clone())
+                .arg(
+                    Arg::new("budget")
+                        .help("the most the group is willing to spend in total, e.g., 29.99")
+                        .long("budget")
+                        .short('b')
+                        .num_args(1)
+                        .value_parser(parse_amount)
+                )
+                .arg(
+                    Arg::new("max-purchases")
+                        .help("the most copies the group is willing to buy in total")
+                        .long("max-purchases")
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                )
+                .arg(
+                    Arg::new("max-missing")
+                        .help("only consider games that at most this many members are missing")
+                        .long("max-missing")
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                        .default_value("2")
+                )
+                .arg(
+                    Arg::new("mode")
+                        .help("greedy is fast, exact tries every combination and only works when there are a handful of candidate games")
+                        .long("mode")
+                        .num_args(1)
+                        .value_parser(value_parser!(OptimizeMode))
+                        .default_value("greedy")
+                )
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("playable-together")
+                .about("find the games the group can play right now: games everyone owns plus Remote Play Together games that at least one member owns")
+                .alias("pt")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.include_family_flag.clone())
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("recommend")
+                .about("rank the games the group has in common by recent group playtime, hours played, reviews, co-op support and how recently the group last played them")
+                .alias("r")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.include_family_flag.clone())
+                .arg(
+                    Arg::new("remote-play")
+                        .help("also recommend Remote Play Together games that only some of the group owns")
+                        .long("remote-play")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("random")
+                        .help("randomly pick games, weighted by their scores, instead of listing the best ones. Picks one game unless --limit is set")
+                        .long("random")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("seed")
+                        .help("seed for --random. Defaults to today's date, so the same group gets the same pick all night")
+                        .long("seed")
+                        .num_args(1)
+                        .value_parser(value_parser!(u64))
+                )
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("own-or-wishlist")
+                .about("find the games that every provided steam account either owns or has on their wishlist")
+                .alias("ow")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+}
+
+pub fn wishlist_command(args: &SharedArgs) -> Command {
+    Command::new("wishlist")
+        .about("module for commands that look at the wishlists of a group")
+        .alias("wl")
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("overlap")
+                .about("find the games on several members' wishlists, ranked by how many members want them and then by their current discount")
+                .alias("o")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.country_code_arg.clone())
+                .arg(
+                    Arg::new("min-members")
+                        .help("only show games wishlisted by at least this many members")
+                        .long("min-members")
+                        .short('m')
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                        .default_value("2")
+                )
+                .arg(args.steam_ids_arg.clone())
+                .arg_required_else_help(true)
+        )
+}
+
+pub fn friends_command(args: &SharedArgs) -> Command {
+    Command::new("friends")
+        .about("module for commands that compare the libraries of friends. Without a subcommand, it lists the friends of the steam id like get-user-friends-list")
+        .alias("f")
+        .arg(args.steam_id_arg.clone())
+        .arg(args.self_flag.clone())
+        .args_conflicts_with_subcommands(true)
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("similar")
+                .about("rank your friends by how similar their libraries are to yours")
+                .alias("s")
+                .arg(args.include_family_flag.clone())
+                .arg(
+                    Arg::new("by")
+                        .help("jaccard compares which games are owned, playtime weighs each game by how much both of you have played it")
+                        .long("by")
+                        .num_args(1)
+                        .value_parser(value_parser!(SimilarityMetric))
+                        .default_value("jaccard")
+                )
+                .arg(
+                    Arg::new("limit")
+                        .help("only show this many friends")
+                        .long("limit")
+                        .short('l')
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                )
+        )
+        .subcommand(
+            Command::new("cliques")
+                .about("cluster your friends into gaming groups by how much their libraries and recent playtime overlap, naming each group after its top shared games")
+                .alias("c")
+                .arg(args.include_family_flag.clone())
+                .arg(
+                    Arg::new("min-affinity")
+                        .help("how alike two groups of friends have to be, between 0 and 1, before they're merged into one. Lower values give fewer, larger groups")
+                        .long("min-affinity")
+                        .num_args(1)
+                        .value_parser(value_parser!(f64))
+                        .default_value("0.25")
+                )
+                .arg(
+                    Arg::new("save")
+                        .help("save each group, along with you, so that it can be passed to any command that takes players as @clique-name. This replaces the cliques saved last time")
+                        .long("save")
+                        .action(clap::ArgAction::SetTrue)
+                )
+        )
+        .subcommand(
+            Command::new("list")
+                .about("list your friends by how long you've been friends, oldest friendships first")
+                .alias("l")
+                .arg(
+                    Arg::new("newest-first")
+                        .help("list the newest friendships first")
+                        .long("newest-first")
+                        .action(clap::ArgAction::SetTrue)
+                )
+                .arg(
+                    Arg::new("limit")
+                        .help("only show this many friends")
+                        .long("limit")
+                        .short('l')
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                )
+        )
+        .subcommand(
+            Command::new("inactive")
+                .about("list the friends who haven't been online in a while, longest offline first")
+                .alias("i")
+                .arg(
+                    Arg::new("months")
+                        .help("how many months someone has to have been offline to count as inactive")
+                        .long("months")
+                        .short('m')
+                        .num_args(1)
+                        .value_parser(value_parser!(u64))
+                        .default_value("6")
+                )
+        )
         .subcommand(
-            Command::new("games")
-                .about("module for commands that return lists of games")
+            Command::new("privacy")
+                .about("list the friends whose libraries can't be read because their profile or game details are private")
+                .alias("p")
+        )
+        .subcommand(
+            Command::new("graph")
+                .about("crawl friends of friends outwards from a player, defaulting to you, and show who is friends with who along with mutual friends")
                 .alias("g")
-                .arg(filter_flag.clone())
-                .arg(info_flag.clone())
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(
+                    Arg::new("depth")
+                        .help("how many hops to crawl. Each extra hop fetches the friends list of everyone found so far, so keep it small")
+                        .long("depth")
+                        .short('d')
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                        .default_value("2")
+                )
+                .arg(
+                    Arg::new("concurrency")
+                        .help("how many friends lists to fetch at once")
+                        .long("concurrency")
+                        .num_args(1)
+                        .value_parser(value_parser!(usize))
+                        .default_value("8")
+                )
+                .arg(
+                    Arg::new("format")
+                        .help("adjacency lists each player's friends, dot is for graphviz, e.g., steam-cli friends graph --format dot | dot -Tsvg > friends.svg")
+                        .long("format")
+                        .num_args(1)
+                        .value_parser(value_parser!(GraphFormat))
+                        .default_value("adjacency")
+                )
+                .arg(
+                    Arg::new("steam_ids")
+                        .help("the player to start crawling from. Defaults to you")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                )
+        )
+        .subcommand(
+            Command::new("overlap")
+                .about("print a table of how many games each pair of the provided steam accounts have in common")
+                .alias("o")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.include_family_flag.clone())
+                .arg(args.steam_ids_arg.clone())
                 .arg_required_else_help(true)
-                .subcommand(
-                    Command::new("in-common")
-                        .about("find the intersection of games owned by provided steam accounts")
-                        .alias("ic")
-                        .arg(strict_matching_flag.clone())
-                        .arg(use_ids_flag.clone())
-                        .arg(steam_ids_arg.clone())
-                        .arg_required_else_help(true),)
-                .subcommand(
-                    Command::new("missing-from-group")
-                        .about("find the games owned by everyone in the group except for the focused steam account")
-                        .alias("mfg")
-                        .arg(strict_matching_flag.clone())
-                        .arg(use_ids_flag.clone())
-                        .arg(
-                            Arg::new("focus_steam_id")
-                                .help("id associated with the focus steam account")
-                                .num_args(1)
-                                .value_parser(value_parser!(String))
-                        )
-                        .arg(steam_ids_arg.clone())
-                        .arg_required_else_help(true)
+        )
+}
+
+pub fn config_command(_args: &SharedArgs) -> Command {
+    Command::new("config")
+        .about("module for showing and changing the config file. Environment variables, including ones in a .env file, override the values in it")
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("show")
+                .about("show every setting in effect and where it came from")
+        )
+        .subcommand(
+            Command::new("set")
+                .about("set a value in the current profile, e.g., steam-cli config set country de. Leave the value out to unset it")
+                .arg(
+                    Arg::new("key")
+                        .help("the setting to change")
+                        .num_args(1)
+                        .value_parser(KEYS.map(|(key, _, _)| key))
+                        .required(true)
                 )
+                .arg(
+                    Arg::new("value")
+                        .help("the new value")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                )
+                .arg_required_else_help(true)
         )
         .subcommand(
-            Command::new("get-available-endpoints")
-                .about("print out all of the available endpoints. You'll probably want to pipe these into another file that you can search through")
+            Command::new("validate")
+                .about("check the config file for mistakes")
+        )
+}
+
+pub fn init_command(_args: &SharedArgs) -> Command {
+    Command::new("init")
+        .about("write a first config file by answering questions on the terminal")
+        .arg(
+            Arg::new("force")
+                .help("replace the current profile if it already exists")
+                .long("force")
+                .action(clap::ArgAction::SetTrue),
         )
+}
+
+pub fn group_command(args: &SharedArgs) -> Command {
+    Command::new("group")
+        .about("module for saving groups of players, e.g., @friday, that can be passed anywhere a list of players is expected")
+        .arg_required_else_help(true)
         .subcommand(
-            Command::new("get-user-friends-list")
-                .alias("friends")
-                .about("get the friends list of the user")
-                .arg(steam_id_arg.clone())
-                .arg(self_flag.clone())
+            Command::new("create")
+                .about("save a new group, e.g., steam-cli group create friday alice bob @duo --filter multiplayer")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.group_filter_flag.clone())
+                .arg(args.group_name_arg.clone())
+                .arg(args.group_members_arg.clone())
+                .arg_required_else_help(true)
         )
         .subcommand(
-            Command::new("get-player-summary")
-                .about("get user summary data.")
-                .long_about("get user summary data. Much more data is provided by the steam api than what is exposed by this command. Feel free to submit a PR to update this is you want more")
-                .arg(strict_matching_flag.clone())
-                .arg(use_ids_flag.clone())
-                .arg(steam_ids_arg.clone())
-                .arg(self_flag.clone())
+            Command::new("add")
+                .about("add players or nested groups to a group, or change its default filter")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.group_filter_flag.clone())
+                .arg(args.group_name_arg.clone())
+                .arg(args.group_members_arg.clone())
                 .arg_required_else_help(true)
         )
         .subcommand(
-            Command::new("friends-who-own-game")
-                .arg(game_id_arg.clone())
+            Command::new("remove")
+                .about("remove players or nested groups from a group. If none are given, then the whole group is removed")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(args.group_name_arg.clone())
+                .arg(args.group_members_arg.clone())
                 .arg_required_else_help(true)
         )
         .subcommand(
-            Command::new("get-game-info")
-                .arg(game_id_arg.clone())
+            Command::new("list")
+                .about("list the saved groups")
+                .alias("l")
+        )
+        .subcommand(
+            Command::new("show")
+                .about("list everyone in a group, including the members of nested groups")
+                .arg(args.group_name_arg.clone())
                 .arg_required_else_help(true)
         )
-        .try_get_matches_from(args)?;
-    Ok(matches)
+}
+
+pub fn identity_command(args: &SharedArgs) -> Command {
+    Command::new("identity")
+        .about("module for linking a person's main and alt accounts, so that they count as owning the games of all of them")
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("link")
+                .about("link accounts to a person, creating them if needed. Their first account is their main one, e.g., steam-cli identity link chris chris chris-alt")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(
+                    Arg::new("name")
+                        .help("the name of the person, which can also be used anywhere players are expected")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                )
+                .arg(
+                    Arg::new("accounts")
+                        .help("the steam ids, aliases or names of their accounts")
+                        .num_args(1..)
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                )
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("unlink")
+                .about("unlink accounts from a person. If none are given, then the person is removed")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(
+                    Arg::new("name")
+                        .help("the name of the person")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                )
+                .arg(
+                    Arg::new("accounts")
+                        .help("the accounts to unlink")
+                        .num_args(1..)
+                        .value_parser(value_parser!(String))
+                )
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("list")
+                .about("list everyone with linked accounts")
+                .alias("l")
+        )
+}
+
+pub fn alias_command(args: &SharedArgs) -> Command {
+    Command::new("alias")
+        .about("module for saving short names for friends that keep working when they change their persona name. Aliases are checked before persona and real names anywhere players are expected")
+        .alias("a")
+        .arg_required_else_help(true)
+        .subcommand(
+            Command::new("add")
+                .about("save an alias for a player, replacing whoever it pointed at before, e.g., steam-cli alias add dave xXDaveXx")
+                .arg(args.strict_matching_flag.clone())
+                .arg(args.use_ids_flag.clone())
+                .arg(
+                    Arg::new("alias")
+                        .help("the short name to save")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                )
+                .arg(
+                    Arg::new("steam_ids")
+                        .help("the player the alias points at, as a steam id or name")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                )
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("remove")
+                .about("remove an alias")
+                .arg(
+                    Arg::new("alias")
+                        .help("the alias to remove")
+                        .num_args(1)
+                        .value_parser(value_parser!(String))
+                        .required(true)
+                )
+                .arg_required_else_help(true)
+        )
+        .subcommand(
+            Command::new("list")
+                .about("list the saved aliases, along with the persona names each player has been seen with")
+                .alias("l")
+        )
+}
+
+pub fn get_available_endpoints_command(_args: &SharedArgs) -> Command {
+    Command::new("get-available-endpoints")
+        .about("print out all of the available endpoints. You'll probably want to pipe these into another file that you can search through")
+}
+
+pub fn get_user_friends_list_command(args: &SharedArgs) -> Command {
+    Command::new("get-user-friends-list")
+        .about("get the friends list of the user")
+        .arg(args.steam_id_arg.clone())
+        .arg(args.self_flag.clone())
+}
+
+pub fn resolve_command(_args: &SharedArgs) -> Command {
+    Command::new("resolve")
+        .about("show which friend each name resolves to, along with the other friends it could have matched and their scores")
+        .arg(
+            Arg::new("names")
+                .help("names to resolve, e.g., steam-cli resolve chris \"deep rock fan\"")
+                .num_args(1..)
+                .value_parser(value_parser!(String))
+        )
+        .arg_required_else_help(true)
+}
+
+pub fn get_player_summary_command(args: &SharedArgs) -> Command {
+    Command::new("get-player-summary")
+        .about("get user summary data.")
+        .long_about("get user summary data. Much more data is provided by the steam api than what is exposed by this command. Feel free to submit a PR to update this is you want more")
+        .arg(args.strict_matching_flag.clone())
+        .arg(args.use_ids_flag.clone())
+        .arg(args.steam_ids_arg.clone())
+        .arg(args.self_flag.clone())
+        .arg_required_else_help(true)
+}
+
+pub fn friends_who_own_game_command(args: &SharedArgs) -> Command {
+    Command::new("friends-who-own-game")
+        .arg(args.game_id_arg.clone())
+        .arg(args.include_family_flag.clone())
+        .arg_required_else_help(true)
+}
+
+pub fn get_game_info_command(args: &SharedArgs) -> Command {
+    Command::new("get-game-info")
+        .arg(args.game_id_arg.clone())
+        .arg_required_else_help(true)
 }
 
 #[derive(Debug)]
@@ -142,11 +765,14 @@ impl Display for Error {
 
 #[cfg(test)]
 mod test {
-    use super::{get_matches, Error};
+    use super::{games_command, get_matches, Error, SharedArgs};
 
     #[test]
     fn test_get_matches() -> Result<(), Error> {
-        match get_matches(["steam-cli", "games", "--filter", "multiplayer"]) {
+        match get_matches(
+            ["steam-cli", "games", "--filter", "multiplayer"],
+            [games_command(&SharedArgs::default())],
+        ) {
             Err(err) => {
                 panic!("caught error when trying to match arguments: {:?}", err);
             }
@@ -1,18 +1,35 @@
-use std::{
-    borrow::Borrow,
-    collections::HashMap,
-    env::{self, VarError},
-    fmt,
-};
+use std::{borrow::Borrow, collections::HashMap, fmt};
 
 use reqwest;
-use serde::{Deserialize, Serialize};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
-use super::{logger::FilteringLogger, models::Game};
+use super::{
+    config::settings,
+    logger::FilteringLogger,
+    models::{Game, OwnedGame},
+};
 use backoff::ExponentialBackoff;
 
 const BASE_URL: &str = "http://api.steampowered.com";
 
+fn steam_api_key() -> Result<String, Error> {
+    settings()
+        .values
+        .steam_api_key
+        .clone()
+        .ok_or(Error::MissingApiKey)
+}
+
+/// The Steam Family endpoints don't accept the api key, so they need the access token of a logged
+/// in user instead
+fn steam_access_token() -> Result<String, Error> {
+    settings()
+        .values
+        .steam_access_token
+        .clone()
+        .ok_or(Error::MissingAccessToken)
+}
+
 // This is a macro instead of a function so that it can take a statically sized $params instead of
 // a dynamically typed vector
 // This must be called from an async context
@@ -49,7 +66,7 @@ macro_rules! retry_query {
 pub async fn get_owned_games<'a>(
     request: GetUserDetailsRequest,
     logger: &'a FilteringLogger<'a>,
-) -> Result<Vec<Game>, Error> {
+) -> Result<Vec<OwnedGame>, Error> {
     let url = format!(
         "{base}/IPlayerService/GetOwnedGames/v0001/",
         base = BASE_URL
@@ -57,7 +74,7 @@ pub async fn get_owned_games<'a>(
     let url_slice = &url[..];
 
     let params = [
-        ("key", env::var("STEAM_API_KEY")?),
+        ("key", steam_api_key()?),
         ("steamId", request.id.to_string()),
         ("format", "json".to_string()),
         ("include_appinfo", "true".to_string()),
@@ -91,7 +108,7 @@ pub async fn get_owned_games<'a>(
 
 /// Fetch all available endpoints on the ISteamWebAPIUtil endpoints
 pub async fn get_available_endpoints() -> Result<GetAvailableEndpointsResponse, Error> {
-    let params = [("key", env::var("STEAM_API_KEY")?)];
+    let params = [("key", steam_api_key()?)];
 
     let url = format!(
         "{base}/ISteamWebAPIUtil/GetSupportedAPIList/v0001/",
@@ -123,15 +140,12 @@ pub async fn get_user_friends_list<'a>(
     let user = request.id;
     logger.trace(format!("getting user friends for user: {user}"));
 
-    let params = [
-        ("key", env::var("STEAM_API_KEY")?),
-        ("steamid", user.to_string()),
-    ];
+    let params = [("key", steam_api_key()?), ("steamid", user.to_string())];
 
     let url = format!("{base}/ISteamUser/GetFriendList/v0001/", base = BASE_URL);
+    let url_slice = &url[..];
 
-    let client = reqwest::Client::new();
-    let response = client.get(url).query(&params).send().await?;
+    let response = retry_query!(url_slice, &params, user.to_string(), logger);
 
     if response.status().is_success() {
         let body = response.text().await?;
@@ -147,9 +161,20 @@ pub async fn get_user_friends_list<'a>(
     Err(Error::HttpStatus(response.status().into()))
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Whether a friends list request failed because the list is private, which steam reports as 401
+pub fn is_private_friends_list(err: &Error) -> bool {
+    matches!(err, Error::HttpStatus(401 | 403))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Friend {
     pub steamid: String,
+    /// e.g., friend
+    #[serde(default)]
+    pub relationship: String,
+    /// unix timestamp of when the friendship started
+    #[serde(default)]
+    pub friend_since: u64,
 }
 
 impl fmt::Display for Friend {
@@ -158,6 +183,196 @@ impl fmt::Display for Friend {
     }
 }
 
+/// Fetch the apps on the user's wishlist. Names aren't included, so those need to be looked up
+/// separately, e.g., with get_game_info
+pub async fn get_wishlist<'a>(
+    request: GetUserDetailsRequest,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<WishlistItem>, Error> {
+    let url = format!("{base}/IWishlistService/GetWishlist/v1/", base = BASE_URL);
+    let url_slice = &url[..];
+
+    let params = [
+        ("key", steam_api_key()?),
+        ("steamid", request.id.to_string()),
+    ];
+
+    let response = retry_query!(
+        url_slice,
+        &params,
+        format!("wishlist for {}", request.id),
+        logger
+    );
+
+    if response.status().is_success() {
+        let body = response.text().await?;
+        let parse_body: serde_json::Value = serde_json::from_str(&body)?;
+        // an empty wishlist comes back as an empty response object
+        return match parse_body["response"]["items"].as_array() {
+            None => Ok(vec![]),
+            Some(items) => Ok(serde_json::from_value(serde_json::Value::Array(
+                items.to_owned(),
+            ))?),
+        };
+    }
+    Err(Error::HttpStatus(response.status().as_u16()))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WishlistItem {
+    pub appid: u64,
+    #[serde(default)]
+    pub priority: u32,
+    #[serde(default)]
+    pub date_added: u64,
+}
+
+/// Get the id of the Steam Family that the user belongs to, if any. The family endpoints don't
+/// accept the web api key, so this uses the STEAM_ACCESS_TOKEN of a logged in user instead
+pub async fn get_family_group_for_user<'a>(
+    request: GetUserDetailsRequest,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Option<u64>, Error> {
+    let url = format!(
+        "{base}/IFamilyGroupsService/GetFamilyGroupForUser/v1/",
+        base = BASE_URL
+    );
+    let url_slice = &url[..];
+
+    let params = [
+        ("access_token", steam_access_token()?),
+        ("steamid", request.id.to_string()),
+    ];
+
+    let response = retry_query!(
+        url_slice,
+        &params,
+        format!("family group for {}", request.id),
+        logger
+    );
+
+    if response.status().is_success() {
+        let body = response.text().await?;
+        let parse_body: serde_json::Value = serde_json::from_str(&body)?;
+        if parse_body["response"]["is_not_member_of_any_group"]
+            .as_bool()
+            .unwrap_or(false)
+        {
+            return Ok(None);
+        }
+        return match parse_body["response"]["family_groupid"].as_str() {
+            None => Ok(None),
+            Some(family_groupid) => Ok(Some(
+                family_groupid
+                    .parse::<u64>()
+                    .map_err(|_| Error::JsonMissingValue)?,
+            )),
+        };
+    }
+    Err(Error::HttpStatus(response.status().as_u16()))
+}
+
+/// Get the apps that the user can play through their Steam Family, leaving out the apps that they
+/// own themselves
+pub async fn get_shared_library_apps<'a>(
+    family_groupid: u64,
+    request: GetUserDetailsRequest,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<SharedLibraryApp>, Error> {
+    let url = format!(
+        "{base}/IFamilyGroupsService/GetSharedLibraryApps/v1/",
+        base = BASE_URL
+    );
+    let url_slice = &url[..];
+
+    let params = [
+        ("access_token", steam_access_token()?),
+        ("family_groupid", family_groupid.to_string()),
+        ("steamid", request.id.to_string()),
+        ("include_own", "false".to_string()),
+        ("include_excluded", "false".to_string()),
+        ("include_free", "false".to_string()),
+        ("include_non_games", "false".to_string()),
+    ];
+
+    let response = retry_query!(
+        url_slice,
+        &params,
+        format!("shared library apps for {}", request.id),
+        logger
+    );
+
+    if response.status().is_success() {
+        let body = response.text().await?;
+        let parse_body: serde_json::Value = serde_json::from_str(&body)?;
+        return match parse_body["response"]["apps"].as_array() {
+            None => Ok(vec![]),
+            Some(apps) => Ok(serde_json::from_value(serde_json::Value::Array(
+                apps.to_owned(),
+            ))?),
+        };
+    }
+    Err(Error::HttpStatus(response.status().as_u16()))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SharedLibraryApp {
+    pub appid: u64,
+    pub name: String,
+    #[serde(default)]
+    pub owner_steamids: Vec<String>,
+}
+
+/// Fetch a page of the games in the steam store. Pass the last_appid of the previous page to get
+/// the next one, and if_modified_since to only get the games that have changed since then
+pub async fn get_app_list<'a>(
+    request: GetAppListRequest,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<GetAppListResponse, Error> {
+    let url = format!("{base}/IStoreService/GetAppList/v1/", base = BASE_URL);
+    let url_slice = &url[..];
+
+    let mut params = vec![
+        ("key", steam_api_key()?),
+        ("include_games", "true".to_string()),
+        ("max_results", "50000".to_string()),
+    ];
+    if let Some(if_modified_since) = request.if_modified_since {
+        params.push(("if_modified_since", if_modified_since.to_string()));
+    }
+    if let Some(last_appid) = request.last_appid {
+        params.push(("last_appid", last_appid.to_string()));
+    }
+
+    let response = retry_query!(url_slice, &params, "app list", logger);
+
+    if response.status().is_success() {
+        let body = response.text().await?;
+        let parse_body: serde_json::Value = serde_json::from_str(&body)?;
+        return Ok(serde_json::from_value(parse_body["response"].to_owned())?);
+    }
+    Err(Error::HttpStatus(response.status().as_u16()))
+}
+
+#[derive(Debug)]
+pub struct GetAppListRequest {
+    pub if_modified_since: Option<u64>,
+    pub last_appid: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GetAppListResponse {
+    #[serde(default)]
+    pub apps: Vec<Game>,
+    #[serde(default)]
+    pub have_more_results: bool,
+    pub last_appid: Option<u64>,
+}
+
+/// The most steam ids that will be requested in a single GetPlayerSummaries call. Callers are
+/// expected to keep the number of ids at or below this
+pub const USER_SUMMARIES_BATCH_SIZE: usize = 100;
+
 pub async fn get_user_summaries<'a>(
     request: GetUserSummariesRequest,
     logger: &'a FilteringLogger<'a>,
@@ -166,7 +381,7 @@ pub async fn get_user_summaries<'a>(
     logger.trace(format!("getting player summary for users: {:?}", users));
 
     let params = [
-        ("key", env::var("STEAM_API_KEY")?),
+        ("key", steam_api_key()?),
         (
             "steamids",
             users.iter().fold(String::new(), |aggregate, id| {
@@ -202,13 +417,22 @@ pub struct GetUserSummariesRequest {
     pub ids: Vec<u64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserSummary {
     pub steamid: String,
     pub personaname: String,
     pub realname: Option<String>,
+    /// unix timestamp of the last time the user was online. Only provided for public profiles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lastlogoff: Option<u64>,
+    /// 3 if the profile is public. Anything else means it's private or friends only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub communityvisibilitystate: Option<u8>,
 }
 
+/// The communityvisibilitystate of a public profile
+pub const PUBLIC_VISIBILITY_STATE: u8 = 3;
+
 #[derive(Debug)]
 pub struct GetUserDetailsRequest {
     pub id: u64,
@@ -222,20 +446,20 @@ pub struct ApiList {
 #[derive(Serialize, Deserialize)]
 pub struct SteamEndpoint {
     pub name: String,
-    methods: Vec<SteamMethod>,
+    pub methods: Vec<SteamMethod>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SteamMethod {
-    name: String,
-    version: i32,
-    httpmethod: String,
-    parameters: Vec<SteamMethodParameter>,
+    pub name: String,
+    pub version: i32,
+    pub httpmethod: String,
+    pub parameters: Vec<SteamMethodParameter>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct SteamMethodParameter {
-    name: String,
+    pub name: String,
     #[serde(rename = "type")]
     parameter_type: String,
     optional: bool,
@@ -247,7 +471,8 @@ pub enum Error {
     JsonMissingValue,
     Http(reqwest::Error),
     HttpStatus(u16),
-    MissingApiKey(VarError),
+    MissingApiKey,
+    MissingAccessToken,
 }
 
 impl From<serde_json::Error> for Error {
@@ -262,12 +487,6 @@ impl From<reqwest::Error> for Error {
     }
 }
 
-impl From<VarError> for Error {
-    fn from(value: VarError) -> Self {
-        Self::MissingApiKey(value)
-    }
-}
-
 impl From<u16> for Error {
     fn from(value: u16) -> Self {
         Error::HttpStatus(value)
@@ -281,17 +500,183 @@ impl fmt::Display for Error {
             Error::JsonMissingValue => write!(f, "JsonMissingValueError"),
             Error::Http(err) => write!(f, "HttpError({})", err),
             Error::HttpStatus(err) => write!(f, "HttpStatusError({})", err),
-            Error::MissingApiKey(err) => write!(f, "MissingApiKey({})", err),
+            Error::MissingApiKey => write!(
+                f,
+                "MissingApiKey: set steam_api_key in the config or STEAM_API_KEY"
+            ),
+            Error::MissingAccessToken => write!(
+                f,
+                "MissingAccessToken: set steam_access_token in the config or STEAM_ACCESS_TOKEN"
+            ),
         }
     }
 }
 
+/// The most apps that will be requested in a single GetItems call
+pub const STORE_ITEMS_BATCH_SIZE: usize = 100;
+
+/// Fetch the store metadata for many apps at once. Unlike appdetails, this doesn't include things
+/// like pc requirements, but it does include reviews, and it can take many apps per request.
+/// Callers are expected to keep the number of appids at or below STORE_ITEMS_BATCH_SIZE
+pub async fn get_store_items<'a>(
+    appids: &[u64],
+    country_code: &str,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<StoreItem>, Error> {
+    let url = format!("{base}/IStoreBrowseService/GetItems/v1/", base = BASE_URL);
+    let url_slice = &url[..];
+
+    let input_json = serde_json::json!({
+        "ids": appids
+            .iter()
+            .map(|appid| serde_json::json!({ "appid": appid }))
+            .collect::<Vec<_>>(),
+        "context": {
+            "language": "english",
+            "country_code": country_code.to_ascii_uppercase(),
+        },
+        "data_request": {
+            "include_basic_info": true,
+            "include_platforms": true,
+            "include_reviews": true,
+            "include_release": true,
+        },
+    });
+    let params = [
+        ("key", steam_api_key()?),
+        ("input_json", input_json.to_string()),
+    ];
+
+    let response = retry_query!(
+        url_slice,
+        &params,
+        format!("store items for {} apps", appids.len()),
+        logger
+    );
+
+    if response.status().is_success() {
+        let body = response.text().await?;
+        let parse_body: serde_json::Value = serde_json::from_str(&body)?;
+        return match parse_body["response"]["store_items"].as_array() {
+            None => Ok(vec![]),
+            Some(store_items) => Ok(serde_json::from_value(serde_json::Value::Array(
+                store_items.to_owned(),
+            ))?),
+        };
+    }
+    Err(Error::HttpStatus(response.status().as_u16()))
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoreItem {
+    pub appid: Option<u64>,
+    /// 1 if the item was found
+    #[serde(default)]
+    pub success: u32,
+    #[serde(default)]
+    pub name: String,
+    /// the EStoreAppType, e.g., 0 for games or 4 for dlc
+    #[serde(rename = "type", default)]
+    pub app_type: Option<u32>,
+    #[serde(default)]
+    pub is_free: bool,
+    pub categories: Option<StoreCategories>,
+    pub platforms: Option<StorePlatforms>,
+    pub reviews: Option<StoreReviews>,
+    pub best_purchase_option: Option<StorePurchaseOption>,
+    pub release: Option<StoreRelease>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoreCategories {
+    #[serde(default)]
+    pub supported_player_categoryids: Vec<u8>,
+    #[serde(default)]
+    pub feature_categoryids: Vec<u8>,
+    #[serde(default)]
+    pub controller_categoryids: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StorePlatforms {
+    #[serde(default)]
+    pub windows: bool,
+    #[serde(default)]
+    pub mac: bool,
+    #[serde(default)]
+    pub steamos_linux: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoreReviews {
+    pub summary_filtered: Option<ReviewSummary>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReviewSummary {
+    #[serde(default, deserialize_with = "u64_from_string_or_number")]
+    pub review_count: u64,
+    #[serde(default)]
+    pub percent_positive: u8,
+    #[serde(default)]
+    pub review_score: u8,
+    #[serde(default)]
+    pub review_score_label: String,
+}
+
+/// Prices are in the smallest unit of the currency, e.g., cents
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StorePurchaseOption {
+    #[serde(default, deserialize_with = "u64_from_string_or_number")]
+    pub final_price_in_cents: u64,
+    #[serde(default, deserialize_with = "u64_from_string_or_number")]
+    pub original_price_in_cents: u64,
+    #[serde(default)]
+    pub formatted_final_price: String,
+    pub formatted_original_price: Option<String>,
+    #[serde(default)]
+    pub discount_pct: u8,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct StoreRelease {
+    /// unix timestamp
+    pub steam_release_date: Option<u64>,
+}
+
+/// The store apis encode 64 bit numbers as strings, so this accepts either
+fn u64_from_string_or_number<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Number(number) => number
+            .as_u64()
+            .ok_or(serde::de::Error::custom("expected a positive integer")),
+        serde_json::Value::String(string) => string.parse().map_err(serde::de::Error::custom),
+        _ => Err(serde::de::Error::custom("expected a number or a string")),
+    }
+}
+
 pub async fn get_game_info<'a>(
     gameid: &u64,
     logger: &'a FilteringLogger<'a>,
+) -> Result<GetGameInfoResponse, Error> {
+    get_game_info_in_region(gameid, None, logger).await
+}
+
+/// Same as get_game_info, but prices will be in the currency of the provided country code, e.g.,
+/// "us" or "de". If no country code is provided, then the store picks one
+pub async fn get_game_info_in_region<'a>(
+    gameid: &u64,
+    country_code: Option<&str>,
+    logger: &'a FilteringLogger<'a>,
 ) -> Result<GetGameInfoResponse, Error> {
     let url = "http://store.steampowered.com/api/appdetails/";
-    let params = [("appids", gameid.to_string())];
+    let mut params = vec![("appids", gameid.to_string())];
+    if let Some(country_code) = country_code {
+        params.push(("cc", country_code.to_string()));
+    }
     let response = retry_query!(url, &params, format!("appdetails for {}", gameid), logger);
 
     if response.status().is_success() {
@@ -303,15 +688,15 @@ pub async fn get_game_info<'a>(
         return Ok(GetGameInfoResponse {
             games: parse_body
                 .as_object()
-                .unwrap()
+                .ok_or(Error::JsonMissingValue)?
                 .iter()
                 .map(|(key, value)| {
-                    (
-                        key.parse::<u64>().unwrap(),
-                        serde_json::from_value::<GameInfo>(value.to_owned()).unwrap(),
-                    )
+                    Ok((
+                        key.parse::<u64>().map_err(|_| Error::JsonMissingValue)?,
+                        serde_json::from_value::<GameInfo>(value.to_owned())?,
+                    ))
                 })
-                .collect(),
+                .collect::<Result<_, Error>>()?,
         });
     }
     Err(Error::HttpStatus(response.status().as_u16()))
@@ -336,15 +721,43 @@ impl fmt::Display for GameInfo {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GameData {
     // TODO: Make this a set to improve performance
+    #[serde(default)]
     pub categories: Vec<PlayStyleCategories>,
+    #[serde(default, deserialize_with = "deserialize_pc_requirements")]
     pub pc_requirements: Option<PcRequirements>,
     pub name: String,
     pub steam_appid: u64,
+    /// e.g., game, dlc, demo, music, tool or video
+    #[serde(rename = "type", default)]
+    pub app_type: Option<String>,
+    #[serde(default)]
+    pub is_free: bool,
+    pub price_overview: Option<PriceOverview>,
+    pub metacritic: Option<Metacritic>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Metacritic {
+    pub score: u8,
+}
+
+/// Prices are in the smallest unit of the currency, e.g., cents
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriceOverview {
+    pub currency: String,
+    pub initial: u64,
+    #[serde(rename = "final")]
+    pub final_price: u64,
+    pub discount_percent: u8,
+    pub initial_formatted: String,
+    pub final_formatted: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[allow(dead_code)]
 pub enum PlayStyle {
     OnlineCoop = 38,
+    RemotePlayTogether = 44,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -357,3 +770,39 @@ pub struct PlayStyleCategories {
 pub struct PcRequirements {
     pub recommended: Option<String>,
 }
+
+/// appdetails sends an empty array instead of an object for apps without requirements, e.g.,
+/// soundtracks and dlc
+fn deserialize_pc_requirements<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<PcRequirements>, D::Error> {
+    match Option::<serde_json::Value>::deserialize(deserializer)? {
+        Some(requirements @ serde_json::Value::Object(_)) => serde_json::from_value(requirements)
+            .map(Some)
+            .map_err(D::Error::custom),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GameData;
+
+    #[test]
+    fn empty_pc_requirements() {
+        let data = serde_json::from_str::<GameData>(
+            r#"{"type": "music", "name": "Soundtrack", "steam_appid": 10, "pc_requirements": []}"#,
+        )
+        .unwrap();
+        assert!(data.pc_requirements.is_none());
+        let data = serde_json::from_str::<GameData>(
+            r#"{"name": "Portal", "steam_appid": 400, "pc_requirements": {"recommended": "a gpu"}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            data.pc_requirements
+                .and_then(|requirements| requirements.recommended),
+            Some("a gpu".to_string())
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    groups::slugify,
+    models::{Game, OwnedGame},
+    similarity::Similarity,
+};
+
+const JACCARD_WEIGHT: f64 = 0.4;
+const PLAYTIME_WEIGHT: f64 = 0.3;
+const RECENT_WEIGHT: f64 = 0.3;
+/// How many of the top shared games are listed with each clique. The first two name it
+const TOP_GAMES: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct Clique {
+    /// made from the clique's top shared games, e.g., deep-rock-galactic-valheim
+    pub name: String,
+    pub members: Vec<u64>,
+    /// the average affinity between each pair of members
+    pub affinity: f64,
+    pub top_games: Vec<Game>,
+}
+
+/// How likely two players are to play together, between 0 and 1. Owning the same games counts,
+/// but putting hours into the same games and playing the same games lately count for more
+pub fn affinity(similarity: &Similarity) -> f64 {
+    JACCARD_WEIGHT * similarity.jaccard
+        + PLAYTIME_WEIGHT * similarity.playtime
+        + RECENT_WEIGHT * similarity.recent
+}
+
+/// Cluster the players with average linkage clustering: every player starts in their own clique
+/// and the two cliques with the highest average affinity between their members are merged until no
+/// pair of cliques reaches min_affinity. Players are sorted by steam id first and ties are broken
+/// by that order, so the same libraries always give the same cliques. Players left on their own
+/// and empty libraries, which are usually private profiles, are left out
+pub fn find_cliques(libraries: &[(u64, Vec<OwnedGame>)], min_affinity: f64) -> Vec<Clique> {
+    let mut libraries = libraries
+        .iter()
+        .filter(|(_, games)| !games.is_empty())
+        .collect::<Vec<_>>();
+    libraries.sort_by_key(|(steam_id, _)| *steam_id);
+
+    let affinities = libraries
+        .iter()
+        .map(|(_, mine)| {
+            libraries
+                .iter()
+                .map(|(steam_id, theirs)| affinity(&Similarity::between(*steam_id, mine, theirs)))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let average_affinity = |a: &[usize], b: &[usize]| {
+        a.iter()
+            .flat_map(|i| b.iter().map(|j| affinities[*i][*j]))
+            .sum::<f64>()
+            / (a.len() * b.len()) as f64
+    };
+
+    let mut clusters = (0..libraries.len()).map(|i| vec![i]).collect::<Vec<_>>();
+    loop {
+        let mut best: Option<(usize, usize, f64)> = None;
+        for a in 0..clusters.len() {
+            for b in a + 1..clusters.len() {
+                let affinity = average_affinity(&clusters[a], &clusters[b]);
+                if affinity >= min_affinity && best.is_none_or(|(_, _, best)| affinity > best) {
+                    best = Some((a, b, affinity));
+                }
+            }
+        }
+        match best {
+            None => break,
+            Some((a, b, _)) => {
+                let merged = clusters.remove(b);
+                clusters[a].extend(merged);
+            }
+        }
+    }
+
+    let mut cliques = clusters
+        .into_iter()
+        .filter(|cluster| cluster.len() > 1)
+        .map(|mut cluster| {
+            cluster.sort();
+            let pairs = cluster.len() * (cluster.len() - 1);
+            let affinity = cluster
+                .iter()
+                .flat_map(|i| {
+                    cluster
+                        .iter()
+                        .filter(move |j| *j != i)
+                        .map(|j| affinities[*i][*j])
+                })
+                .sum::<f64>()
+                / pairs as f64;
+            let members = cluster.iter().map(|i| libraries[*i]).collect::<Vec<_>>();
+            let top_games = top_shared_games(&members);
+            Clique {
+                name: String::new(),
+                members: members.iter().map(|(steam_id, _)| *steam_id).collect(),
+                affinity,
+                top_games,
+            }
+        })
+        .collect::<Vec<_>>();
+    cliques.sort_by(|a, b| {
+        b.members
+            .len()
+            .cmp(&a.members.len())
+            .then_with(|| b.affinity.total_cmp(&a.affinity))
+    });
+    name_cliques(&mut cliques);
+    cliques
+}
+
+/// The games owned by the most members, breaking ties by how much the members have played them
+fn top_shared_games(members: &[&(u64, Vec<OwnedGame>)]) -> Vec<Game> {
+    let mut owners_and_playtime = HashMap::<&Game, (usize, u64)>::new();
+    for (_, games) in members {
+        for owned_game in games {
+            let entry = owners_and_playtime.entry(&owned_game.game).or_default();
+            entry.0 += 1;
+            entry.1 += owned_game.playtime_forever;
+        }
+    }
+    let mut games = owners_and_playtime.into_iter().collect::<Vec<_>>();
+    games.sort_by(|(a, a_score), (b, b_score)| {
+        b_score
+            .cmp(a_score)
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| a.appid.cmp(&b.appid))
+    });
+    games
+        .into_iter()
+        .take(TOP_GAMES)
+        .map(|(game, _)| game.clone())
+        .collect()
+}
+
+/// Name each clique after its top two games, numbering any names that are already taken
+fn name_cliques(cliques: &mut [Clique]) {
+    let mut taken = HashSet::new();
+    for clique in cliques {
+        let base_name = slugify(
+            &clique
+                .top_games
+                .iter()
+                .take(2)
+                .map(|game| game.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        let base_name = if base_name.is_empty() {
+            "clique".to_string()
+        } else {
+            base_name
+        };
+        let mut name = base_name.clone();
+        let mut suffix = 2;
+        while !taken.insert(name.clone()) {
+            name = format!("{base_name}-{suffix}");
+            suffix += 1;
+        }
+        clique.name = name;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::find_cliques;
+    use crate::steam::models::OwnedGame;
+
+    #[test]
+    fn clusters_players_who_play_the_same_games() {
+        let rock_and_stone = || {
+            vec![
+                OwnedGame {
+                    playtime_forever: 600,
+                    playtime_2weeks: 60,
+                    ..OwnedGame::new(1, "Deep Rock Galactic")
+                },
+                OwnedGame {
+                    playtime_forever: 300,
+                    playtime_2weeks: 30,
+                    ..OwnedGame::new(2, "Valheim")
+                },
+                OwnedGame::new(3, "Portal 2"),
+            ]
+        };
+        let racers = || {
+            vec![
+                OwnedGame {
+                    playtime_forever: 600,
+                    playtime_2weeks: 60,
+                    ..OwnedGame::new(10, "Forza")
+                },
+                OwnedGame {
+                    playtime_forever: 600,
+                    playtime_2weeks: 60,
+                    ..OwnedGame::new(11, "Trackmania")
+                },
+            ]
+        };
+        let libraries = vec![
+            (5, racers()),
+            (1, rock_and_stone()),
+            (4, racers()),
+            (3, rock_and_stone()),
+            (2, rock_and_stone()),
+            (
+                6,
+                vec![OwnedGame {
+                    playtime_forever: 50,
+                    playtime_2weeks: 5,
+                    ..OwnedGame::new(99, "Solitaire")
+                }],
+            ),
+            (7, vec![]),
+        ];
+        let cliques = find_cliques(&libraries, 0.3);
+        let summary = cliques
+            .iter()
+            .map(|clique| (clique.name.as_str(), clique.members.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                ("deep-rock-galactic-valheim", vec![1, 2, 3]),
+                ("forza-trackmania", vec![4, 5]),
+            ]
+        );
+    }
+}
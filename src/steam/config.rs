@@ -0,0 +1,484 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Display,
+    fs,
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use serde::{Deserialize, Serialize};
+
+const APP_DIRECTORY: &str = "steam-cli";
+const CONFIG_FILE: &str = "config.toml";
+/// Points at a config file to use instead of the one in the config directory
+const CONFIG_PATH_VARIABLE: &str = "STEAM_CLI_CONFIG";
+/// Picks the profile to use instead of the config file's default_profile
+pub const PROFILE_VARIABLE: &str = "STEAM_CLI_PROFILE";
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Every setting that can be put in a profile, along with the environment variable that overrides
+/// it and a description for config show and init
+pub const KEYS: [(&str, &str, &str); 7] = [
+    (
+        "steam_api_key",
+        "STEAM_API_KEY",
+        "your steam web api key, from https://steamcommunity.com/dev/apikey",
+    ),
+    (
+        "user_steam_id",
+        "USER_STEAM_ID",
+        "your steam id, used to look up your friends by name",
+    ),
+    (
+        "steam_access_token",
+        "STEAM_ACCESS_TOKEN",
+        "the webapi_token of a logged in store session, only needed for --include-family",
+    ),
+    (
+        "discord_token",
+        "DISCORD_TOKEN",
+        "the token of the discord bot, only needed for discord-steam-cli",
+    ),
+    (
+        "country",
+        "STEAM_CLI_COUNTRY",
+        "the country code used for store prices when --country isn't given, e.g., us or de",
+    ),
+    (
+        "match_threshold",
+        "STEAM_CLI_MATCH_THRESHOLD",
+        "the default for --match-threshold",
+    ),
+    (
+        "include_family",
+        "STEAM_CLI_INCLUDE_FAMILY",
+        "whether to count Steam Family games as if --include-family was always passed",
+    ),
+];
+
+/// Settings that would leak access to an account if they were printed
+const SECRET_KEYS: [&str; 3] = ["steam_api_key", "steam_access_token", "discord_token"];
+
+static SETTINGS: RwLock<Option<Arc<Settings>>> = RwLock::new(None);
+
+/// The config file as it's written to disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steam_api_key: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_steam_id: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub steam_access_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub discord_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_threshold: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub include_family: Option<bool>,
+}
+
+/// The settings in effect: the selected profile with any environment variables applied on top
+#[derive(Debug, Default)]
+pub struct Settings {
+    pub profile_name: String,
+    pub values: Profile,
+}
+
+/// The settings loaded at startup, or by the last --profile. If none were loaded, e.g., in tests,
+/// then everything is unset
+pub fn settings() -> Arc<Settings> {
+    match SETTINGS.read() {
+        Ok(settings) => settings.clone().unwrap_or_default(),
+        Err(_) => Arc::default(),
+    }
+}
+
+fn replace_settings(settings: Settings) {
+    if let Ok(mut current) = SETTINGS.write() {
+        *current = Some(Arc::new(settings));
+    }
+}
+
+/// Switch to the named profile, e.g., for --profile
+pub fn use_profile(name: &str) -> Result<(), Error> {
+    // bad environment variables were already reported by init_settings
+    let (settings, _) = load_settings(|variable| env::var(variable).ok(), Some(name))?;
+    replace_settings(settings);
+    Ok(())
+}
+
+/// Load the settings of the selected profile and make them available through settings(). Whatever
+/// couldn't be used is skipped and returned so that it can be reported: a config file that can't
+/// be read leaves only the environment, and a bad environment variable is left out on its own
+pub fn init_settings() -> Vec<Error> {
+    let lookup = |variable: &str| env::var(variable).ok();
+    let (settings, problems) = match load_settings(lookup, None) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            let mut values = Profile::default();
+            let mut problems = vec![Error::IgnoredConfigFile(Box::new(err))];
+            problems.extend(values.apply_environment(lookup, true));
+            (
+                Settings {
+                    profile_name: DEFAULT_PROFILE.to_string(),
+                    values,
+                },
+                problems,
+            )
+        }
+    };
+    replace_settings(settings);
+    problems
+}
+
+/// Load the settings of the profile, or of the one picked by the environment and config file if
+/// none is given. The environment overrides the config file, but a profile passed here, e.g., by
+/// --profile, keeps its own values and the environment only fills in what it leaves out, so that
+/// switching to an alt's profile doesn't keep using the USER_STEAM_ID of the main account
+pub fn load_settings(
+    lookup: impl Fn(&str) -> Option<String>,
+    profile: Option<&str>,
+) -> Result<(Settings, Vec<Error>), Error> {
+    let config = load_config_file(&config_path()?)?;
+    let picked = profile.is_some();
+    let profile_name = profile
+        .map(str::to_string)
+        .unwrap_or_else(|| config.profile_name(&lookup));
+    let mut values = config.profile(&profile_name)?;
+    let problems = values.apply_environment(lookup, !picked);
+    Ok((
+        Settings {
+            profile_name,
+            values,
+        },
+        problems,
+    ))
+}
+
+/// The path of the config file, e.g., ~/.config/steam-cli/config.toml on linux
+pub fn config_path() -> Result<PathBuf, Error> {
+    if let Ok(path) = env::var(CONFIG_PATH_VARIABLE) {
+        return Ok(PathBuf::from(path));
+    }
+    Ok(dirs::config_dir()
+        .ok_or(Error::NoConfigDirectory)?
+        .join(APP_DIRECTORY)
+        .join(CONFIG_FILE))
+}
+
+/// Load the config file. If it doesn't exist yet, then an empty config is returned instead
+pub fn load_config_file(path: &Path) -> Result<ConfigFile, Error> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(toml::from_str(&contents)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(ConfigFile::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+impl ConfigFile {
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// The profile picked by STEAM_CLI_PROFILE, falling back to default_profile and then default
+    pub fn profile_name(&self, lookup: impl Fn(&str) -> Option<String>) -> String {
+        lookup(PROFILE_VARIABLE)
+            .or(self.default_profile.clone())
+            .unwrap_or(DEFAULT_PROFILE.to_string())
+    }
+
+    /// The named profile. The default profile doesn't have to exist, so that the environment alone
+    /// is enough to run
+    pub fn profile(&self, name: &str) -> Result<Profile, Error> {
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.clone()),
+            None if name == DEFAULT_PROFILE => Ok(Profile::default()),
+            None => Err(Error::UnknownProfile(name.to_string())),
+        }
+    }
+
+    /// Everything that looks wrong in the config, e.g., a steam id that's too short to be one
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if let Some(default_profile) = &self.default_profile {
+            if !self.profiles.contains_key(default_profile) {
+                problems.push(format!(
+                    "default_profile is {default_profile}, but there is no [profiles.{default_profile}]"
+                ));
+            }
+        }
+        for (name, profile) in &self.profiles {
+            problems.extend(
+                profile
+                    .validate()
+                    .into_iter()
+                    .map(|problem| format!("[profiles.{name}] {problem}")),
+            );
+        }
+        problems
+    }
+}
+
+impl Profile {
+    pub fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        Ok(match key {
+            "steam_api_key" => self.steam_api_key.clone(),
+            "user_steam_id" => self.user_steam_id.map(|id| id.to_string()),
+            "steam_access_token" => self.steam_access_token.clone(),
+            "discord_token" => self.discord_token.clone(),
+            "country" => self.country.clone(),
+            "match_threshold" => self.match_threshold.map(|threshold| threshold.to_string()),
+            "include_family" => self.include_family.map(|include| include.to_string()),
+            other => return Err(Error::UnknownKey(other.to_string())),
+        })
+    }
+
+    /// Set the key from a string, parsing it into the key's type. None unsets it
+    pub fn set(&mut self, key: &str, value: Option<&str>) -> Result<(), Error> {
+        let value = value.map(str::trim);
+        let invalid = |value: &str| Error::InvalidValue(key.to_string(), value.to_string());
+        match key {
+            "steam_api_key" => self.steam_api_key = value.map(str::to_string),
+            "user_steam_id" => {
+                self.user_steam_id = value
+                    .map(|value| value.parse().map_err(|_| invalid(value)))
+                    .transpose()?
+            }
+            "steam_access_token" => self.steam_access_token = value.map(str::to_string),
+            "discord_token" => self.discord_token = value.map(str::to_string),
+            "country" => self.country = value.map(str::to_lowercase),
+            "match_threshold" => {
+                self.match_threshold = value
+                    .map(|value| value.parse().map_err(|_| invalid(value)))
+                    .transpose()?
+            }
+            "include_family" => {
+                self.include_family = value
+                    .map(|value| match value.to_lowercase().as_str() {
+                        "true" | "1" | "yes" => Ok(true),
+                        "false" | "0" | "no" => Ok(false),
+                        _ => Err(invalid(value)),
+                    })
+                    .transpose()?
+            }
+            other => return Err(Error::UnknownKey(other.to_string())),
+        }
+        Ok(())
+    }
+
+    /// Set every key whose environment variable is set. Unless overwrite is set, keys that already
+    /// have a value keep it. Variables that don't parse are skipped and returned
+    pub fn apply_environment(
+        &mut self,
+        lookup: impl Fn(&str) -> Option<String>,
+        overwrite: bool,
+    ) -> Vec<Error> {
+        let mut problems = vec![];
+        for (key, variable, _) in KEYS {
+            if !overwrite && self.get(key).ok().flatten().is_some() {
+                continue;
+            }
+            if let Some(value) = lookup(variable) {
+                if self.set(key, Some(&value)).is_err() {
+                    problems.push(Error::InvalidVariable(variable.to_string(), value));
+                }
+            }
+        }
+        problems
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut problems = vec![];
+        if let Some(key) = &self.steam_api_key {
+            if key.len() != 32 || !key.chars().all(|c| c.is_ascii_hexdigit()) {
+                problems.push("steam_api_key should be 32 hexadecimal characters".to_string());
+            }
+        }
+        if let Some(steam_id) = self.user_steam_id {
+            // steam ids of individual accounts all start with the same bits, so they're 17 digits
+            // long and start with 7656119
+            if !steam_id.to_string().starts_with("7656119") || steam_id.to_string().len() != 17 {
+                problems.push(format!(
+                    "user_steam_id {steam_id} doesn't look like a steam id, they're 17 digits long and start with 7656119"
+                ));
+            }
+        }
+        if let Some(country) = &self.country {
+            if country.len() != 2 || !country.chars().all(|c| c.is_ascii_alphabetic()) {
+                problems.push(format!(
+                    "country {country} should be a two letter country code, e.g., us or de"
+                ));
+            }
+        }
+        problems
+    }
+}
+
+/// Hide all but the end of secrets, so that config show can be shared when asking for help
+pub fn display_value(key: &str, value: &str) -> String {
+    if SECRET_KEYS.contains(&key) {
+        let hidden = value.chars().count().saturating_sub(4);
+        format!(
+            "{}{}",
+            "*".repeat(hidden.min(8)),
+            value.chars().skip(hidden).collect::<String>()
+        )
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NoConfigDirectory,
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+    UnknownProfile(String),
+    UnknownKey(String),
+    InvalidValue(String, String),
+    InvalidVariable(String, String),
+    IgnoredConfigFile(Box<Error>),
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for Error {
+    fn from(value: toml::de::Error) -> Self {
+        Error::Parse(value)
+    }
+}
+
+impl From<toml::ser::Error> for Error {
+    fn from(value: toml::ser::Error) -> Self {
+        Error::Serialize(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NoConfigDirectory => write!(f, "could not find a config directory"),
+            Error::Io(err) => write!(f, "IoError: {}", err),
+            Error::Parse(err) => write!(f, "the config file is invalid: {}", err),
+            Error::Serialize(err) => write!(f, "failed to write the config: {}", err),
+            Error::UnknownProfile(name) => write!(
+                f,
+                "there is no [profiles.{name}] in the config file, set {PROFILE_VARIABLE} to an existing profile"
+            ),
+            Error::UnknownKey(key) => write!(
+                f,
+                "unknown config key {key}, expected one of {}",
+                KEYS.map(|(key, _, _)| key).join(", ")
+            ),
+            Error::InvalidValue(key, value) => write!(f, "{value} is not a valid {key}"),
+            Error::InvalidVariable(variable, value) => {
+                write!(f, "ignoring {variable}, {value} is not a valid value for it")
+            }
+            Error::IgnoredConfigFile(err) => write!(f, "ignoring the config file: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{display_value, ConfigFile, Error, Profile};
+
+    const CONFIG: &str = r#"
+default_profile = "alt"
+
+[profiles.default]
+steam_api_key = "0123456789abcdef0123456789ABCDEF"
+user_steam_id = 76561197960287930
+country = "de"
+
+[profiles.alt]
+user_steam_id = 42
+include_family = true
+"#;
+
+    #[test]
+    fn picks_profiles_and_applies_the_environment() {
+        let config: ConfigFile = toml::from_str(CONFIG).unwrap();
+        let environment = HashMap::from([
+            ("STEAM_CLI_PROFILE", "default"),
+            ("STEAM_CLI_COUNTRY", "US"),
+        ]);
+        let lookup = |variable: &str| environment.get(variable).map(|value| value.to_string());
+
+        assert_eq!(config.profile_name(|_| None), "alt");
+        assert_eq!(config.profile_name(lookup), "default");
+        let mut profile = config.profile("default").unwrap();
+        assert!(profile.apply_environment(lookup, false).is_empty());
+        assert_eq!(profile.country.as_deref(), Some("de"));
+        assert!(profile.apply_environment(lookup, true).is_empty());
+        assert_eq!(profile.country.as_deref(), Some("us"));
+        assert_eq!(profile.user_steam_id, Some(76561197960287930));
+        assert!(config.profile("missing").is_err());
+        assert_eq!(config.profile("default").unwrap().include_family, None);
+    }
+
+    #[test]
+    fn validates_values() {
+        let config: ConfigFile = toml::from_str(CONFIG).unwrap();
+        assert_eq!(
+            config.validate(),
+            vec!["[profiles.alt] user_steam_id 42 doesn't look like a steam id, they're 17 digits long and start with 7656119"]
+        );
+        assert!(toml::from_str::<ConfigFile>("[profiles.default]\nsteam_key = \"x\"").is_err());
+        assert!(Profile::default()
+            .set("include_family", Some("maybe"))
+            .is_err());
+    }
+
+    #[test]
+    fn skips_only_bad_environment_variables() {
+        let mut profile = Profile::default();
+        let problems = profile.apply_environment(
+            |variable| match variable {
+                "STEAM_CLI_MATCH_THRESHOLD" => Some("abc".to_string()),
+                "STEAM_CLI_COUNTRY" => Some("se".to_string()),
+                _ => None,
+            },
+            true,
+        );
+        assert!(matches!(
+            problems.as_slice(),
+            [Error::InvalidVariable(variable, _)] if variable == "STEAM_CLI_MATCH_THRESHOLD"
+        ));
+        assert_eq!(profile.country.as_deref(), Some("se"));
+        assert_eq!(profile.match_threshold, None);
+    }
+
+    #[test]
+    fn hides_secrets() {
+        assert_eq!(display_value("steam_api_key", "abcdef"), "**cdef");
+        assert_eq!(display_value("discord_token", "ééééé"), "*éééé");
+        assert_eq!(display_value("country", "de"), "de");
+    }
+}
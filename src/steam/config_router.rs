@@ -0,0 +1,261 @@
+use std::{
+    env,
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use clap::{ArgMatches, Command};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use super::{
+    arg_matcher::{self, SharedArgs},
+    config::{self, config_path, display_value, load_config_file, settings, Profile, KEYS},
+    logger::FilteringLogger,
+    output::CommandOutput,
+    registry::SteamCommand,
+    router::{unknown_subcommand, Error},
+};
+
+pub struct ConfigCommand;
+
+impl SteamCommand for ConfigCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::config_command(args)
+    }
+
+    fn uses_local_files(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        _user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_config_command(arguments, logger))
+    }
+}
+
+pub async fn run_config_command<'a>(
+    arguments: &ArgMatches,
+    _logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    let path = config_path()?;
+    match arguments.subcommand() {
+        Some(("show", _)) => {
+            let config = load_config_file(&path)?;
+            let settings = settings();
+            let file_profile = config.profile(&settings.profile_name)?;
+            Ok(CommandOutput::Config {
+                path,
+                profile_name: settings.profile_name.clone(),
+                file_profile,
+                values: settings.values.clone(),
+            })
+        }
+        Some(("set", arguments)) => {
+            let key = arguments
+                .get_one::<String>("key")
+                .ok_or(Error::Argument("key is required".to_string()))?;
+            let value = arguments.get_one::<String>("value");
+            let mut config = load_config_file(&path)?;
+            let profile_name = settings().profile_name.clone();
+            let profile = config.profiles.entry(profile_name.clone()).or_default();
+            profile.set(key, value.map(String::as_str))?;
+            let problems = config.validate();
+            config.save(&path)?;
+            Ok(CommandOutput::Message(format!(
+                "{action} {key} in [profiles.{profile_name}] of {path}{problems}",
+                action = if value.is_some() { "set" } else { "unset" },
+                path = path.display(),
+                problems = compute_problems_string(&problems),
+            )))
+        }
+        Some(("validate", _)) => {
+            let mut problems = load_config_file(&path)?.validate();
+            if settings().values.steam_api_key.is_none() {
+                problems.push(
+                    "steam_api_key isn't set in the config or in STEAM_API_KEY, so every command that calls steam will fail"
+                        .to_string(),
+                );
+            }
+            Ok(if problems.is_empty() {
+                CommandOutput::Message(format!("{} is valid", path.display()))
+            } else {
+                CommandOutput::ConfigProblems(problems)
+            })
+        }
+        _ => Err(unknown_subcommand(arguments)),
+    }
+}
+
+pub struct InitCommand;
+
+impl SteamCommand for InitCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::init_command(args)
+    }
+
+    fn uses_local_files(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        _user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_init_command(arguments, logger))
+    }
+}
+
+/// Ask for each setting on the terminal and write them to the current profile of the config file
+pub async fn run_init_command<'a>(
+    arguments: &ArgMatches,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    if !io::stdin().is_terminal() {
+        return Err(Error::Argument(
+            "init asks questions on the terminal, use config set to write the config otherwise"
+                .to_string(),
+        ));
+    }
+    let path = config_path()?;
+    let mut config = load_config_file(&path)?;
+    let profile_name = settings().profile_name.clone();
+    if config.profiles.contains_key(&profile_name) && !arguments.get_flag("force") {
+        return Err(Error::Argument(format!(
+            "[profiles.{profile_name}] already exists in {}, pass --force to replace it or use config set to change single values",
+            path.display()
+        )));
+    }
+    logger.info(format!(
+        "Writing [profiles.{profile_name}] to {}. Leave anything blank to skip it",
+        path.display()
+    ));
+    let mut profile = Profile::default();
+    for (key, variable, description) in KEYS {
+        let current = env::var(variable).ok();
+        logger.info(format!(
+            "{key}: {description}{current}",
+            current = match &current {
+                Some(value) => format!(" [{}]", display_value(key, value)),
+                None => String::new(),
+            }
+        ));
+        let mut line = String::new();
+        io::stdin()
+            .read_line(&mut line)
+            .map_err(config::Error::from)?;
+        let value = match line.trim() {
+            "" => current,
+            value => Some(value.to_string()),
+        };
+        profile.set(key, value.as_deref())?;
+    }
+    config.profiles.insert(profile_name, profile);
+    let problems = config.validate();
+    config.save(&path)?;
+    Ok(CommandOutput::Message(format!(
+        "wrote {}{}",
+        path.display(),
+        compute_problems_string(&problems)
+    )))
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProblemRecord<'a> {
+    problem: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SettingRecord {
+    key: &'static str,
+    value: Option<String>,
+    /// config, or the environment variable the value came from
+    source: Option<&'static str>,
+}
+
+pub fn compute_config_records(file_profile: &Profile, resolved: &Profile) -> Vec<SettingRecord> {
+    KEYS.iter()
+        .map(|(key, variable, _)| {
+            let value = resolved.get(key).ok().flatten();
+            let from_file = file_profile.get(key).ok().flatten();
+            SettingRecord {
+                key,
+                source: value.as_ref().map(|value| {
+                    if from_file.as_ref() == Some(value) {
+                        "config"
+                    } else {
+                        *variable
+                    }
+                }),
+                value: value.map(|value| display_value(key, &value)),
+            }
+        })
+        .collect()
+}
+
+pub fn compute_problem_records(problems: &[String]) -> Vec<ProblemRecord<'_>> {
+    problems
+        .iter()
+        .map(|problem| ProblemRecord { problem })
+        .collect()
+}
+
+/// The problems found by config validate
+pub fn compute_config_problems_string(problems: &[String]) -> String {
+    format!(
+        "{problems}\n\tTotal: {total}\n",
+        problems = problems.join("\n"),
+        total = problems.len(),
+    )
+}
+
+fn compute_problems_string(problems: &[String]) -> String {
+    problems
+        .iter()
+        .map(|problem| format!("\nwarning: {problem}"))
+        .collect()
+}
+
+/// Every setting in effect and where it came from. Secrets are mostly hidden
+pub fn compute_config_string(
+    path: &Path,
+    profile_name: &str,
+    file_profile: &Profile,
+    resolved: &Profile,
+) -> String {
+    let mut lines = vec![
+        format!(
+            "config file: {}{}",
+            path.display(),
+            if path.exists() {
+                ""
+            } else {
+                " (not created yet, run init)"
+            }
+        ),
+        format!("profile: {profile_name}"),
+    ];
+    for (key, variable, _) in KEYS {
+        let value = resolved.get(key).ok().flatten();
+        let from_file = file_profile.get(key).ok().flatten();
+        lines.push(match value {
+            None => format!("{key}: not set"),
+            Some(value) => format!(
+                "{key} = {} (from {})",
+                display_value(key, &value),
+                if from_file.as_ref() == Some(&value) {
+                    "config"
+                } else {
+                    variable
+                }
+            ),
+        });
+    }
+    lines.join("\n")
+}
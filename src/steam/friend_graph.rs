@@ -0,0 +1,232 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use clap::ValueEnum;
+use serde::Serialize;
+
+/// The friends lists crawled outwards from one player. Friendships are undirected, so each edge is
+/// stored once with the smaller steam id first
+#[derive(Debug, Default)]
+pub struct FriendGraph {
+    pub root: u64,
+    /// how many hops each player is from the root
+    pub depths: BTreeMap<u64, usize>,
+    pub edges: BTreeSet<(u64, u64)>,
+    /// players whose friends list is private
+    pub private: BTreeSet<u64>,
+}
+
+impl FriendGraph {
+    pub fn new(root: u64) -> Self {
+        FriendGraph {
+            root,
+            depths: BTreeMap::from([(root, 0)]),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_friendship(&mut self, a: u64, b: u64) {
+        self.edges.insert((a.min(b), a.max(b)));
+    }
+
+    pub fn friends_of(&self, steam_id: u64) -> BTreeSet<u64> {
+        self.edges
+            .iter()
+            .filter_map(|(a, b)| match (*a == steam_id, *b == steam_id) {
+                (true, _) => Some(*b),
+                (_, true) => Some(*a),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn mutual_friends(&self, a: u64, b: u64) -> BTreeSet<u64> {
+        self.friends_of(a)
+            .intersection(&self.friends_of(b))
+            .copied()
+            .collect()
+    }
+
+    /// Every player who isn't the root, with the root's friends that they're also friends with.
+    /// Players that share the most friends with the root come first
+    pub fn mutual_friends_with_root(&self) -> Vec<(u64, BTreeSet<u64>)> {
+        let mut mutuals = self
+            .depths
+            .keys()
+            .filter(|steam_id| **steam_id != self.root)
+            .map(|steam_id| (*steam_id, self.mutual_friends(self.root, *steam_id)))
+            .collect::<Vec<_>>();
+        mutuals.sort_by(|(a, a_mutuals), (b, b_mutuals)| {
+            b_mutuals.len().cmp(&a_mutuals.len()).then(a.cmp(b))
+        });
+        mutuals
+    }
+}
+
+/// How friends graph writes out the graph as text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GraphFormat {
+    Adjacency,
+    Dot,
+    Json,
+}
+
+pub fn compute_graph_string(
+    graph: &FriendGraph,
+    format: GraphFormat,
+    name: impl Fn(u64) -> String,
+) -> Result<String, serde_json::Error> {
+    Ok(match format {
+        GraphFormat::Adjacency => compute_adjacency_string(graph, name),
+        GraphFormat::Dot => compute_dot_string(graph, name),
+        GraphFormat::Json => compute_json_string(graph, name)?,
+    })
+}
+
+/// Render the graph in the Graphviz DOT language, e.g., for `dot -Tsvg`. Players are labelled by
+/// name and private players are drawn dashed
+pub fn compute_dot_string(graph: &FriendGraph, name: impl Fn(u64) -> String) -> String {
+    let mut lines = vec!["graph friends {".to_string()];
+    for (steam_id, depth) in &graph.depths {
+        let mut attributes = vec![format!(
+            "label=\"{}\"",
+            name(*steam_id).replace('"', "\\\"")
+        )];
+        if *depth == 0 {
+            attributes.push("shape=doublecircle".to_string());
+        }
+        if graph.private.contains(steam_id) {
+            attributes.push("style=dashed".to_string());
+        }
+        lines.push(format!("  \"{steam_id}\" [{}];", attributes.join(", ")));
+    }
+    for (a, b) in &graph.edges {
+        lines.push(format!("  \"{a}\" -- \"{b}\";"));
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayerNode {
+    steamid: String,
+    name: String,
+    depth: usize,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    private: bool,
+    friends: Vec<String>,
+    mutual_friends_with_root: Vec<String>,
+}
+
+pub fn compute_json_string(
+    graph: &FriendGraph,
+    name: impl Fn(u64) -> String,
+) -> Result<String, serde_json::Error> {
+    serde_json::to_string_pretty(&compute_player_nodes(graph, name))
+}
+
+/// Every player in the graph along with their friends
+pub fn compute_player_nodes(graph: &FriendGraph, name: impl Fn(u64) -> String) -> Vec<PlayerNode> {
+    let mutuals = graph
+        .mutual_friends_with_root()
+        .into_iter()
+        .collect::<BTreeMap<_, _>>();
+    graph
+        .depths
+        .iter()
+        .map(|(steam_id, depth)| PlayerNode {
+            steamid: steam_id.to_string(),
+            name: name(*steam_id),
+            depth: *depth,
+            private: graph.private.contains(steam_id),
+            friends: graph
+                .friends_of(*steam_id)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            mutual_friends_with_root: mutuals
+                .get(steam_id)
+                .into_iter()
+                .flatten()
+                .map(ToString::to_string)
+                .collect(),
+        })
+        .collect()
+}
+
+/// One line per player listing their friends, followed by who shares the most friends with the root
+pub fn compute_adjacency_string(graph: &FriendGraph, name: impl Fn(u64) -> String) -> String {
+    let mut lines = graph
+        .depths
+        .iter()
+        .map(|(steam_id, depth)| {
+            let friends = graph
+                .friends_of(*steam_id)
+                .into_iter()
+                .map(&name)
+                .collect::<Vec<_>>();
+            format!(
+                "{player} (depth {depth}{private}): {friends}",
+                player = name(*steam_id),
+                private = if graph.private.contains(steam_id) {
+                    ", private"
+                } else {
+                    ""
+                },
+                friends = friends.join(", "),
+            )
+        })
+        .collect::<Vec<_>>();
+    let mutuals = graph
+        .mutual_friends_with_root()
+        .into_iter()
+        .filter(|(_, mutuals)| !mutuals.is_empty())
+        .map(|(steam_id, mutuals)| {
+            format!(
+                "{player}: {count} mutual friends ({friends})",
+                player = name(steam_id),
+                count = mutuals.len(),
+                friends = mutuals
+                    .into_iter()
+                    .map(&name)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )
+        })
+        .collect::<Vec<_>>();
+    if !mutuals.is_empty() {
+        lines.push(format!("\nMutual friends with {}:", name(graph.root)));
+        lines.extend(mutuals);
+    }
+    lines.push(format!(
+        "\tPlayers: {players}, friendships: {edges}, private: {private}\n",
+        players = graph.depths.len(),
+        edges = graph.edges.len(),
+        private = graph.private.len(),
+    ));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeSet;
+
+    use super::FriendGraph;
+
+    #[test]
+    fn mutual_friends_with_root() {
+        let mut graph = FriendGraph::new(1);
+        for (a, b, depth) in [(1, 2, 1), (1, 3, 1), (2, 4, 2), (4, 3, 2), (3, 5, 2)] {
+            graph.add_friendship(a, b);
+            graph.depths.entry(b).or_insert(depth);
+        }
+        assert_eq!(
+            graph.mutual_friends_with_root(),
+            vec![
+                (4, BTreeSet::from([2, 3])),
+                (5, BTreeSet::from([3])),
+                (2, BTreeSet::new()),
+                (3, BTreeSet::new()),
+            ]
+        );
+    }
+}
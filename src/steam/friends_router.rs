@@ -0,0 +1,569 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::{ArgMatches, Command};
+use futures::{future::BoxFuture, join};
+use serde::Serialize;
+
+use super::{
+    arg_matcher::{self, SharedArgs},
+    client::{self, GetUserSummariesRequest, UserSummary},
+    cliques::{find_cliques, Clique},
+    friend_graph::GraphFormat,
+    games_router::persona_name,
+    groups::{load_groups, Group, CLIQUE_GROUP_PREFIX, GROUP_PREFIX},
+    logger::FilteringLogger,
+    output::CommandOutput,
+    registry::SteamCommand,
+    router::{
+        get_include_family, get_steam_id, get_steam_ids, get_user_friends_list, unknown_subcommand,
+        Error,
+    },
+    service::{
+        audit_friends_privacy, crawl_friend_graph, find_inactive_friends, get_friend_details,
+        get_friends_libraries, get_libraries_by_player, get_library, get_user_summaries_in_batches,
+        FriendDetails, PrivacyIssue,
+    },
+    similarity::{pairwise_similarities, rank_by_similarity, Similarity, SimilarityMetric},
+};
+use crate::util::date::format_unix_date;
+
+/// Months are treated as 30 days when reporting how long ago something was
+const SECONDS_PER_MONTH: u64 = 30 * 24 * 60 * 60;
+
+pub struct FriendsCommand;
+
+impl SteamCommand for FriendsCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::friends_command(args)
+    }
+
+    /// only cliques --save writes anything, to the saved groups
+    fn uses_local_files_with(&self, arguments: &ArgMatches) -> bool {
+        matches!(arguments.subcommand(), Some(("cliques", cliques)) if cliques.get_flag("save"))
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_friends_command(arguments, user_steam_id, logger))
+    }
+}
+
+pub async fn run_friends_command<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    match arguments.subcommand() {
+        Some(("similar", arguments)) => {
+            let user_steam_id = user_steam_id.ok_or(Error::Argument(
+                "user_steam_id must be set to run this command".to_string(),
+            ))?;
+            let metric = arguments
+                .get_one::<SimilarityMetric>("by")
+                .copied()
+                .unwrap_or(SimilarityMetric::Jaccard);
+            let include_family = get_include_family(arguments);
+            let (friends_libraries, my_library) = join!(
+                get_friends_libraries(user_steam_id, include_family, logger),
+                get_library(user_steam_id, include_family, logger)
+            );
+            let mut similarities = rank_by_similarity(&my_library?, &friends_libraries?, metric);
+            if let Some(limit) = arguments.get_one::<usize>("limit") {
+                similarities.truncate(*limit);
+            }
+            let steam_ids = similarities
+                .iter()
+                .map(|similarity| similarity.steam_id)
+                .collect::<Vec<_>>();
+            let summaries = get_user_summaries_in_batches(&steam_ids, logger).await?;
+            Ok(CommandOutput::Similarities {
+                similarities,
+                summaries,
+            })
+        }
+        Some(("overlap", arguments)) => {
+            let steam_ids = get_steam_ids(arguments, user_steam_id, "steam_ids", logger).await?;
+            let libraries =
+                get_libraries_by_player(steam_ids.clone(), get_include_family(arguments), logger)
+                    .await?;
+            let summaries =
+                client::get_user_summaries(GetUserSummariesRequest { ids: steam_ids }, logger)
+                    .await?;
+            let steam_ids = libraries.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+            Ok(CommandOutput::SimilarityMatrix {
+                similarities: pairwise_similarities(&libraries),
+                steam_ids,
+                summaries,
+            })
+        }
+        Some(("cliques", arguments)) => {
+            let user_steam_id = user_steam_id.ok_or(Error::Argument(
+                "user_steam_id must be set to run this command".to_string(),
+            ))?;
+            let min_affinity = arguments
+                .get_one::<f64>("min-affinity")
+                .ok_or(Error::Argument("min-affinity is required".to_string()))?;
+            let libraries =
+                get_friends_libraries(user_steam_id, get_include_family(arguments), logger).await?;
+            let cliques = find_cliques(&libraries, *min_affinity);
+            if arguments.get_flag("save") {
+                let mut groups = load_groups()?;
+                groups.replace_cliques(cliques.iter().map(|clique| {
+                    // the cliques are made of friends, but the groups are for playing with them
+                    let mut members = vec![user_steam_id];
+                    members.extend(&clique.members);
+                    (
+                        clique.name.clone(),
+                        Group {
+                            members,
+                            ..Default::default()
+                        },
+                    )
+                }));
+                groups.save()?;
+            }
+            let steam_ids = cliques
+                .iter()
+                .flat_map(|clique| clique.members.iter().copied())
+                .collect::<Vec<_>>();
+            let summaries = get_user_summaries_in_batches(&steam_ids, logger).await?;
+            Ok(CommandOutput::Cliques {
+                cliques,
+                summaries,
+                saved: arguments.get_flag("save"),
+            })
+        }
+        Some(("graph", arguments)) => {
+            let root = match get_steam_id(arguments, user_steam_id, "steam_ids", logger).await? {
+                Some(root) => root,
+                None => user_steam_id.ok_or(Error::Argument(
+                    "either a steam id or user_steam_id is required".to_string(),
+                ))?,
+            };
+            let depth = arguments
+                .get_one::<usize>("depth")
+                .ok_or(Error::Argument("depth is required".to_string()))?;
+            let concurrency = arguments
+                .get_one::<usize>("concurrency")
+                .ok_or(Error::Argument("concurrency is required".to_string()))?;
+            let graph = crawl_friend_graph(root, *depth, *concurrency, logger).await?;
+            let summaries = get_user_summaries_in_batches(
+                &graph.depths.keys().copied().collect::<Vec<_>>(),
+                logger,
+            )
+            .await?;
+            Ok(CommandOutput::FriendGraph {
+                graph,
+                summaries,
+                format: arguments
+                    .get_one::<GraphFormat>("format")
+                    .copied()
+                    .unwrap_or(GraphFormat::Adjacency),
+            })
+        }
+        Some(("list", arguments)) => {
+            let user_steam_id = user_steam_id.ok_or(Error::Argument(
+                "user_steam_id must be set to run this command".to_string(),
+            ))?;
+            let mut friends = get_friend_details(user_steam_id, logger).await?;
+            friends.sort_by_key(|friend| (friend.friend.friend_since, friend.steam_id));
+            if arguments.get_flag("newest-first") {
+                friends.reverse();
+            }
+            if let Some(limit) = arguments.get_one::<usize>("limit") {
+                friends.truncate(*limit);
+            }
+            Ok(CommandOutput::FriendsSince {
+                friends,
+                now: now(),
+            })
+        }
+        Some(("inactive", arguments)) => {
+            let user_steam_id = user_steam_id.ok_or(Error::Argument(
+                "user_steam_id must be set to run this command".to_string(),
+            ))?;
+            let months = arguments
+                .get_one::<u64>("months")
+                .ok_or(Error::Argument("months is required".to_string()))?;
+            let friends = get_friend_details(user_steam_id, logger).await?;
+            let now = now();
+            let (inactive, unknown) =
+                find_inactive_friends(&friends, now.saturating_sub(months * SECONDS_PER_MONTH));
+            Ok(CommandOutput::InactiveFriends {
+                inactive: inactive.into_iter().cloned().collect(),
+                unknown: unknown.into_iter().cloned().collect(),
+                now,
+            })
+        }
+        Some(("privacy", _)) => {
+            let user_steam_id = user_steam_id.ok_or(Error::Argument(
+                "user_steam_id must be set to run this command".to_string(),
+            ))?;
+            let (total, issues) = audit_friends_privacy(user_steam_id, logger).await?;
+            Ok(CommandOutput::PrivacyIssues { issues, total })
+        }
+        // friends used to be an alias of get-user-friends-list, so it still lists them on its own
+        None => get_user_friends_list(arguments, user_steam_id, logger).await,
+        _ => Err(unknown_subcommand(arguments)),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |now| now.as_secs())
+}
+
+fn friend_name(friend: &FriendDetails) -> String {
+    friend
+        .summary
+        .as_ref()
+        .map_or(friend.steam_id.to_string(), |summary| {
+            summary.personaname.clone()
+        })
+}
+
+fn months_since(timestamp: u64, now: u64) -> u64 {
+    now.saturating_sub(timestamp) / SECONDS_PER_MONTH
+}
+
+/// The date of the timestamp, or None for 0 since that's what steam uses when it doesn't know
+fn optional_date(timestamp: u64) -> Option<String> {
+    (timestamp != 0).then(|| format_unix_date(timestamp))
+}
+
+#[derive(Debug, Serialize)]
+pub struct FriendRecord {
+    steamid: u64,
+    name: String,
+    friend_since: Option<String>,
+    months: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InactiveFriendRecord {
+    steamid: u64,
+    name: String,
+    /// None if it's unknown, usually because the profile is private
+    last_online: Option<String>,
+    months: Option<u64>,
+    friend_since: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrivacyRecord {
+    steamid: u64,
+    name: String,
+    issue: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SimilarityRecord {
+    steamid: u64,
+    name: String,
+    shared_games: usize,
+    jaccard: f64,
+    playtime: f64,
+    recent: f64,
+}
+
+/// One cell of the overlap matrix
+#[derive(Debug, Serialize)]
+pub struct OverlapRecord {
+    player: String,
+    #[serde(flatten)]
+    similarity: SimilarityRecord,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CliqueRecord<'a> {
+    name: &'a str,
+    affinity: f64,
+    members: Vec<String>,
+    top_games: Vec<&'a str>,
+}
+
+fn compute_similarity_record(
+    similarity: &Similarity,
+    summaries: &[UserSummary],
+) -> SimilarityRecord {
+    SimilarityRecord {
+        steamid: similarity.steam_id,
+        name: persona_name(summaries, similarity.steam_id),
+        shared_games: similarity.shared_games,
+        jaccard: similarity.jaccard,
+        playtime: similarity.playtime,
+        recent: similarity.recent,
+    }
+}
+
+pub fn compute_similarity_records(
+    similarities: &[Similarity],
+    summaries: &[UserSummary],
+) -> Vec<SimilarityRecord> {
+    similarities
+        .iter()
+        .map(|similarity| compute_similarity_record(similarity, summaries))
+        .collect()
+}
+
+/// One record per cell of the overlap matrix, row by row
+pub fn compute_overlap_records(
+    steam_ids: &[u64],
+    similarities: &[Vec<Similarity>],
+    summaries: &[UserSummary],
+) -> Vec<OverlapRecord> {
+    steam_ids
+        .iter()
+        .zip(similarities)
+        .flat_map(|(steam_id, row)| {
+            let player = persona_name(summaries, *steam_id);
+            row.iter().map(move |similarity| OverlapRecord {
+                player: player.clone(),
+                similarity: compute_similarity_record(similarity, summaries),
+            })
+        })
+        .collect()
+}
+
+pub fn compute_clique_records<'a>(
+    cliques: &'a [Clique],
+    summaries: &[UserSummary],
+) -> Vec<CliqueRecord<'a>> {
+    cliques
+        .iter()
+        .map(|clique| CliqueRecord {
+            name: &clique.name,
+            affinity: clique.affinity,
+            members: clique
+                .members
+                .iter()
+                .map(|id| persona_name(summaries, *id))
+                .collect(),
+            top_games: clique
+                .top_games
+                .iter()
+                .map(|game| game.name.as_str())
+                .collect(),
+        })
+        .collect()
+}
+
+pub fn compute_friend_records(friends: &[FriendDetails], now: u64) -> Vec<FriendRecord> {
+    friends
+        .iter()
+        .map(|friend| FriendRecord {
+            steamid: friend.steam_id,
+            name: friend_name(friend),
+            friend_since: optional_date(friend.friend.friend_since),
+            months: (friend.friend.friend_since != 0)
+                .then(|| months_since(friend.friend.friend_since, now)),
+        })
+        .collect()
+}
+
+/// The inactive friends followed by the ones whose last time online is unknown
+pub fn compute_inactive_friend_records(
+    inactive: &[FriendDetails],
+    unknown: &[FriendDetails],
+    now: u64,
+) -> Vec<InactiveFriendRecord> {
+    inactive
+        .iter()
+        .chain(unknown)
+        .map(|friend| {
+            let last_online = friend
+                .summary
+                .as_ref()
+                .and_then(|summary| summary.lastlogoff);
+            InactiveFriendRecord {
+                steamid: friend.steam_id,
+                name: friend_name(friend),
+                last_online: last_online.and_then(optional_date),
+                months: last_online.map(|last_online| months_since(last_online, now)),
+                friend_since: optional_date(friend.friend.friend_since),
+            }
+        })
+        .collect()
+}
+
+pub fn compute_privacy_records(issues: &[(FriendDetails, PrivacyIssue)]) -> Vec<PrivacyRecord> {
+    issues
+        .iter()
+        .map(|(friend, issue)| PrivacyRecord {
+            steamid: friend.steam_id,
+            name: friend_name(friend),
+            issue: issue.to_string(),
+        })
+        .collect()
+}
+
+pub fn compute_friends_since_string(friends: &[FriendDetails], now: u64) -> String {
+    format!(
+        "{friends}\n\tTotal: {total}\n",
+        friends = friends
+            .iter()
+            .map(|friend| match friend.friend.friend_since {
+                // steam doesn't know when some very old friendships started
+                0 => format!(
+                    "{}: friends since before steam kept track",
+                    friend_name(friend)
+                ),
+                friend_since => format!(
+                    "{name}: friends since {date} ({months} months)",
+                    name = friend_name(friend),
+                    date = format_unix_date(friend_since),
+                    months = months_since(friend_since, now),
+                ),
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = friends.len(),
+    )
+}
+
+pub fn compute_inactive_friends_string(
+    inactive: &[&FriendDetails],
+    unknown: &[&FriendDetails],
+    now: u64,
+) -> String {
+    let mut lines = inactive
+        .iter()
+        .map(|friend| {
+            let last_online = friend
+                .summary
+                .as_ref()
+                .and_then(|summary| summary.lastlogoff)
+                .unwrap_or_default();
+            format!(
+                "{name}: last online {date} ({months} months ago){since}",
+                name = friend_name(friend),
+                date = format_unix_date(last_online),
+                months = months_since(last_online, now),
+                since = match friend.friend.friend_since {
+                    0 => String::new(),
+                    friend_since => format!(", friends since {}", format_unix_date(friend_since)),
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!("\tTotal: {}", inactive.len()));
+    if !unknown.is_empty() {
+        lines.push(format!(
+            "\tLast time online unknown, usually because the profile is private: {}",
+            unknown
+                .iter()
+                .map(|friend| friend_name(friend))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+pub fn compute_privacy_string(issues: &[(FriendDetails, PrivacyIssue)], total: usize) -> String {
+    format!(
+        "{friends}\n\t{count} of {total} friends' libraries can't be read\n",
+        friends = issues
+            .iter()
+            .map(|(friend, issue)| format!("{}: {issue}", friend_name(friend)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        count = issues.len(),
+    )
+}
+
+pub fn compute_similar_friends_string(
+    similarities: &[Similarity],
+    summaries: &[UserSummary],
+) -> String {
+    format!(
+        "{friends}\n\tTotal: {total}\n",
+        friends = similarities
+            .iter()
+            .map(|similarity| format!(
+                "{name}: {shared} games in common, {jaccard:.0}% library overlap, {playtime:.0}% playtime overlap",
+                name = persona_name(summaries, similarity.steam_id),
+                shared = similarity.shared_games,
+                jaccard = similarity.jaccard * 100.0,
+                playtime = similarity.playtime * 100.0,
+            ))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = similarities.len(),
+    )
+}
+
+pub fn compute_cliques_string(
+    cliques: &[Clique],
+    summaries: &[UserSummary],
+    saved: bool,
+) -> String {
+    format!(
+        "{cliques}\n\tTotal: {total}\n",
+        cliques = cliques
+            .iter()
+            .map(|clique| format!(
+                "{prefix}{name} ({affinity:.0}% affinity): {members}\n\ttop games: {games}",
+                prefix = if saved {
+                    format!("{GROUP_PREFIX}{CLIQUE_GROUP_PREFIX}")
+                } else {
+                    String::new()
+                },
+                name = clique.name,
+                affinity = clique.affinity * 100.0,
+                members = clique
+                    .members
+                    .iter()
+                    .map(|id| persona_name(summaries, *id))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                games = clique
+                    .top_games
+                    .iter()
+                    .map(|game| game.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = cliques.len(),
+    )
+}
+
+/// A markdown table where each cell holds the number of games the two players have in common and
+/// how much of their libraries overlap
+pub fn compute_overlap_matrix_string(
+    steam_ids: &[u64],
+    similarities: &[Vec<Similarity>],
+    summaries: &[UserSummary],
+) -> String {
+    let player_names = steam_ids
+        .iter()
+        .map(|id| persona_name(summaries, *id))
+        .collect::<Vec<_>>();
+    let header = format!("| | {} |", player_names.join(" | "));
+    let separator = format!("| --- |{}", " --- |".repeat(player_names.len()));
+    let rows = player_names.iter().zip(similarities).map(|(name, row)| {
+        let cells = row
+            .iter()
+            .map(|similarity| {
+                format!(
+                    "{} ({:.0}%)",
+                    similarity.shared_games,
+                    similarity.jaccard * 100.0
+                )
+            })
+            .collect::<Vec<_>>();
+        format!("| {} | {} |", name, cells.join(" | "))
+    });
+    format!(
+        "{header}\n{separator}\n{rows}\n",
+        rows = rows.collect::<Vec<_>>().join("\n"),
+    )
+}
@@ -1,118 +1,1177 @@
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 // TODO: the arg_matcher, router and games_router files should all be moved into their own
 // submodule
-use clap::ArgMatches;
-use futures::future::join_all;
+use clap::{parser::ValueSource, ArgMatches, Command};
+use futures::{future::BoxFuture, join};
+use serde::Serialize;
+use serde_json::{Map, Value};
 
-use crate::steam::{client::GameInfo, models::Game, service::games_missing_from_group};
+use crate::steam::{
+    models::{Game, OwnedGame},
+    service::games_missing_from_group,
+};
 
 use super::{
-    client::{self, GetGameInfoResponse},
+    app_types::{filter_games_by_type, get_app_types, GAME_TYPE},
+    arg_matcher::{self, SharedArgs},
+    client::{self, GetUserSummariesRequest, UserSummary},
+    groups::{group_name, load_groups},
     logger::FilteringLogger,
-    router::{get_steam_ids, Error},
-    service::{filter_games, find_games_in_common},
+    output::{CommandOutput, ListedGame},
+    playtime_filter::PlaytimeFilter,
+    purchase_optimizer::{self, OptimizeMode},
+    recommender::{self, Recommendation, ScoringContext},
+    registry::SteamCommand,
+    router::{
+        get_country, get_include_family, get_steam_id, get_steam_ids, unknown_subcommand, Error,
+    },
+    service::{
+        filter_games, filter_games_by_metadata, find_borrowers,
+        find_games_in_common_from_libraries, find_games_missing_from_each_member,
+        find_games_owned_or_wishlisted_by_all, find_games_playable_together,
+        find_games_playable_together_in_libraries, find_linked_owners, format_amount,
+        get_game_metadata, get_game_metadata_from_appdetails, get_game_prices,
+        get_libraries_by_player, get_library, get_owned_games_by_player, get_ownership_matrix,
+        total_price, GameMetadata, OwnershipCell, OwnershipMatrix, PlayableTogether, Price,
+    },
+    sorting::{sort_games, SortContext, SortKey},
 };
 
+/// How many games recommend lists when --limit isn't set
+const DEFAULT_RECOMMENDATIONS: usize = 10;
+
+pub struct GamesCommand;
+
+impl SteamCommand for GamesCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::games_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_games_command(arguments, user_steam_id, logger))
+    }
+}
+
 pub async fn run_games_command<'a>(
     arguments: &ArgMatches,
     user_steam_id: Option<u64>,
     logger: &'a FilteringLogger<'a>,
-) -> Result<String, Error> {
-    let games = match arguments.subcommand() {
+) -> Result<CommandOutput, Error> {
+    let filter = get_filter(arguments, logger)?;
+    if let Some((name, _)) = arguments.subcommand() {
+        reject_unsupported_list_options(arguments, name)?;
+    }
+    // the libraries are kept around so that the output can mark which games are borrowed
+    let (games, libraries) = match arguments.subcommand() {
         Some(("in-common", arguments)) => {
             let steam_ids = get_steam_ids(arguments, user_steam_id, "steam_ids", logger).await?;
-            find_games_in_common(steam_ids, logger).await?
+            let libraries =
+                get_libraries_by_player(steam_ids, get_include_family(arguments), logger).await?;
+            let mut games = find_games_in_common_from_libraries(&libraries);
+            let playtime_filter =
+                get_playtime_filter(arguments, user_steam_id, &libraries, logger).await?;
+            games.retain(|game| playtime_filter.matches(game, &libraries));
+            (games, libraries)
         }
         Some(("missing-from-group", arguments)) => {
-            let focus_steam_id = get_steam_ids(arguments, user_steam_id, "focus_steam_id", logger)
+            let focus_steam_id = get_steam_id(arguments, user_steam_id, "focus_steam_id", logger)
                 .await?
-                .first()
-                .ok_or(Error::Argument("could not find focus_steam_id".to_string()))?
-                .to_owned();
+                .ok_or(Error::Argument("could not find focus_steam_id".to_string()))?;
             let other_steam_ids =
                 get_steam_ids(arguments, user_steam_id, "steam_ids", logger).await?;
 
-            games_missing_from_group(focus_steam_id, other_steam_ids, logger).await?
+            let include_family = get_include_family(arguments);
+            let (focus_library, other_libraries) = join!(
+                get_library(focus_steam_id, include_family, logger),
+                get_libraries_by_player(other_steam_ids, include_family, logger)
+            );
+            let other_libraries = other_libraries?;
+            let mut games = games_missing_from_group(&focus_library?, &other_libraries);
+            let playtime_filter =
+                get_playtime_filter(arguments, user_steam_id, &other_libraries, logger).await?;
+            games.retain(|game| playtime_filter.matches(game, &other_libraries));
+            (games, other_libraries)
         }
-        _ => {
-            panic!("no subcommand matched")
+        Some(("matrix", matrix_arguments)) => {
+            let steam_ids =
+                get_steam_ids(matrix_arguments, user_steam_id, "steam_ids", logger).await?;
+            let mut matrix = get_ownership_matrix(
+                steam_ids.clone(),
+                get_include_family(matrix_arguments),
+                logger,
+            )
+            .await?;
+            let filtered_games =
+                apply_filter_argument(filter.as_deref(), matrix.games().cloned(), logger).await?;
+            matrix.retain_games(&filtered_games);
+            let summaries = client::get_user_summaries(
+                GetUserSummariesRequest {
+                    ids: steam_ids
+                        .into_iter()
+                        .chain(matrix.linked_accounts())
+                        .collect(),
+                },
+                logger,
+            )
+            .await?;
+            let show_hours = matrix_arguments.get_flag("hours");
+            return Ok(CommandOutput::OwnershipMatrix {
+                matrix,
+                summaries,
+                show_hours,
+            });
         }
-    };
-    let filtered_games = match arguments.get_one::<String>("filter") {
-        None => games,
-        Some(filter) => {
-            let filter_numbers = HashSet::from_iter(
-                match filter.as_str() {
-                    "multiplayer" => [27, 36, 38].iter(),
-                    "controller" => [28].iter(),
-                    _ => panic!(),
+        Some(("cost-to-complete", cost_arguments)) => {
+            let steam_ids =
+                get_steam_ids(cost_arguments, user_steam_id, "steam_ids", logger).await?;
+            let missing_games = find_games_missing_from_each_member(
+                steam_ids.clone(),
+                get_include_family(cost_arguments),
+                logger,
+            )
+            .await?;
+            let filtered_games = apply_filter_argument(
+                filter.as_deref(),
+                missing_games
+                    .iter()
+                    .flat_map(|(_, games)| games.iter().cloned())
+                    .collect::<HashSet<_>>(),
+                logger,
+            )
+            .await?;
+            let country_code = &get_country(cost_arguments)?;
+            let prices = get_game_prices(filtered_games.iter(), country_code, logger).await;
+            let summaries =
+                client::get_user_summaries(GetUserSummariesRequest { ids: steam_ids }, logger)
+                    .await?;
+            let missing_games = missing_games
+                .into_iter()
+                .map(|(id, games)| {
+                    let mut games = games
+                        .into_iter()
+                        .filter(|game| filtered_games.contains(game))
+                        .collect::<Vec<_>>();
+                    games.sort_by(|a, b| a.name.cmp(&b.name));
+                    (id, games)
+                })
+                .collect::<Vec<_>>();
+            return Ok(CommandOutput::CostToComplete {
+                missing_games,
+                prices,
+                summaries,
+            });
+        }
+        Some(("optimize-purchases", optimize_arguments)) => {
+            let steam_ids =
+                get_steam_ids(optimize_arguments, user_steam_id, "steam_ids", logger).await?;
+            let max_missing = optimize_arguments
+                .get_one::<usize>("max-missing")
+                .ok_or(Error::Argument("max-missing is required".to_string()))?;
+            let libraries = get_owned_games_by_player(steam_ids.clone(), logger).await?;
+            let candidates = purchase_optimizer::find_candidates(&libraries, *max_missing);
+            let filtered_games = apply_filter_argument(
+                filter.as_deref(),
+                candidates.iter().map(|(game, _)| game.clone()),
+                logger,
+            )
+            .await?;
+            let country_code = &get_country(optimize_arguments)?;
+            let prices = get_game_prices(filtered_games.iter(), country_code, logger).await;
+            let candidates = candidates
+                .into_iter()
+                .filter(|(game, _)| filtered_games.contains(game))
+                .filter_map(|(game, buyers)| match prices.get(&game.appid) {
+                    Some(Price::Unavailable) | None => {
+                        logger.trace(format!("skipping {game} since it has no price"));
+                        None
+                    }
+                    Some(price) => Some(purchase_optimizer::Candidate {
+                        unit_price: price.final_price(),
+                        game,
+                        buyers,
+                    }),
+                })
+                .collect::<Vec<_>>();
+            let constraints = purchase_optimizer::Constraints {
+                budget: optimize_arguments.get_one::<u64>("budget").copied(),
+                max_purchases: optimize_arguments
+                    .get_one::<usize>("max-purchases")
+                    .copied(),
+            };
+            let mode = optimize_arguments
+                .get_one::<OptimizeMode>("mode")
+                .copied()
+                .unwrap_or(OptimizeMode::Greedy);
+            let chosen = match mode {
+                OptimizeMode::Exact => {
+                    if candidates.len() > purchase_optimizer::MAX_EXACT_CANDIDATES {
+                        return Err(Error::Argument(format!(
+                            "exact mode supports at most {} candidate games but found {}. Narrow them down with --filter or --max-missing, or use --mode greedy",
+                            purchase_optimizer::MAX_EXACT_CANDIDATES,
+                            candidates.len()
+                        )));
+                    }
+                    purchase_optimizer::optimize_exactly(candidates, &constraints)
+                }
+                OptimizeMode::Greedy => {
+                    purchase_optimizer::optimize_greedily(candidates, &constraints)
                 }
-                .cloned(),
+            };
+            // every price was looked up in the same country, so the chosen games share a currency
+            let currency = chosen
+                .iter()
+                .find_map(|candidate| prices.get(&candidate.game.appid).and_then(price_currency))
+                .unwrap_or_default()
+                .to_string();
+            let summaries =
+                client::get_user_summaries(GetUserSummariesRequest { ids: steam_ids }, logger)
+                    .await?;
+            return Ok(CommandOutput::PurchasePlan {
+                games_in_common: find_games_in_common_from_libraries(&libraries).len(),
+                chosen,
+                currency,
+                summaries,
+            });
+        }
+        Some(("playable-together", playable_arguments)) => {
+            let steam_ids =
+                get_steam_ids(playable_arguments, user_steam_id, "steam_ids", logger).await?;
+            let mut playable = find_games_playable_together(
+                steam_ids.clone(),
+                get_include_family(playable_arguments),
+                logger,
+            )
+            .await?;
+            let filtered_games = apply_filter_argument(
+                filter.as_deref(),
+                playable
+                    .in_common
+                    .iter()
+                    .chain(playable.remote_play.iter().map(|(game, _)| game))
+                    .cloned(),
+                logger,
+            )
+            .await?;
+            playable
+                .in_common
+                .retain(|game| filtered_games.contains(game));
+            playable
+                .remote_play
+                .retain(|(game, _)| filtered_games.contains(game));
+            let summaries =
+                client::get_user_summaries(GetUserSummariesRequest { ids: steam_ids }, logger)
+                    .await?;
+            return Ok(CommandOutput::PlayableTogether {
+                playable,
+                summaries,
+            });
+        }
+        Some(("recommend", recommend_arguments)) => {
+            let steam_ids =
+                get_steam_ids(recommend_arguments, user_steam_id, "steam_ids", logger).await?;
+            let libraries =
+                get_libraries_by_player(steam_ids, get_include_family(recommend_arguments), logger)
+                    .await?;
+            let (games, hosts_by_game) = if recommend_arguments.get_flag("remote-play") {
+                let playable =
+                    find_games_playable_together_in_libraries(&libraries, logger).await?;
+                let hosts_by_game = playable.remote_play.into_iter().collect::<HashMap<_, _>>();
+                (
+                    playable
+                        .in_common
+                        .into_iter()
+                        .chain(hosts_by_game.keys().cloned())
+                        .collect::<HashSet<_>>(),
+                    hosts_by_game,
+                )
+            } else {
+                (
+                    find_games_in_common_from_libraries(&libraries),
+                    HashMap::new(),
+                )
+            };
+            let games = filter_by_type_argument(arguments, games, logger).await;
+            let metadata =
+                get_game_metadata(games.iter().map(|game| game.appid), None, false, logger).await;
+            let games = filter_by_argument(filter.as_deref(), games, &metadata, logger)?;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs());
+            let mut recommendations = recommender::recommend(
+                games,
+                &ScoringContext {
+                    libraries: &libraries,
+                    metadata: &metadata,
+                    now,
+                },
             );
-            let filtered_games = filter_games(games.to_owned(), filter_numbers, logger).await?;
-            HashSet::from_iter(filtered_games.iter().cloned())
+            let limit = arguments.get_one::<usize>("limit").copied();
+            let seed = if recommend_arguments.get_flag("random") {
+                // the day rolls over at noon UTC, so the seed stays the same through an evening
+                // in both Europe and the Americas
+                let seed = recommend_arguments
+                    .get_one::<u64>("seed")
+                    .copied()
+                    .unwrap_or(now.saturating_sub(12 * 60 * 60) / (24 * 60 * 60));
+                recommendations =
+                    recommender::pick_randomly(recommendations, limit.unwrap_or(1), seed);
+                Some(seed)
+            } else {
+                recommendations.truncate(limit.unwrap_or(DEFAULT_RECOMMENDATIONS));
+                None
+            };
+            if !hosts_by_game.is_empty() {
+                let summaries = client::get_user_summaries(
+                    GetUserSummariesRequest {
+                        ids: libraries.iter().map(|(id, _)| *id).collect(),
+                    },
+                    logger,
+                )
+                .await?;
+                for recommendation in recommendations.iter_mut() {
+                    if let Some(hosts) = hosts_by_game.get(&recommendation.game) {
+                        recommendation.reasons.push(format!(
+                            "Remote Play Together hosted by {}",
+                            hosts
+                                .iter()
+                                .map(|id| persona_name(&summaries, *id))
+                                .collect::<Vec<_>>()
+                                .join(" or ")
+                        ));
+                    }
+                }
+            }
+            return Ok(CommandOutput::Recommendations {
+                recommendations,
+                seed,
+            });
         }
+        Some(("own-or-wishlist", arguments)) => {
+            let steam_ids = get_steam_ids(arguments, user_steam_id, "steam_ids", logger).await?;
+            (
+                find_games_owned_or_wishlisted_by_all(steam_ids, logger).await?,
+                vec![],
+            )
+        }
+        _ => return Err(unknown_subcommand(arguments)),
+    };
+    let sort_key = arguments
+        .get_one::<SortKey>("sort")
+        .copied()
+        .unwrap_or(SortKey::Name);
+    let descending = match arguments.get_one::<String>("order").map(String::as_str) {
+        Some("asc") => false,
+        Some("desc") => true,
+        _ => sort_key.descending_by_default(),
     };
-    if !arguments.get_flag("info") {
-        Ok(compute_sorted_games_string(filtered_games))
+    let show_info = arguments.get_flag("info");
+    let games = filter_by_type_argument(arguments, games, logger).await;
+
+    // the metadata is fetched once up front so that filtering, sorting and --info can all share it
+    let appids = games.iter().map(|game| game.appid);
+    let mut metadata = if sort_key == SortKey::Metacritic {
+        get_game_metadata_from_appdetails(appids, None, logger).await
+    } else if show_info || sort_key.needs_metadata() || filter.is_some() {
+        get_game_metadata(appids, None, show_info, logger).await
     } else {
-        // TODO: this get_game_info call should be able to take all of them at once
-        let game_infos: Vec<Result<client::GetGameInfoResponse, client::Error>> = join_all(
+        HashMap::new()
+    };
+    let mut filtered_games = filter_by_argument(filter.as_deref(), games, &metadata, logger)?
+        .into_iter()
+        .collect::<Vec<_>>();
+    let my_library = match (sort_key, user_steam_id) {
+        (SortKey::MyPlaytime, Some(user_steam_id)) => {
+            match libraries.iter().find(|(id, _)| *id == user_steam_id) {
+                Some((_, games)) => Some(games.clone()),
+                None => Some(get_library(user_steam_id, false, logger).await?),
+            }
+        }
+        _ => None,
+    };
+    sort_games(
+        &mut filtered_games,
+        sort_key,
+        descending,
+        &SortContext {
+            libraries: &libraries,
+            my_library: my_library.as_deref(),
+            metadata: &metadata,
+        },
+    );
+    if let Some(limit) = arguments.get_one::<usize>("limit") {
+        filtered_games.truncate(*limit);
+    }
+
+    if show_info {
+        return Ok(CommandOutput::GameDetails(
             filtered_games
-                .iter()
-                .map(|game| client::get_game_info(&game.appid, logger)),
+                .into_iter()
+                .map(|game| {
+                    let metadata = metadata.remove(&game.appid);
+                    (game, metadata)
+                })
+                .collect(),
+        ));
+    }
+    let borrowers = find_borrowers(&libraries);
+    let linked_owners = find_linked_owners(&libraries);
+    let summaries = if borrowers.is_empty() && linked_owners.is_empty() {
+        vec![]
+    } else {
+        client::get_user_summaries(
+            GetUserSummariesRequest {
+                ids: libraries
+                    .iter()
+                    .map(|(id, _)| *id)
+                    .chain(linked_owners.values().flatten().copied())
+                    .collect(),
+            },
+            logger,
         )
-        .await
+        .await?
+    };
+    let names = |ids: Option<&Vec<u64>>| {
+        ids.into_iter()
+            .flatten()
+            .map(|id| persona_name(&summaries, *id))
+            .collect::<Vec<_>>()
+    };
+    Ok(CommandOutput::Games(
+        filtered_games
+            .into_iter()
+            .map(|game| ListedGame {
+                borrowed_by: names(borrowers.get(&game)),
+                owned_on: names(linked_owners.get(&game)),
+                game,
+            })
+            .collect(),
+    ))
+}
+
+/// Build the playtime filter from the arguments of in-common or missing-from-group. The played by
+/// player's library is reused from the group when they're in it
+async fn get_playtime_filter<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    libraries: &[(u64, Vec<OwnedGame>)],
+    logger: &'a FilteringLogger<'a>,
+) -> Result<PlaytimeFilter, Error> {
+    let played_by = match arguments.get_one::<String>("played-by") {
+        None => None,
+        Some(_) => {
+            let played_by_id = get_steam_id(arguments, user_steam_id, "played-by", logger)
+                .await?
+                .ok_or(Error::Argument("could not find played-by".to_string()))?;
+            Some(match libraries.iter().find(|(id, _)| *id == played_by_id) {
+                Some((_, games)) => games.clone(),
+                None => get_library(played_by_id, false, logger).await?,
+            })
+        }
+    };
+    let not_played_since = arguments
+        .get_one::<Duration>("not-played-since")
+        .map(|duration| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |now| now.as_secs())
+                .saturating_sub(duration.as_secs())
+        });
+    Ok(PlaytimeFilter {
+        unplayed_by_all: arguments.get_flag("unplayed-by-all"),
+        min_minutes_each: arguments
+            .get_one::<f64>("min-hours-each")
+            .map(|hours| (hours * 60.0).ceil() as u64),
+        played_by,
+        not_played_since,
+    })
+}
+
+/// Filter out soundtracks, demos, tools and other apps that aren't games, unless their types were
+/// opted back in with the `--include-type` argument
+async fn filter_by_type_argument<'a>(
+    arguments: &ArgMatches,
+    games: HashSet<Game>,
+    logger: &'a FilteringLogger<'a>,
+) -> HashSet<Game> {
+    let mut included_types = HashSet::from([GAME_TYPE.to_string()]);
+    included_types.extend(
+        arguments
+            .get_many::<String>("include-type")
+            .into_iter()
+            .flatten()
+            .map(|app_type| app_type.trim().to_lowercase()),
+    );
+    if included_types.contains("all") {
+        return games;
+    }
+    let types = get_app_types(games.iter().map(|game| game.appid), logger).await;
+    HashSet::from_iter(filter_games_by_type(games, &types, &included_types))
+}
+
+/// The `--filter` argument on the games command. If it wasn't provided, then the default filter of
+/// the first group passed as players that has one is used instead
+fn get_filter<'a>(
+    arguments: &ArgMatches,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Option<String>, Error> {
+    if let Some(filter) = arguments.get_one::<String>("filter") {
+        return Ok(Some(filter.clone()));
+    }
+    let Some((_, subcommand_arguments)) = arguments.subcommand() else {
+        return Ok(None);
+    };
+    // not every subcommand takes both, so missing arguments are skipped rather than unwrapped
+    let group_names = ["focus_steam_id", "steam_ids"]
         .into_iter()
+        .flat_map(|key| {
+            subcommand_arguments
+                .try_get_many::<String>(key)
+                .ok()
+                .flatten()
+                .into_iter()
+                .flatten()
+        })
+        .filter_map(|argument| group_name(argument))
+        .collect::<Vec<_>>();
+    if group_names.is_empty() {
+        return Ok(None);
+    }
+    let groups = load_groups()?;
+    for name in group_names {
+        if let Some(filter) = groups.default_filter(name)? {
+            logger.trace(format!("using the {filter} filter of @{name}"));
+            return Ok(Some(filter.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Filter the games down to the categories selected by the `--filter` argument on the games
+/// command. If no filter was provided, then all of the games are returned
+pub async fn apply_filter_argument<'a>(
+    filter: Option<&str>,
+    games: impl IntoIterator<Item = Game>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<HashSet<Game>, Error> {
+    match get_filter_categories(filter)? {
+        None => Ok(HashSet::from_iter(games)),
+        Some(filter_numbers) => {
+            let filtered_games = filter_games(games, filter_numbers, logger).await?;
+            Ok(HashSet::from_iter(filtered_games))
+        }
+    }
+}
+
+/// Same as apply_filter_argument, but using metadata that has already been fetched
+fn filter_by_argument<'a>(
+    filter: Option<&str>,
+    games: impl IntoIterator<Item = Game>,
+    metadata: &HashMap<u64, GameMetadata>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<HashSet<Game>, Error> {
+    match get_filter_categories(filter)? {
+        None => Ok(HashSet::from_iter(games)),
+        Some(filter_numbers) => Ok(HashSet::from_iter(filter_games_by_metadata(
+            games,
+            &filter_numbers,
+            metadata,
+            logger,
+        ))),
+    }
+}
+
+fn get_filter_categories(filter: Option<&str>) -> Result<Option<HashSet<u8>>, Error> {
+    match filter {
+        None => Ok(None),
+        Some(filter) => Ok(Some(HashSet::from_iter(
+            match filter {
+                "multiplayer" => [27, 36, 38].iter(),
+                "controller" => [28].iter(),
+                other => {
+                    return Err(Error::Argument(format!(
+                        "unknown filter: {other}, expected multiplayer or controller"
+                    )))
+                }
+            }
+            .cloned(),
+        ))),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GameInfoRecord<'a> {
+    appid: u64,
+    name: &'a str,
+    platforms: Vec<&'static str>,
+    review: Option<&'a str>,
+    percent_positive: Option<u8>,
+    review_count: Option<u64>,
+    price: Option<f64>,
+    currency: Option<&'a str>,
+    discount_percent: u8,
+    requirements: Option<&'a str>,
+}
+
+/// Only the subcommands that list games can sort and limit them, apart from recommend which
+/// takes --limit as the number of games to recommend
+fn reject_unsupported_list_options(arguments: &ArgMatches, subcommand: &str) -> Result<(), Error> {
+    let supported: &[&str] = match subcommand {
+        "in-common" | "missing-from-group" | "own-or-wishlist" => &["sort", "order", "limit"],
+        "recommend" => &["limit"],
+        _ => &[],
+    };
+    match ["sort", "order", "limit"].into_iter().find(|option| {
+        !supported.contains(option)
+            && arguments.value_source(option) == Some(ValueSource::CommandLine)
+    }) {
+        None => Ok(()),
+        Some(option) => Err(Error::Argument(format!(
+            "--{option} isn't supported by {subcommand}"
+        ))),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct MissingGameRecord<'a> {
+    steamid: u64,
+    player: String,
+    appid: u64,
+    name: &'a str,
+    price: Option<f64>,
+    currency: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PurchaseRecord<'a> {
+    appid: u64,
+    name: &'a str,
+    buyers: Vec<String>,
+    unit_price: f64,
+    /// the cost of this purchase and every one before it
+    total_cost: f64,
+    currency: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlayableRecord<'a> {
+    appid: u64,
+    name: &'a str,
+    owned_by_everyone: bool,
+    /// who could host it with Remote Play Together, if not everyone owns it
+    hosts: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RecommendationRecord<'a> {
+    rank: usize,
+    appid: u64,
+    name: &'a str,
+    score: f64,
+    reasons: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+}
+
+/// The price in the currency's main unit, e.g., dollars instead of cents. None if it can't be
+/// bought
+pub fn price_amount(price: &Price) -> Option<f64> {
+    match price {
+        Price::Unavailable => None,
+        Price::Free | Price::Paid(_) => Some(price.final_price() as f64 / 100.0),
+    }
+}
+
+pub fn price_currency(price: &Price) -> Option<&str> {
+    match price {
+        Price::Paid(price_overview) => Some(&price_overview.currency),
+        Price::Free | Price::Unavailable => None,
+    }
+}
+
+pub fn compute_game_info_record<'a>(
+    game: &'a Game,
+    metadata: Option<&'a GameMetadata>,
+) -> GameInfoRecord<'a> {
+    let review = metadata.and_then(|metadata| metadata.review.as_ref());
+    let price = metadata.map(|metadata| &metadata.price);
+    GameInfoRecord {
+        appid: game.appid,
+        name: &game.name,
+        platforms: metadata
+            .and_then(|metadata| metadata.platforms.as_ref())
+            .map(|platforms| {
+                [
+                    (platforms.windows, "windows"),
+                    (platforms.mac, "mac"),
+                    (platforms.steamos_linux, "linux"),
+                ]
+                .into_iter()
+                .filter(|(supported, _)| *supported)
+                .map(|(_, platform)| platform)
+                .collect()
+            })
+            .unwrap_or_default(),
+        review: review.map(|review| review.review_score_label.as_str()),
+        percent_positive: review.map(|review| review.percent_positive),
+        review_count: review.map(|review| review.review_count),
+        price: price.and_then(price_amount),
+        currency: price.and_then(price_currency),
+        discount_percent: price.map_or(0, Price::discount_percent),
+        requirements: metadata.and_then(|metadata| metadata.pc_requirements.as_deref()),
+    }
+}
+
+/// One record per game, with a column per player. The columns hold the hours played with --hours
+/// and whether they own it otherwise. If any game is owned on a linked account or borrowed, each
+/// player also gets an "owned on" column naming where their copy is from
+pub fn compute_ownership_matrix_records(
+    matrix: &OwnershipMatrix,
+    summaries: &[UserSummary],
+    show_hours: bool,
+) -> Vec<Map<String, Value>> {
+    let player_names = matrix
+        .steam_ids
+        .iter()
+        .map(|id| persona_name(summaries, *id))
         .collect::<Vec<_>>();
-        let game_infos: Result<Vec<client::GetGameInfoResponse>, client::Error> =
-            game_infos.into_iter().collect();
-        Ok(compute_game_info_string(game_infos?))
+    let show_owned_on = matrix
+        .rows
+        .iter()
+        .flat_map(|row| row.cells.iter().flatten())
+        .any(|cell| owned_on(cell, summaries).is_some());
+    matrix
+        .rows
+        .iter()
+        .map(|row| {
+            let mut record = Map::new();
+            record.insert("appid".to_string(), row.game.appid.into());
+            record.insert("name".to_string(), row.game.name.clone().into());
+            for (name, cell) in player_names.iter().zip(&row.cells) {
+                let value = match cell {
+                    None if show_hours => Value::Null,
+                    Some(cell) if show_hours => {
+                        ((cell.playtime as f64 / 6.0).round() / 10.0).into()
+                    }
+                    cell => cell.is_some().into(),
+                };
+                record.insert(name.clone(), value);
+                if show_owned_on {
+                    record.insert(
+                        format!("{name} owned on"),
+                        cell.as_ref()
+                            .and_then(|cell| owned_on(cell, summaries))
+                            .map_or(Value::Null, Value::from),
+                    );
+                }
+            }
+            record
+        })
+        .collect()
+}
+
+/// Where a player's copy of a game is from, if it isn't their own account
+fn owned_on(cell: &OwnershipCell, summaries: &[UserSummary]) -> Option<String> {
+    match cell.account {
+        Some(account) => Some(persona_name(summaries, account)),
+        None if cell.borrowed => Some("steam family".to_string()),
+        None => None,
     }
 }
 
+pub fn compute_missing_game_records<'a>(
+    missing_games: &'a [(u64, Vec<Game>)],
+    prices: &'a HashMap<u64, Price>,
+    summaries: &[UserSummary],
+) -> Vec<MissingGameRecord<'a>> {
+    missing_games
+        .iter()
+        .flat_map(|(id, games)| {
+            games.iter().map(|game| {
+                let price = prices.get(&game.appid).unwrap_or(&Price::Unavailable);
+                MissingGameRecord {
+                    steamid: *id,
+                    player: persona_name(summaries, *id),
+                    appid: game.appid,
+                    name: &game.name,
+                    price: price_amount(price),
+                    currency: price_currency(price),
+                }
+            })
+        })
+        .collect()
+}
+
+pub fn compute_purchase_records<'a>(
+    chosen: &'a [purchase_optimizer::Candidate],
+    currency: &'a str,
+    summaries: &[UserSummary],
+) -> Vec<PurchaseRecord<'a>> {
+    let mut total_cost = 0;
+    chosen
+        .iter()
+        .map(|candidate| {
+            total_cost += candidate.cost();
+            PurchaseRecord {
+                appid: candidate.game.appid,
+                name: &candidate.game.name,
+                buyers: candidate
+                    .buyers
+                    .iter()
+                    .map(|id| persona_name(summaries, *id))
+                    .collect(),
+                unit_price: candidate.unit_price as f64 / 100.0,
+                total_cost: total_cost as f64 / 100.0,
+                currency,
+            }
+        })
+        .collect()
+}
+
+/// The games everyone owns followed by the ones that need Remote Play Together, each by name
+pub fn compute_playable_records<'a>(
+    playable: &'a PlayableTogether,
+    summaries: &[UserSummary],
+) -> Vec<PlayableRecord<'a>> {
+    let mut in_common = playable.in_common.iter().collect::<Vec<_>>();
+    in_common.sort_by(|a, b| a.name.cmp(&b.name));
+    let mut remote_play = playable.remote_play.iter().collect::<Vec<_>>();
+    remote_play.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    in_common
+        .into_iter()
+        .map(|game| PlayableRecord {
+            appid: game.appid,
+            name: &game.name,
+            owned_by_everyone: true,
+            hosts: vec![],
+        })
+        .chain(remote_play.into_iter().map(|(game, hosts)| {
+            PlayableRecord {
+                appid: game.appid,
+                name: &game.name,
+                owned_by_everyone: false,
+                hosts: hosts
+                    .iter()
+                    .map(|id| persona_name(summaries, *id))
+                    .collect(),
+            }
+        }))
+        .collect()
+}
+
+pub fn compute_recommendation_records(
+    recommendations: &[Recommendation],
+    seed: Option<u64>,
+) -> Vec<RecommendationRecord<'_>> {
+    recommendations
+        .iter()
+        .enumerate()
+        .map(|(index, recommendation)| RecommendationRecord {
+            rank: index + 1,
+            appid: recommendation.game.appid,
+            name: &recommendation.game.name,
+            score: recommendation.score,
+            reasons: &recommendation.reasons,
+            seed,
+        })
+        .collect()
+}
+
 pub fn compute_sorted_games_string(games: impl IntoIterator<Item = Game>) -> String {
     let mut games: Vec<Game> = games.into_iter().collect();
     games.sort_by(|a, b| a.name.cmp(&b.name));
+    compute_games_string_with_notes(&games, |_| None)
+}
+
+/// List the games in order, noting who only borrows each one and which linked accounts own it
+pub fn compute_listed_games_string(games: &[ListedGame]) -> String {
     format!(
         "{games}\n\tTotal: {total}\n",
         games = games
             .iter()
-            .map(ToString::to_string)
+            .map(|listed| {
+                let notes = [
+                    ("borrowed by", &listed.borrowed_by),
+                    ("owned on", &listed.owned_on),
+                ]
+                .into_iter()
+                .filter(|(_, names)| !names.is_empty())
+                .map(|(note, names)| format!("{note} {}", names.join(", ")))
+                .collect::<Vec<_>>();
+                if notes.is_empty() {
+                    listed.game.to_string()
+                } else {
+                    format!("{} ({})", listed.game, notes.join(", "))
+                }
+            })
             .collect::<Vec<String>>()
             .join("\n"),
         total = games.len()
     )
 }
 
-pub fn compute_game_info_string(games: impl IntoIterator<Item = GetGameInfoResponse>) -> String {
-    let games: Vec<GameInfo> = games
-        .into_iter()
-        .flat_map(|response| response.games.into_values())
-        .collect();
+/// List the games in the order provided. Any note returned for a game is put in parentheses
+/// after it
+pub fn compute_games_string_with_notes(
+    games: &[Game],
+    note: impl Fn(&Game) -> Option<String>,
+) -> String {
+    format!(
+        "{games}\n\tTotal: {total}\n",
+        games = games
+            .iter()
+            .map(|game| match note(game) {
+                None => game.to_string(),
+                Some(note) => format!("{game} ({note})"),
+            })
+            .collect::<Vec<String>>()
+            .join("\n"),
+        total = games.len()
+    )
+}
+
+pub fn compute_game_info_string<'a>(
+    games: impl IntoIterator<Item = Option<&'a GameMetadata>>,
+) -> String {
+    let games: Vec<Option<&GameMetadata>> = games.into_iter().collect();
     format!(
         "{games}\n\tTotal: {total}\n",
         games = games
             .iter()
-            .map(|game_info| match &game_info.data {
+            .map(|metadata| match metadata {
                 None => "No info".to_string(),
-                Some(data) => format!(
-                    "{name},{id},{requirements}",
-                    name = data.name,
-                    id = data.steam_appid,
-                    requirements = match &data.pc_requirements {
-                        None => "No requirements".to_string(),
-                        Some(req) => match &req.recommended {
-                            None => "No recommendations".to_string(),
-                            Some(req) => req.clone(),
-                        },
+                Some(metadata) => format!(
+                    "{name},{id},{platforms},{review},{price},{requirements}",
+                    name = metadata.game.name,
+                    id = metadata.game.appid,
+                    platforms = match &metadata.platforms {
+                        None => "No platforms".to_string(),
+                        Some(platforms) => [
+                            (platforms.windows, "windows"),
+                            (platforms.mac, "mac"),
+                            (platforms.steamos_linux, "linux"),
+                        ]
+                        .iter()
+                        .filter(|(supported, _)| *supported)
+                        .map(|(_, platform)| *platform)
+                        .collect::<Vec<_>>()
+                        .join("/"),
+                    },
+                    review = match &metadata.review {
+                        None => "No reviews".to_string(),
+                        Some(review) => format!(
+                            "{} ({}% of {})",
+                            review.review_score_label, review.percent_positive, review.review_count
+                        ),
+                    },
+                    price = compute_price_string(&metadata.price),
+                    requirements = match &metadata.pc_requirements {
+                        None => "No recommendations".to_string(),
+                        Some(requirements) => requirements.clone(),
                     }
-                )
-                .to_string(),
+                ),
             })
             .collect::<Vec<String>>()
             .join("\n"),
         total = games.len()
     )
 }
+
+/// Render the matrix as a markdown table so that it can be pasted directly into a doc
+pub fn compute_ownership_matrix_string(
+    matrix: &OwnershipMatrix,
+    summaries: &[UserSummary],
+    show_hours: bool,
+) -> String {
+    let player_names = matrix
+        .steam_ids
+        .iter()
+        .map(|id| persona_name(summaries, *id))
+        .collect::<Vec<_>>();
+    let header = format!("| Game | {} |", player_names.join(" | "));
+    let separator = format!("| --- |{}", " --- |".repeat(player_names.len()));
+    let rows = matrix.rows.iter().map(|row| {
+        let cells = row
+            .cells
+            .iter()
+            .map(|cell| {
+                let Some(cell) = cell else {
+                    return "".to_string();
+                };
+                let mark = if show_hours {
+                    format!("{:.1}h", cell.playtime as f64 / 60.0)
+                } else {
+                    "X".to_string()
+                };
+                match owned_on(cell, summaries) {
+                    None => mark,
+                    Some(owned_on) => format!("{mark} (on {owned_on})"),
+                }
+            })
+            .collect::<Vec<_>>();
+        format!("| {} | {} |", row.game.name, cells.join(" | "))
+    });
+    format!(
+        "{header}\n{separator}\n{rows}\n\tTotal: {total}\n",
+        rows = rows.collect::<Vec<_>>().join("\n"),
+        total = matrix.rows.len()
+    )
+}
+
+pub fn compute_cost_to_complete_string(
+    missing_games: impl IntoIterator<Item = (u64, Vec<Game>)>,
+    prices: &HashMap<u64, Price>,
+    summaries: &[UserSummary],
+) -> String {
+    missing_games
+        .into_iter()
+        .map(|(id, mut games)| {
+            games.sort_by(|a, b| a.name.cmp(&b.name));
+            let game_prices = games
+                .iter()
+                .map(|game| (game, prices.get(&game.appid).unwrap_or(&Price::Unavailable)))
+                .collect::<Vec<_>>();
+            let total = total_price(game_prices.iter().map(|(_, price)| *price));
+            let game_lines = game_prices
+                .iter()
+                .map(|(game, price)| format!("\t{game}: {}", compute_price_string(price)))
+                .collect::<Vec<_>>();
+            format!(
+                "{name}: {count} games missing, total {total}{unavailable}\n{games}",
+                name = persona_name(summaries, id),
+                count = games.len(),
+                unavailable = match total.unavailable {
+                    0 => String::new(),
+                    unavailable => format!(" plus {unavailable} without a price"),
+                },
+                total = format_amount(total.amount, &total.currency),
+                games = game_lines.join("\n"),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Walk through the chosen purchases in order, showing how many games each one adds to the
+/// group's common library
+pub fn compute_purchase_plan_string(
+    chosen: &[purchase_optimizer::Candidate],
+    games_in_common: usize,
+    currency: &str,
+    summaries: &[UserSummary],
+) -> String {
+    let format_price = |price: u64| format_amount(price, currency);
+    let mut total_cost = 0;
+    let mut lines = chosen
+        .iter()
+        .enumerate()
+        .map(|(index, candidate)| {
+            total_cost += candidate.cost();
+            format!(
+                "{buyers} buying {game} for {price} each: +1 game ({unlocked} unlocked for {total_cost} so far)",
+                buyers = candidate
+                    .buyers
+                    .iter()
+                    .map(|id| persona_name(summaries, *id))
+                    .collect::<Vec<_>>()
+                    .join(" and "),
+                game = candidate.game,
+                price = format_price(candidate.unit_price),
+                unlocked = index + 1,
+                total_cost = format_price(total_cost),
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.push(format!(
+        "\t{purchases} purchases unlock {unlocked} games for {total}, taking the group from {games_in_common} to {new_total} games in common",
+        purchases = chosen
+            .iter()
+            .map(|candidate| candidate.buyers.len())
+            .sum::<usize>(),
+        unlocked = chosen.len(),
+        total = format_price(total_cost),
+        new_total = games_in_common + chosen.len(),
+    ));
+    lines.join("\n")
+}
+
+pub fn compute_playable_together_string(
+    playable: &PlayableTogether,
+    summaries: &[UserSummary],
+) -> String {
+    let mut remote_play = playable.remote_play.clone();
+    remote_play.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+    format!(
+        "Owned by everyone:\n{in_common}Remote Play Together, only the host needs to own it:\n{remote_play}\n\tTotal: {total}\n",
+        in_common = compute_sorted_games_string(playable.in_common.iter().cloned()),
+        remote_play = remote_play
+            .iter()
+            .map(|(game, hosts)| format!(
+                "{game}, hosted by {hosts}",
+                hosts = hosts
+                    .iter()
+                    .map(|id| persona_name(summaries, *id))
+                    .collect::<Vec<_>>()
+                    .join(" or ")
+            ))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = remote_play.len(),
+    )
+}
+
+pub fn compute_recommendations_string(
+    recommendations: &[Recommendation],
+    seed: Option<u64>,
+) -> String {
+    let mut lines = recommendations
+        .iter()
+        .enumerate()
+        .map(|(index, recommendation)| {
+            format!(
+                "{rank}. {game} scored {score:.2}: {reasons}",
+                rank = index + 1,
+                game = recommendation.game,
+                score = recommendation.score,
+                reasons = if recommendation.reasons.is_empty() {
+                    "nothing stands out".to_string()
+                } else {
+                    recommendation.reasons.join(", ")
+                },
+            )
+        })
+        .collect::<Vec<_>>();
+    if let Some(seed) = seed {
+        lines.push(format!("\tPicked with seed {seed}"));
+    }
+    lines.join("\n")
+}
+
+pub fn compute_price_string(price: &Price) -> String {
+    match price {
+        Price::Free => "free".to_string(),
+        Price::Unavailable => "price unavailable".to_string(),
+        Price::Paid(price_overview) if price_overview.discount_percent > 0 => format!(
+            "{} (-{}%, was {})",
+            price_overview.final_formatted,
+            price_overview.discount_percent,
+            price_overview.initial_formatted
+        ),
+        Price::Paid(price_overview) => price_overview.final_formatted.clone(),
+    }
+}
+
+pub fn persona_name(summaries: &[UserSummary], steam_id: u64) -> String {
+    summaries
+        .iter()
+        .find(|summary| summary.steamid == steam_id.to_string())
+        .map_or(steam_id.to_string(), |summary| summary.personaname.clone())
+}
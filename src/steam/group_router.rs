@@ -0,0 +1,262 @@
+use clap::{ArgMatches, Command};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use super::{
+    arg_matcher::{self, SharedArgs},
+    client::UserSummary,
+    games_router::persona_name,
+    groups::{group_name, load_groups, Error as GroupError, Group, Groups, GROUP_PREFIX},
+    logger::FilteringLogger,
+    output::CommandOutput,
+    registry::SteamCommand,
+    router::{resolve_players, unknown_subcommand, Error},
+    service::get_user_summaries_in_batches,
+};
+
+pub struct GroupCommand;
+
+impl SteamCommand for GroupCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::group_command(args)
+    }
+
+    fn uses_local_files(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_group_command(arguments, user_steam_id, logger))
+    }
+}
+
+pub async fn run_group_command<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    match arguments.subcommand() {
+        Some(("create", arguments)) => {
+            let name = get_group_name(arguments)?;
+            let mut groups = load_groups()?;
+            if groups.groups.contains_key(name) {
+                return Err(GroupError::AlreadyExists(name.to_string()).into());
+            }
+            groups.groups.insert(name.to_string(), Group::default());
+            add_members(&mut groups, name, arguments, user_steam_id, logger).await?;
+            groups.save()?;
+            show_group(&groups, name, logger).await
+        }
+        Some(("add", arguments)) => {
+            let name = get_group_name(arguments)?;
+            let mut groups = load_groups()?;
+            groups.get(name)?;
+            add_members(&mut groups, name, arguments, user_steam_id, logger).await?;
+            groups.save()?;
+            show_group(&groups, name, logger).await
+        }
+        Some(("remove", arguments)) => {
+            let name = get_group_name(arguments)?;
+            let mut groups = load_groups()?;
+            let (includes, players) = split_members(arguments);
+            if includes.is_empty() && players.is_empty() {
+                groups.remove(name)?;
+                groups.save()?;
+                return Ok(CommandOutput::Message(format!(
+                    "removed {GROUP_PREFIX}{name}"
+                )));
+            }
+            let players = resolve_players(arguments, user_steam_id, players, logger).await?;
+            let group = groups.get_mut(name)?;
+            group.members.retain(|member| !players.contains(member));
+            group
+                .includes
+                .retain(|include| !includes.contains(&include.as_str()));
+            groups.save()?;
+            show_group(&groups, name, logger).await
+        }
+        Some(("list", _)) => Ok(CommandOutput::Groups(load_groups()?)),
+        Some(("show", arguments)) => {
+            let name = get_group_name(arguments)?;
+            show_group(&load_groups()?, name, logger).await
+        }
+        _ => Err(unknown_subcommand(arguments)),
+    }
+}
+
+/// The group name argument, with or without the @
+fn get_group_name(arguments: &ArgMatches) -> Result<&str, Error> {
+    let name = arguments
+        .get_one::<String>("name")
+        .ok_or(Error::Argument("name is required".to_string()))?;
+    Ok(group_name(name).unwrap_or(name.trim()))
+}
+
+/// Split the members argument into the names of nested groups and everything else
+fn split_members(arguments: &ArgMatches) -> (Vec<&str>, Vec<&str>) {
+    let (includes, players): (Vec<&str>, Vec<&str>) = arguments
+        .get_many::<String>("members")
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .partition(|member| group_name(member).is_some());
+    (
+        includes.into_iter().filter_map(group_name).collect(),
+        players,
+    )
+}
+
+/// Add the members and nested groups from the arguments to the group, along with its --filter.
+/// Nested groups have to exist and can't end up including the group itself
+async fn add_members<'a>(
+    groups: &mut Groups,
+    name: &str,
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<(), Error> {
+    let (includes, players) = split_members(arguments);
+    for include in &includes {
+        groups.get(include)?;
+    }
+    let players = resolve_players(arguments, user_steam_id, players, logger).await?;
+    let group = groups.get_mut(name)?;
+    for player in players {
+        if !group.members.contains(&player) {
+            group.members.push(player);
+        }
+    }
+    for include in includes {
+        if !group.includes.iter().any(|existing| existing == include) {
+            group.includes.push(include.to_string());
+        }
+    }
+    if let Some(filter) = arguments.get_one::<String>("filter") {
+        group.filter = match filter.as_str() {
+            "none" => None,
+            filter => Some(filter.to_string()),
+        };
+    }
+    // checks for cycles
+    groups.expand(name)?;
+    Ok(())
+}
+
+async fn show_group<'a>(
+    groups: &Groups,
+    name: &str,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    let members = groups.expand(name)?;
+    let summaries = get_user_summaries_in_batches(&members, logger).await?;
+    Ok(CommandOutput::Group {
+        name: name.to_string(),
+        group: groups.get(name)?.clone(),
+        members,
+        summaries,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct MemberRecord {
+    steamid: u64,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GroupRecord<'a> {
+    name: &'a str,
+    /// None if the group can't be expanded, e.g., because a nested group is missing
+    members: Option<usize>,
+    includes: &'a [String],
+    filter: Option<&'a str>,
+}
+
+pub fn compute_member_records(members: &[u64], summaries: &[UserSummary]) -> Vec<MemberRecord> {
+    members
+        .iter()
+        .map(|steam_id| MemberRecord {
+            steamid: *steam_id,
+            name: persona_name(summaries, *steam_id),
+        })
+        .collect()
+}
+
+pub fn compute_group_records(groups: &Groups) -> Vec<GroupRecord<'_>> {
+    groups
+        .groups
+        .iter()
+        .map(|(name, group)| GroupRecord {
+            name,
+            members: groups.expand(name).ok().map(|members| members.len()),
+            includes: &group.includes,
+            filter: group.filter.as_deref(),
+        })
+        .collect()
+}
+
+pub fn compute_group_string(
+    name: &str,
+    group: &Group,
+    members: &[u64],
+    summaries: &[UserSummary],
+) -> String {
+    let mut lines = vec![format!("{GROUP_PREFIX}{name}")];
+    if !group.includes.is_empty() {
+        lines.push(format!(
+            "includes: {}",
+            group
+                .includes
+                .iter()
+                .map(|include| format!("{GROUP_PREFIX}{include}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+    if let Some(filter) = &group.filter {
+        lines.push(format!("default filter: {filter}"));
+    }
+    lines.extend(
+        members
+            .iter()
+            .map(|steam_id| format!("{} ({steam_id})", persona_name(summaries, *steam_id))),
+    );
+    lines.push(format!("\tTotal: {}\n", members.len()));
+    lines.join("\n")
+}
+
+pub fn compute_groups_string(groups: &Groups) -> String {
+    format!(
+        "{groups}\n\tTotal: {total}\n",
+        groups = groups
+            .groups
+            .iter()
+            .map(|(name, group)| {
+                let mut details = vec![match groups.expand(name) {
+                    Ok(members) => format!("{} members", members.len()),
+                    Err(err) => err.to_string(),
+                }];
+                details.extend(
+                    group
+                        .includes
+                        .iter()
+                        .map(|include| format!("includes {GROUP_PREFIX}{include}")),
+                );
+                details.extend(
+                    group
+                        .filter
+                        .iter()
+                        .map(|filter| format!("filters to {filter}")),
+                );
+                format!("{GROUP_PREFIX}{name}: {}", details.join(", "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = groups.groups.len(),
+    )
+}
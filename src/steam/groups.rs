@@ -0,0 +1,272 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::util::storage;
+
+const GROUPS_FILE: &str = "groups.json";
+/// Arguments starting with this are treated as the name of a saved group, e.g., @tuesday-night
+pub const GROUP_PREFIX: char = '@';
+/// The groups saved by friends cliques --save start with this, so that they never replace groups
+/// that were saved by hand
+pub const CLIQUE_GROUP_PREFIX: &str = "clique-";
+
+/// Named lists of players that can be passed anywhere a list of players is expected
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Groups {
+    #[serde(deserialize_with = "deserialize_groups")]
+    pub groups: BTreeMap<String, Group>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Group {
+    pub members: Vec<u64>,
+    /// other groups whose members are also members of this one, without the @
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+    /// the games --filter used when this group is passed and no --filter is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+}
+
+/// Groups used to be saved as just their members, so either shape is read
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredGroup {
+    Members(Vec<u64>),
+    Group(Group),
+}
+
+fn deserialize_groups<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Group>, D::Error> {
+    Ok(BTreeMap::<String, StoredGroup>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(name, group)| match group {
+            StoredGroup::Members(members) => (
+                name,
+                Group {
+                    members,
+                    ..Default::default()
+                },
+            ),
+            StoredGroup::Group(group) => (name, group),
+        })
+        .collect())
+}
+
+pub fn load_groups() -> Result<Groups, Error> {
+    Ok(storage::load_json(GROUPS_FILE)?)
+}
+
+/// The name of the group an argument refers to, if it refers to one, e.g., @friday is friday
+pub fn group_name(argument: &str) -> Option<&str> {
+    argument.trim().strip_prefix(GROUP_PREFIX)
+}
+
+impl Groups {
+    pub fn save(&self) -> Result<(), Error> {
+        Ok(storage::save_json(GROUPS_FILE, self)?)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Group, Error> {
+        self.groups
+            .get(name)
+            .ok_or(Error::NotFound(name.to_string()))
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Result<&mut Group, Error> {
+        self.groups
+            .get_mut(name)
+            .ok_or(Error::NotFound(name.to_string()))
+    }
+
+    /// Every member of the group, including the members of any groups nested inside it, in the
+    /// order they were added and without duplicates
+    pub fn expand(&self, name: &str) -> Result<Vec<u64>, Error> {
+        let mut members = vec![];
+        self.expand_into(name, &mut vec![], &mut members)?;
+        Ok(members)
+    }
+
+    fn expand_into<'a>(
+        &'a self,
+        name: &'a str,
+        path: &mut Vec<&'a str>,
+        members: &mut Vec<u64>,
+    ) -> Result<(), Error> {
+        if path.contains(&name) {
+            path.push(name);
+            return Err(Error::Cycle(path.join(" -> ")));
+        }
+        let group = self.get(name)?;
+        path.push(name);
+        for member in &group.members {
+            if !members.contains(member) {
+                members.push(*member);
+            }
+        }
+        for include in &group.includes {
+            self.expand_into(include, path, members)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
+    /// The group's filter, or the first filter set on a group nested inside it
+    pub fn default_filter(&self, name: &str) -> Result<Option<&str>, Error> {
+        // checks for cycles and missing groups up front, so the search below always ends
+        self.expand(name)?;
+        Ok(self.find_filter(name))
+    }
+
+    fn find_filter(&self, name: &str) -> Option<&str> {
+        let group = self.groups.get(name)?;
+        group.filter.as_deref().or_else(|| {
+            group
+                .includes
+                .iter()
+                .find_map(|include| self.find_filter(include))
+        })
+    }
+
+    /// Replace the groups saved from cliques with these ones, so that cliques that have since
+    /// broken up don't linger. The names are given without the clique- prefix
+    pub fn replace_cliques(&mut self, cliques: impl IntoIterator<Item = (String, Group)>) {
+        let stale = self
+            .groups
+            .keys()
+            .filter(|name| name.starts_with(CLIQUE_GROUP_PREFIX))
+            .cloned()
+            .collect::<Vec<_>>();
+        for name in stale {
+            let _ = self.remove(&name);
+        }
+        self.groups.extend(
+            cliques
+                .into_iter()
+                .map(|(name, group)| (format!("{CLIQUE_GROUP_PREFIX}{name}"), group)),
+        );
+    }
+
+    /// Remove the group, along with it being nested inside any other groups
+    pub fn remove(&mut self, name: &str) -> Result<Group, Error> {
+        let group = self
+            .groups
+            .remove(name)
+            .ok_or(Error::NotFound(name.to_string()))?;
+        for other in self.groups.values_mut() {
+            other.includes.retain(|include| include != name);
+        }
+        Ok(group)
+    }
+}
+
+/// Turn a game or group name into something that's easy to type after an @, e.g., "Deep Rock
+/// Galactic" becomes deep-rock-galactic
+pub fn slugify(name: &str) -> String {
+    name.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Storage(storage::Error),
+    NotFound(String),
+    AlreadyExists(String),
+    Cycle(String),
+}
+
+impl From<storage::Error> for Error {
+    fn from(value: storage::Error) -> Self {
+        Error::Storage(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Storage(err) => write!(f, "StorageError: {}", err),
+            Error::NotFound(name) => write!(f, "there is no saved group named {name}"),
+            Error::AlreadyExists(name) => write!(
+                f,
+                "there is already a group named {name}, use group add to add members to it"
+            ),
+            Error::Cycle(path) => write!(f, "groups can't include themselves: {path}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, Group, Groups, CLIQUE_GROUP_PREFIX};
+
+    fn group(members: &[u64], includes: &[&str], filter: Option<&str>) -> Group {
+        Group {
+            members: members.to_vec(),
+            includes: includes.iter().map(|name| name.to_string()).collect(),
+            filter: filter.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn expands_nested_groups() {
+        let mut groups = Groups::default();
+        groups
+            .groups
+            .insert("duo".to_string(), group(&[1, 2], &[], None));
+        groups.groups.insert(
+            "squad".to_string(),
+            group(&[3, 1], &["duo"], Some("multiplayer")),
+        );
+        groups
+            .groups
+            .insert("friday".to_string(), group(&[4], &["squad", "duo"], None));
+
+        assert_eq!(groups.expand("friday").unwrap(), vec![4, 3, 1, 2]);
+        assert_eq!(
+            groups.default_filter("friday").unwrap(),
+            Some("multiplayer")
+        );
+        assert_eq!(groups.default_filter("duo").unwrap(), None);
+
+        groups
+            .get_mut("duo")
+            .unwrap()
+            .includes
+            .push("friday".to_string());
+        assert!(matches!(groups.expand("friday"), Err(Error::Cycle(_))));
+    }
+
+    #[test]
+    fn replaces_saved_cliques() {
+        let mut groups = Groups::default();
+        groups
+            .groups
+            .insert("portal".to_string(), group(&[1], &["clique-old"], None));
+        groups
+            .groups
+            .insert("clique-old".to_string(), group(&[2], &[], None));
+        groups.replace_cliques([("portal".to_string(), group(&[3], &[], None))]);
+
+        assert_eq!(
+            groups.groups.keys().collect::<Vec<_>>(),
+            vec![&format!("{CLIQUE_GROUP_PREFIX}portal"), "portal"]
+        );
+        assert_eq!(groups.expand("portal").unwrap(), vec![1]);
+    }
+
+    #[test]
+    fn loads_groups_saved_as_member_lists() {
+        let groups: Groups = serde_json::from_str(
+            r#"{"groups": {"duo": [1, 2], "friday": {"members": [3], "includes": ["duo"]}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(groups.get("duo").unwrap().members, vec![1, 2]);
+        assert_eq!(groups.expand("friday").unwrap(), vec![3, 1, 2]);
+    }
+}
@@ -0,0 +1,249 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{logger::FilteringLogger, models::OwnedGame};
+use crate::util::storage;
+
+const IDENTITIES_FILE: &str = "identities.json";
+
+/// People who own games across more than one steam account, e.g., a main and an alt. Any of an
+/// identity's accounts counts as owning the games of all of them
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Identities {
+    /// each identity's accounts, main account first
+    pub identities: BTreeMap<String, Vec<u64>>,
+}
+
+pub fn load_identities() -> Result<Identities, Error> {
+    Ok(storage::load_json(IDENTITIES_FILE)?)
+}
+
+impl Identities {
+    pub fn save(&self) -> Result<(), Error> {
+        Ok(storage::save_json(IDENTITIES_FILE, self)?)
+    }
+
+    /// The name the identity is saved under. Names are matched ignoring case like aliases are, and
+    /// new ones are saved lowercased
+    fn key(&self, name: &str) -> String {
+        let name = name.trim().to_lowercase();
+        self.identities
+            .keys()
+            .find(|key| key.to_lowercase() == name)
+            .cloned()
+            .unwrap_or(name)
+    }
+
+    /// The accounts of the identity, main account first
+    pub fn accounts(&self, name: &str) -> Option<&[u64]> {
+        self.identities.get(&self.key(name)).map(Vec::as_slice)
+    }
+
+    /// Add the accounts to the identity, creating it if it doesn't exist yet. An account can only
+    /// belong to one identity
+    pub fn link(&mut self, name: &str, accounts: &[u64]) -> Result<(), Error> {
+        let name = self.key(name);
+        for account in accounts {
+            if let Some(other) = self.identity_of(*account) {
+                if other != name {
+                    return Err(Error::AlreadyLinked(*account, other.to_string()));
+                }
+            }
+        }
+        let linked = self.identities.entry(name).or_default();
+        for account in accounts {
+            if !linked.contains(account) {
+                linked.push(*account);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove the accounts from the identity, or the whole identity if no accounts are given
+    pub fn unlink(&mut self, name: &str, accounts: &[u64]) -> Result<(), Error> {
+        let key = self.key(name);
+        let linked = self
+            .identities
+            .get_mut(&key)
+            .ok_or(Error::NotFound(name.to_string()))?;
+        linked.retain(|account| !accounts.contains(account));
+        if accounts.is_empty() || linked.is_empty() {
+            self.identities.remove(&key);
+        }
+        Ok(())
+    }
+
+    pub fn identity_of(&self, steam_id: u64) -> Option<&str> {
+        self.identities
+            .iter()
+            .find(|(_, accounts)| accounts.contains(&steam_id))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// The other accounts of whoever owns this one
+    pub fn linked_accounts(&self, steam_id: u64) -> Vec<u64> {
+        self.identity_of(steam_id)
+            .and_then(|name| self.identities.get(name))
+            .into_iter()
+            .flatten()
+            .filter(|account| **account != steam_id)
+            .copied()
+            .collect()
+    }
+}
+
+/// Load the identities for linking accounts while fetching libraries. Failing to read them only
+/// logs, so that a broken file doesn't stop libraries from being fetched
+pub fn load_identities_for_libraries<'a>(logger: &'a FilteringLogger<'a>) -> Identities {
+    load_identities().unwrap_or_else(|err| {
+        logger.trace(format!("failed to load identities: {err}"));
+        Identities::default()
+    })
+}
+
+/// Merge the libraries of one person's accounts into a single library for the first account. A game
+/// owned on several accounts is kept once with their playtimes added up. Games that are only owned
+/// on another account are marked with that account, and owning a game beats borrowing it
+pub fn merge_accounts(libraries: Vec<(u64, Vec<OwnedGame>)>) -> Vec<OwnedGame> {
+    let mut libraries = libraries.into_iter();
+    let Some((main_account, main_library)) = libraries.next() else {
+        return vec![];
+    };
+    let mut merged = main_library;
+    let mut indices = merged
+        .iter()
+        .enumerate()
+        .map(|(index, owned_game)| (owned_game.game.appid, index))
+        .collect::<HashMap<_, _>>();
+    for (account, library) in libraries {
+        for owned_game in library {
+            let owned_game = OwnedGame {
+                account: Some(account).filter(|account| *account != main_account),
+                ..owned_game
+            };
+            match indices.get(&owned_game.game.appid) {
+                None => {
+                    indices.insert(owned_game.game.appid, merged.len());
+                    merged.push(owned_game);
+                }
+                Some(index) => {
+                    let existing = &mut merged[*index];
+                    existing.playtime_forever += owned_game.playtime_forever;
+                    existing.playtime_2weeks += owned_game.playtime_2weeks;
+                    existing.rtime_last_played =
+                        existing.rtime_last_played.max(owned_game.rtime_last_played);
+                    if existing.borrowed && !owned_game.borrowed {
+                        existing.borrowed = false;
+                        existing.account = owned_game.account;
+                    }
+                }
+            }
+        }
+    }
+    merged
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Storage(storage::Error),
+    NotFound(String),
+    AlreadyLinked(u64, String),
+}
+
+impl From<storage::Error> for Error {
+    fn from(value: storage::Error) -> Self {
+        Error::Storage(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Storage(err) => write!(f, "StorageError: {}", err),
+            Error::NotFound(name) => write!(f, "there is no identity named {name}"),
+            Error::AlreadyLinked(account, name) => {
+                write!(f, "{account} is already linked to {name}, unlink it first")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{merge_accounts, Identities};
+    use crate::steam::models::OwnedGame;
+
+    #[test]
+    fn links_accounts() {
+        let mut identities = Identities::default();
+        identities.link("chris", &[1, 2]).unwrap();
+        identities.link("Chris", &[3, 2]).unwrap();
+        assert!(identities.link("dave", &[3]).is_err());
+        assert_eq!(identities.accounts("CHRIS"), Some([1, 2, 3].as_slice()));
+        assert_eq!(identities.linked_accounts(2), vec![1, 3]);
+        assert_eq!(identities.linked_accounts(4), Vec::<u64>::new());
+        identities.unlink("chris", &[1]).unwrap();
+        assert_eq!(identities.linked_accounts(2), vec![3]);
+        identities.unlink("chris", &[]).unwrap();
+        assert!(identities.identities.is_empty());
+    }
+
+    #[test]
+    fn merges_libraries_and_marks_the_owning_account() {
+        let merged = merge_accounts(vec![
+            (
+                1,
+                vec![
+                    OwnedGame {
+                        playtime_forever: 60,
+                        ..OwnedGame::new(10, "game 10")
+                    },
+                    OwnedGame {
+                        borrowed: true,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                ],
+            ),
+            (
+                2,
+                vec![
+                    OwnedGame {
+                        playtime_forever: 30,
+                        ..OwnedGame::new(10, "game 10")
+                    },
+                    OwnedGame {
+                        playtime_forever: 5,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                    OwnedGame {
+                        playtime_forever: 1,
+                        ..OwnedGame::new(30, "game 30")
+                    },
+                ],
+            ),
+        ]);
+        let summary = merged
+            .iter()
+            .map(|owned_game| {
+                (
+                    owned_game.game.appid,
+                    owned_game.playtime_forever,
+                    owned_game.borrowed,
+                    owned_game.account,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (10, 90, false, None),
+                (20, 5, false, Some(2)),
+                (30, 1, false, Some(2)),
+            ]
+        );
+    }
+}
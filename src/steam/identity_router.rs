@@ -0,0 +1,154 @@
+use clap::{ArgMatches, Command};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use super::{
+    arg_matcher::{self, SharedArgs},
+    client::UserSummary,
+    games_router::persona_name,
+    identities::{load_identities, Identities},
+    logger::FilteringLogger,
+    output::CommandOutput,
+    registry::SteamCommand,
+    router::{resolve_players, unknown_subcommand, Error},
+    service::get_user_summaries_in_batches,
+};
+
+pub struct IdentityCommand;
+
+impl SteamCommand for IdentityCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::identity_command(args)
+    }
+
+    fn uses_local_files(&self) -> bool {
+        true
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_identity_command(arguments, user_steam_id, logger))
+    }
+}
+
+pub async fn run_identity_command<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    match arguments.subcommand() {
+        Some(("link", arguments)) => {
+            let name = get_identity_name(arguments)?;
+            let accounts = get_accounts(arguments, user_steam_id, logger).await?;
+            let mut identities = load_identities()?;
+            identities.link(name, &accounts)?;
+            identities.save()?;
+            show_identities(identities, logger).await
+        }
+        Some(("unlink", arguments)) => {
+            let name = get_identity_name(arguments)?;
+            let accounts = get_accounts(arguments, user_steam_id, logger).await?;
+            let mut identities = load_identities()?;
+            identities.unlink(name, &accounts)?;
+            identities.save()?;
+            show_identities(identities, logger).await
+        }
+        Some(("list", _)) => show_identities(load_identities()?, logger).await,
+        _ => Err(unknown_subcommand(arguments)),
+    }
+}
+
+fn get_identity_name(arguments: &ArgMatches) -> Result<&str, Error> {
+    Ok(arguments
+        .get_one::<String>("name")
+        .ok_or(Error::Argument("name is required".to_string()))?
+        .trim())
+}
+
+async fn get_accounts<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<u64>, Error> {
+    let accounts = arguments
+        .get_many::<String>("accounts")
+        .into_iter()
+        .flatten()
+        .map(String::as_str)
+        .collect();
+    resolve_players(arguments, user_steam_id, accounts, logger).await
+}
+
+async fn show_identities<'a>(
+    identities: Identities,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    let summaries = get_user_summaries_in_batches(
+        &identities
+            .identities
+            .values()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>(),
+        logger,
+    )
+    .await?;
+    Ok(CommandOutput::Identities {
+        identities,
+        summaries,
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountRecord<'a> {
+    identity: &'a str,
+    steamid: u64,
+    name: String,
+    main: bool,
+}
+
+/// One record per account, with the main account of each identity marked
+pub fn compute_account_records<'a>(
+    identities: &'a Identities,
+    summaries: &[UserSummary],
+) -> Vec<AccountRecord<'a>> {
+    identities
+        .identities
+        .iter()
+        .flat_map(|(identity, accounts)| {
+            accounts
+                .iter()
+                .enumerate()
+                .map(|(index, account)| AccountRecord {
+                    identity,
+                    steamid: *account,
+                    name: persona_name(summaries, *account),
+                    main: index == 0,
+                })
+        })
+        .collect()
+}
+
+pub fn compute_identities_string(identities: &Identities, summaries: &[UserSummary]) -> String {
+    format!(
+        "{identities}\n\tTotal: {total}\n",
+        identities = identities
+            .identities
+            .iter()
+            .map(|(name, accounts)| format!(
+                "{name}: {}",
+                accounts
+                    .iter()
+                    .map(|account| format!("{} ({account})", persona_name(summaries, *account)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = identities.identities.len(),
+    )
+}
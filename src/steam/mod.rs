@@ -1,7 +1,29 @@
+pub mod alias_router;
+pub mod aliases;
+pub mod app_index;
+pub mod app_types;
 pub mod arg_matcher;
 pub mod client;
+pub mod cliques;
+pub mod config;
+pub mod config_router;
+pub mod friend_graph;
+pub mod friends_router;
 pub mod games_router;
+pub mod group_router;
+pub mod groups;
+pub mod identities;
+pub mod identity_router;
 pub mod logger;
 pub mod models;
+pub mod name_resolver;
+pub mod output;
+pub mod playtime_filter;
+pub mod purchase_optimizer;
+pub mod recommender;
+pub mod registry;
 pub mod router;
 pub mod service;
+pub mod similarity;
+pub mod sorting;
+pub mod wishlist_router;
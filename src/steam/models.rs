@@ -1,12 +1,31 @@
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+};
 
-#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, Hash, Clone)] // Derive Deserialize and Serialize traits for Game
+/// Games are compared by appid alone, since the same game can be named differently depending on
+/// where it came from, e.g., the family library instead of GetOwnedGames
+#[derive(Debug, Deserialize, Serialize, Clone)] // Derive Deserialize and Serialize traits for Game
 pub struct Game {
     pub name: String,
     pub appid: u64,
 }
 
+impl PartialEq for Game {
+    fn eq(&self, other: &Self) -> bool {
+        self.appid == other.appid
+    }
+}
+
+impl Eq for Game {}
+
+impl Hash for Game {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.appid.hash(state);
+    }
+}
+
 impl fmt::Display for Game {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -16,3 +35,42 @@ impl fmt::Display for Game {
         )
     }
 }
+
+/// A game as returned by GetOwnedGames, along with the owner's playtime for it. Playtimes are in
+/// minutes
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OwnedGame {
+    #[serde(flatten)]
+    pub game: Game,
+    #[serde(default)]
+    pub playtime_forever: u64,
+    #[serde(default)]
+    pub playtime_2weeks: u64,
+    #[serde(default)]
+    pub rtime_last_played: u64,
+    /// true if the game is playable through a Steam Family instead of being owned
+    #[serde(default)]
+    pub borrowed: bool,
+    /// the linked account that owns the game, if it isn't owned on the player's own account
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account: Option<u64>,
+}
+
+#[cfg(test)]
+impl OwnedGame {
+    /// An owned game that has never been played, for tests to fill in the rest of with struct
+    /// update syntax
+    pub fn new(appid: u64, name: &str) -> OwnedGame {
+        OwnedGame {
+            game: Game {
+                name: name.to_string(),
+                appid,
+            },
+            playtime_forever: 0,
+            playtime_2weeks: 0,
+            rtime_last_played: 0,
+            borrowed: false,
+            account: None,
+        }
+    }
+}
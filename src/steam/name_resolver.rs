@@ -0,0 +1,214 @@
+use std::fmt::Display;
+
+use super::client::UserSummary;
+
+/// The default minimum fuzzy match score for a name to count as a match at all
+pub const DEFAULT_THRESHOLD: u32 = 50;
+/// A runner up scoring at least this fraction of the best match makes the name ambiguous
+const AMBIGUITY_RATIO: f64 = 0.9;
+const MAX_CANDIDATES: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchedField {
+    PersonaName,
+    RealName,
+}
+
+impl Display for MatchedField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MatchedField::PersonaName => write!(f, "persona name"),
+            MatchedField::RealName => write!(f, "real name"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Candidate<'a> {
+    pub summary: &'a UserSummary,
+    pub score: u32,
+    /// whichever of the persona name and real name scored higher
+    pub matched: MatchedField,
+}
+
+impl<'a> Display for Candidate<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} ({}), matched {} with a score of {}",
+            self.summary.personaname, self.summary.steamid, self.matched, self.score
+        )
+    }
+}
+
+/// Matches names against a fixed set of players. The names to match against are collected once up
+/// front, so any number of names can be resolved without refetching or rebuilding anything
+pub struct NameResolver<'a> {
+    /// every persona name and real name, along with the index of the summary it came from
+    names: Vec<(&'a str, usize, MatchedField)>,
+    summaries: &'a [UserSummary],
+    threshold: u32,
+}
+
+impl<'a> NameResolver<'a> {
+    pub fn new(summaries: &'a [UserSummary], threshold: u32) -> Self {
+        let names = summaries
+            .iter()
+            .enumerate()
+            .flat_map(|(index, summary)| {
+                std::iter::once((
+                    summary.personaname.as_str(),
+                    index,
+                    MatchedField::PersonaName,
+                ))
+                .chain(
+                    summary
+                        .realname
+                        .iter()
+                        .map(move |realname| (realname.as_str(), index, MatchedField::RealName)),
+                )
+            })
+            .collect();
+        NameResolver {
+            names,
+            summaries,
+            threshold,
+        }
+    }
+
+    /// Resolve the name to a single player. An exact (case insensitive) persona name match always
+    /// wins; otherwise the best fuzzy match is used unless another player scores nearly as well
+    pub fn resolve(&self, name: &str) -> Result<&'a UserSummary, Error<'a>> {
+        let exact_matches = self
+            .summaries
+            .iter()
+            .filter(|summary| summary.personaname.eq_ignore_ascii_case(name))
+            .collect::<Vec<_>>();
+        if let [summary] = exact_matches[..] {
+            return Ok(summary);
+        }
+
+        let candidates = self.candidates(name);
+        match &candidates[..] {
+            [] => Err(Error::NotFound(name.to_string())),
+            [best] => Ok(best.summary),
+            [best, runner_up, ..] => {
+                if (runner_up.score as f64) < best.score as f64 * AMBIGUITY_RATIO {
+                    Ok(best.summary)
+                } else {
+                    Err(Error::Ambiguous(
+                        name.to_string(),
+                        candidates.into_iter().take(MAX_CANDIDATES).collect(),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Fuzzy match the name against both the persona names and the real names, keeping each
+    /// player's best score. Players scoring at or below the threshold are left out. Best matches
+    /// first
+    pub fn candidates(&self, name: &str) -> Vec<Candidate<'a>> {
+        let matches = nucleo_matcher::pattern::Pattern::parse(
+            name,
+            nucleo_matcher::pattern::CaseMatching::Ignore,
+            nucleo_matcher::pattern::Normalization::Smart,
+        )
+        .match_list_with_index(
+            self.names.iter().map(|(name, _, _)| name),
+            &mut nucleo_matcher::Matcher::new(nucleo_matcher::Config::DEFAULT),
+        );
+        let mut candidates: Vec<Candidate> = vec![];
+        // the matches are sorted best first, so the first match for each player is their best
+        for (_, score, index) in matches {
+            let (_, summary_index, matched) = self.names[index];
+            let summary = &self.summaries[summary_index];
+            if score > self.threshold
+                && !candidates
+                    .iter()
+                    .any(|candidate| std::ptr::eq(candidate.summary, summary))
+            {
+                candidates.push(Candidate {
+                    summary,
+                    score,
+                    matched,
+                });
+            }
+        }
+        candidates
+    }
+}
+
+#[derive(Debug)]
+pub enum Error<'a> {
+    NotFound(String),
+    Ambiguous(String, Vec<Candidate<'a>>),
+}
+
+impl<'a> Display for Error<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NotFound(name) => write!(f, "could not match {name} to any friend"),
+            Error::Ambiguous(name, candidates) => write!(
+                f,
+                "{name} matches several friends, try a more specific name or one of these steam ids:\n{}",
+                candidates
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, MatchedField, NameResolver};
+    use crate::steam::client::UserSummary;
+
+    fn summary(steamid: &str, personaname: &str, realname: Option<&str>) -> UserSummary {
+        UserSummary {
+            steamid: steamid.to_string(),
+            personaname: personaname.to_string(),
+            realname: realname.map(str::to_string),
+            lastlogoff: None,
+            communityvisibilitystate: None,
+        }
+    }
+
+    fn summaries() -> Vec<UserSummary> {
+        vec![
+            summary("1", "xXSniperXx", Some("Chris West")),
+            summary("2", "sniper", None),
+            summary("3", "Chris", Some("Christopher Robin")),
+            summary("4", "Christine", None),
+        ]
+    }
+
+    #[test]
+    fn exact_persona_name_wins() {
+        let summaries = summaries();
+        let resolver = NameResolver::new(&summaries, 0);
+        assert_eq!(resolver.resolve("Sniper").unwrap().steamid, "2");
+    }
+
+    #[test]
+    fn matches_real_names() {
+        let summaries = summaries();
+        let resolver = NameResolver::new(&summaries, 0);
+        let candidates = resolver.candidates("chris west");
+        assert_eq!(candidates[0].summary.steamid, "1");
+        assert_eq!(candidates[0].matched, MatchedField::RealName);
+    }
+
+    #[test]
+    fn similar_names_are_ambiguous() {
+        let summaries = summaries();
+        let resolver = NameResolver::new(&summaries, 0);
+        match resolver.resolve("chri") {
+            Err(Error::Ambiguous(_, candidates)) => assert!(candidates.len() > 1),
+            other => panic!("expected an ambiguous match, got {other:?}"),
+        }
+    }
+}
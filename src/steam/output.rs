@@ -0,0 +1,623 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use super::{
+    alias_router::{
+        compute_alias_record, compute_alias_records, compute_alias_string, compute_aliases_string,
+    },
+    aliases::AliasBook,
+    client::{GetAvailableEndpointsResponse, GetGameInfoResponse, UserSummary},
+    cliques::Clique,
+    config::Profile,
+    config_router::{
+        compute_config_problems_string, compute_config_records, compute_config_string,
+        compute_problem_records,
+    },
+    friend_graph::{compute_graph_string, compute_player_nodes, FriendGraph, GraphFormat},
+    friends_router::{
+        compute_clique_records, compute_cliques_string, compute_friend_records,
+        compute_friends_since_string, compute_inactive_friend_records,
+        compute_inactive_friends_string, compute_overlap_matrix_string, compute_overlap_records,
+        compute_privacy_records, compute_privacy_string, compute_similar_friends_string,
+        compute_similarity_records,
+    },
+    games_router::{
+        compute_cost_to_complete_string, compute_game_info_record, compute_game_info_string,
+        compute_listed_games_string, compute_missing_game_records,
+        compute_ownership_matrix_records, compute_ownership_matrix_string,
+        compute_playable_records, compute_playable_together_string, compute_purchase_plan_string,
+        compute_purchase_records, compute_recommendation_records, compute_recommendations_string,
+        persona_name,
+    },
+    group_router::{
+        compute_group_records, compute_group_string, compute_groups_string, compute_member_records,
+    },
+    groups::{Group, Groups},
+    identities::Identities,
+    identity_router::{compute_account_records, compute_identities_string},
+    models::Game,
+    purchase_optimizer::Candidate,
+    recommender::Recommendation,
+    router::{compute_resolution_records, compute_resolutions_string},
+    service::{
+        FriendDetails, FriendWithGame, GameMetadata, OwnershipMatrix, PlayableTogether, Price,
+        PrivacyIssue, WishlistOverlap,
+    },
+    similarity::Similarity,
+    wishlist_router::{compute_wishlist_overlap_string, compute_wishlist_records},
+};
+
+/// What a command returns. Each frontend decides how to show it, and anything a frontend doesn't
+/// lay out itself can be rendered with render
+pub enum CommandOutput {
+    /// games in the order they should be listed
+    Games(Vec<ListedGame>),
+    /// the store details of each game, or None if there weren't any
+    GameDetails(Vec<(Game, Option<GameMetadata>)>),
+    GameInfo(GetGameInfoResponse),
+    Users(Vec<UserSummary>),
+    FriendsWithGame(Vec<FriendWithGame>),
+    Endpoints(GetAvailableEndpointsResponse),
+    /// each name passed to resolve, along with the friend it's an alias of if it is one
+    Resolutions {
+        names: Vec<(String, Option<u64>)>,
+        summaries: Vec<UserSummary>,
+        threshold: u32,
+    },
+    OwnershipMatrix {
+        matrix: OwnershipMatrix,
+        summaries: Vec<UserSummary>,
+        show_hours: bool,
+    },
+    /// the games each player is missing, sorted by name, with their prices
+    CostToComplete {
+        missing_games: Vec<(u64, Vec<Game>)>,
+        prices: HashMap<u64, Price>,
+        summaries: Vec<UserSummary>,
+    },
+    PurchasePlan {
+        chosen: Vec<Candidate>,
+        games_in_common: usize,
+        currency: String,
+        summaries: Vec<UserSummary>,
+    },
+    PlayableTogether {
+        playable: PlayableTogether,
+        summaries: Vec<UserSummary>,
+    },
+    /// seed is set if the recommendations were picked randomly
+    Recommendations {
+        recommendations: Vec<Recommendation>,
+        seed: Option<u64>,
+    },
+    WishlistOverlap {
+        overlap: Vec<WishlistOverlap>,
+        summaries: Vec<UserSummary>,
+    },
+    /// the friends most similar to the user, most similar first
+    Similarities {
+        similarities: Vec<Similarity>,
+        summaries: Vec<UserSummary>,
+    },
+    /// how similar each player is to each of the others, one row per player
+    SimilarityMatrix {
+        steam_ids: Vec<u64>,
+        similarities: Vec<Vec<Similarity>>,
+        summaries: Vec<UserSummary>,
+    },
+    /// saved is set if the cliques were also saved as groups
+    Cliques {
+        cliques: Vec<Clique>,
+        summaries: Vec<UserSummary>,
+        saved: bool,
+    },
+    FriendGraph {
+        graph: FriendGraph,
+        summaries: Vec<UserSummary>,
+        format: GraphFormat,
+    },
+    /// friends in the order they should be listed, as of now
+    FriendsSince {
+        friends: Vec<FriendDetails>,
+        now: u64,
+    },
+    /// unknown holds the friends whose last time online couldn't be read
+    InactiveFriends {
+        inactive: Vec<FriendDetails>,
+        unknown: Vec<FriendDetails>,
+        now: u64,
+    },
+    /// the friends whose libraries can't be read, out of total friends
+    PrivacyIssues {
+        issues: Vec<(FriendDetails, PrivacyIssue)>,
+        total: usize,
+    },
+    /// the alias that was just added, with the alias book for the past names of its friend
+    Alias {
+        alias: String,
+        steam_id: u64,
+        aliases: AliasBook,
+        summaries: Vec<UserSummary>,
+    },
+    Aliases {
+        aliases: AliasBook,
+        summaries: Vec<UserSummary>,
+    },
+    /// a single group, with the members of any nested groups expanded
+    Group {
+        name: String,
+        group: Group,
+        members: Vec<u64>,
+        summaries: Vec<UserSummary>,
+    },
+    Groups(Groups),
+    Identities {
+        identities: Identities,
+        summaries: Vec<UserSummary>,
+    },
+    /// the settings in effect, and the profile in the config file they're based on
+    Config {
+        path: PathBuf,
+        profile_name: String,
+        file_profile: Profile,
+        values: Profile,
+    },
+    /// everything config validate found wrong
+    ConfigProblems(Vec<String>),
+    /// what a command did, e.g., removing an alias
+    Message(String),
+}
+
+/// A game in a list, along with who only borrows it and which linked accounts own it
+#[derive(Debug, Serialize)]
+pub struct ListedGame {
+    #[serde(flatten)]
+    pub game: Game,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub borrowed_by: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub owned_on: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct EndpointRecord<'a> {
+    interface: &'a str,
+    method: &'a str,
+    version: i32,
+    httpmethod: &'a str,
+    parameters: Vec<&'a str>,
+}
+
+impl CommandOutput {
+    /// The text that's shown by default along with the records for the structured formats
+    pub fn into_output(self) -> Result<Output, serde_json::Error> {
+        match self {
+            CommandOutput::Games(games) => Output::new(compute_listed_games_string(&games), games),
+            CommandOutput::GameDetails(games) => Output::new(
+                compute_game_info_string(games.iter().map(|(_, metadata)| metadata.as_ref())),
+                games
+                    .iter()
+                    .map(|(game, metadata)| compute_game_info_record(game, metadata.as_ref())),
+            ),
+            CommandOutput::GameInfo(game_info) => Output::new(
+                format!("{:?}", game_info),
+                game_info
+                    .games
+                    .values()
+                    .filter_map(|info| info.data.as_ref()),
+            ),
+            CommandOutput::Users(users) => {
+                Output::new(serde_json::to_string_pretty(&users)?, users)
+            }
+            CommandOutput::FriendsWithGame(friends) => Output::new(
+                format!(
+                    "{}\nTotal: {}",
+                    serde_json::to_string_pretty(&friends)?,
+                    friends.len()
+                ),
+                friends,
+            ),
+            CommandOutput::Endpoints(endpoints) => Output::new(
+                serde_json::to_string_pretty(&endpoints)?,
+                endpoints.apilist.interfaces.iter().flat_map(|interface| {
+                    interface.methods.iter().map(|method| EndpointRecord {
+                        interface: &interface.name,
+                        method: &method.name,
+                        version: method.version,
+                        httpmethod: &method.httpmethod,
+                        parameters: method
+                            .parameters
+                            .iter()
+                            .map(|parameter| parameter.name.as_str())
+                            .collect(),
+                    })
+                }),
+            ),
+            CommandOutput::Resolutions {
+                names,
+                summaries,
+                threshold,
+            } => Output::new(
+                compute_resolutions_string(&names, &summaries, threshold),
+                compute_resolution_records(&names, &summaries, threshold),
+            ),
+            CommandOutput::OwnershipMatrix {
+                matrix,
+                summaries,
+                show_hours,
+            } => Output::new(
+                compute_ownership_matrix_string(&matrix, &summaries, show_hours),
+                compute_ownership_matrix_records(&matrix, &summaries, show_hours),
+            ),
+            CommandOutput::CostToComplete {
+                missing_games,
+                prices,
+                summaries,
+            } => Output::new(
+                compute_cost_to_complete_string(missing_games.clone(), &prices, &summaries),
+                compute_missing_game_records(&missing_games, &prices, &summaries),
+            ),
+            CommandOutput::PurchasePlan {
+                chosen,
+                games_in_common,
+                currency,
+                summaries,
+            } => Output::new(
+                compute_purchase_plan_string(&chosen, games_in_common, &currency, &summaries),
+                compute_purchase_records(&chosen, &currency, &summaries),
+            ),
+            CommandOutput::PlayableTogether {
+                playable,
+                summaries,
+            } => Output::new(
+                compute_playable_together_string(&playable, &summaries),
+                compute_playable_records(&playable, &summaries),
+            ),
+            CommandOutput::Recommendations {
+                recommendations,
+                seed,
+            } => Output::new(
+                compute_recommendations_string(&recommendations, seed),
+                compute_recommendation_records(&recommendations, seed),
+            ),
+            CommandOutput::WishlistOverlap { overlap, summaries } => Output::new(
+                compute_wishlist_overlap_string(&overlap, &summaries),
+                compute_wishlist_records(&overlap, &summaries),
+            ),
+            CommandOutput::Similarities {
+                similarities,
+                summaries,
+            } => Output::new(
+                compute_similar_friends_string(&similarities, &summaries),
+                compute_similarity_records(&similarities, &summaries),
+            ),
+            CommandOutput::SimilarityMatrix {
+                steam_ids,
+                similarities,
+                summaries,
+            } => Output::new(
+                compute_overlap_matrix_string(&steam_ids, &similarities, &summaries),
+                compute_overlap_records(&steam_ids, &similarities, &summaries),
+            ),
+            CommandOutput::Cliques {
+                cliques,
+                summaries,
+                saved,
+            } => Output::new(
+                compute_cliques_string(&cliques, &summaries, saved),
+                compute_clique_records(&cliques, &summaries),
+            ),
+            CommandOutput::FriendGraph {
+                graph,
+                summaries,
+                format,
+            } => {
+                let name = |steam_id| persona_name(&summaries, steam_id);
+                Output::new(
+                    compute_graph_string(&graph, format, name)?,
+                    compute_player_nodes(&graph, name),
+                )
+            }
+            CommandOutput::FriendsSince { friends, now } => Output::new(
+                compute_friends_since_string(&friends, now),
+                compute_friend_records(&friends, now),
+            ),
+            CommandOutput::InactiveFriends {
+                inactive,
+                unknown,
+                now,
+            } => Output::new(
+                compute_inactive_friends_string(
+                    &inactive.iter().collect::<Vec<_>>(),
+                    &unknown.iter().collect::<Vec<_>>(),
+                    now,
+                ),
+                compute_inactive_friend_records(&inactive, &unknown, now),
+            ),
+            CommandOutput::PrivacyIssues { issues, total } => Output::new(
+                compute_privacy_string(&issues, total),
+                compute_privacy_records(&issues),
+            ),
+            CommandOutput::Alias {
+                alias,
+                steam_id,
+                aliases,
+                summaries,
+            } => Output::new(
+                compute_alias_string(&alias, steam_id, &summaries),
+                [compute_alias_record(&alias, steam_id, &aliases, &summaries)],
+            ),
+            CommandOutput::Aliases { aliases, summaries } => Output::new(
+                compute_aliases_string(&aliases, &summaries),
+                compute_alias_records(&aliases, &summaries),
+            ),
+            CommandOutput::Group {
+                name,
+                group,
+                members,
+                summaries,
+            } => Output::new(
+                compute_group_string(&name, &group, &members, &summaries),
+                compute_member_records(&members, &summaries),
+            ),
+            CommandOutput::Groups(groups) => Output::new(
+                compute_groups_string(&groups),
+                compute_group_records(&groups),
+            ),
+            CommandOutput::Identities {
+                identities,
+                summaries,
+            } => Output::new(
+                compute_identities_string(&identities, &summaries),
+                compute_account_records(&identities, &summaries),
+            ),
+            CommandOutput::Config {
+                path,
+                profile_name,
+                file_profile,
+                values,
+            } => Output::new(
+                compute_config_string(&path, &profile_name, &file_profile, &values),
+                compute_config_records(&file_profile, &values),
+            ),
+            CommandOutput::ConfigProblems(problems) => Output::new(
+                compute_config_problems_string(&problems),
+                compute_problem_records(&problems),
+            ),
+            CommandOutput::Message(text) => Ok(Output::message(text)),
+        }
+    }
+}
+
+/// Render the output in the format, the way the terminal shows it
+pub fn render(output: CommandOutput, format: OutputFormat) -> Result<String, serde_json::Error> {
+    Ok(output.into_output()?.render(format))
+}
+
+/// How a command's result is written out. Text is what each command has always printed, while the
+/// others are meant for piping into other tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+    Table,
+    Markdown,
+}
+
+/// The result of a command, both as the text it prints by default and as records, one per row, for
+/// the structured formats
+#[derive(Debug)]
+pub struct Output {
+    text: String,
+    records: Vec<Map<String, Value>>,
+}
+
+impl Output {
+    /// Records that don't serialize to objects are put in a value column
+    pub fn new<T: Serialize>(
+        text: String,
+        records: impl IntoIterator<Item = T>,
+    ) -> Result<Output, serde_json::Error> {
+        let records = records
+            .into_iter()
+            .map(|record| {
+                Ok(match serde_json::to_value(record)? {
+                    Value::Object(record) => record,
+                    value => Map::from_iter([("value".to_string(), value)]),
+                })
+            })
+            .collect::<Result<_, serde_json::Error>>()?;
+        Ok(Output { text, records })
+    }
+
+    /// Output for commands that only report what they did
+    pub fn message(text: String) -> Output {
+        Output {
+            records: vec![Map::from_iter([(
+                "message".to_string(),
+                Value::String(text.clone()),
+            )])],
+            text,
+        }
+    }
+
+    pub fn render(&self, format: OutputFormat) -> String {
+        match format {
+            OutputFormat::Text => self.text.clone(),
+            OutputFormat::Json => format!(
+                "{:#}",
+                Value::Array(self.records.iter().cloned().map(Value::Object).collect())
+            ),
+            OutputFormat::Ndjson => self
+                .records
+                .iter()
+                .map(|record| Value::Object(record.clone()).to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            OutputFormat::Csv => self
+                .rows(|cell| {
+                    if cell.contains([',', '"', '\n', '\r']) {
+                        format!("\"{}\"", cell.replace('"', "\"\""))
+                    } else {
+                        cell
+                    }
+                })
+                .iter()
+                .map(|row| row.join(","))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            OutputFormat::Table => {
+                let rows = self.rows(|cell| cell.replace(['\n', '\r'], " "));
+                let mut widths = vec![0; rows.first().map_or(0, Vec::len)];
+                for row in &rows {
+                    for (width, cell) in widths.iter_mut().zip(row) {
+                        *width = (*width).max(cell.chars().count());
+                    }
+                }
+                let mut lines = rows
+                    .iter()
+                    .map(|row| {
+                        row.iter()
+                            .zip(&widths)
+                            .map(|(cell, width)| format!("{cell:width$}"))
+                            .collect::<Vec<_>>()
+                            .join("  ")
+                            .trim_end()
+                            .to_string()
+                    })
+                    .collect::<Vec<_>>();
+                if !lines.is_empty() {
+                    lines.insert(
+                        1,
+                        widths
+                            .iter()
+                            .map(|width| "-".repeat(*width))
+                            .collect::<Vec<_>>()
+                            .join("  "),
+                    );
+                }
+                lines.join("\n")
+            }
+            OutputFormat::Markdown => {
+                let mut lines = self
+                    .rows(|cell| cell.replace('|', "\\|").replace('\n', "<br>"))
+                    .iter()
+                    .map(|row| format!("| {} |", row.join(" | ")))
+                    .collect::<Vec<_>>();
+                if !lines.is_empty() {
+                    let columns = self.columns().len();
+                    lines.insert(1, format!("|{}", " --- |".repeat(columns)));
+                }
+                lines.join("\n")
+            }
+        }
+    }
+
+    /// Every key used by any record, in the order they first show up
+    fn columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        for key in self.records.iter().flat_map(Map::keys) {
+            if !columns.contains(&key.as_str()) {
+                columns.push(key.as_str());
+            }
+        }
+        columns
+    }
+
+    /// The header followed by one row per record, with every cell passed through escape
+    fn rows(&self, escape: impl Fn(String) -> String) -> Vec<Vec<String>> {
+        let columns = self.columns();
+        if columns.is_empty() {
+            return vec![];
+        }
+        let mut rows = vec![columns
+            .iter()
+            .map(|column| escape(column.to_string()))
+            .collect::<Vec<_>>()];
+        rows.extend(self.records.iter().map(|record| {
+            columns
+                .iter()
+                .map(|column| escape(record.get(*column).map_or(String::new(), cell)))
+                .collect()
+        }));
+        rows
+    }
+}
+
+/// A value as it's shown in a single cell. Lists are joined, and anything nested deeper is left as
+/// json
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        Value::Array(values)
+            if !values
+                .iter()
+                .any(|value| value.is_array() || value.is_object()) =>
+        {
+            values.iter().map(cell).collect::<Vec<_>>().join(", ")
+        }
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde::Serialize;
+
+    use super::{Output, OutputFormat};
+
+    #[derive(Serialize)]
+    struct Row {
+        name: &'static str,
+        appid: u64,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        owners: Vec<&'static str>,
+    }
+
+    fn output() -> Output {
+        Output::new(
+            "text".to_string(),
+            [
+                Row {
+                    name: "Portal 2",
+                    appid: 620,
+                    owners: vec![],
+                },
+                Row {
+                    name: "Stardew, \"Valley\"",
+                    appid: 413150,
+                    owners: vec!["a", "b"],
+                },
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn renders_every_format() {
+        let output = output();
+        assert_eq!(output.render(OutputFormat::Text), "text");
+        assert_eq!(
+            output.render(OutputFormat::Ndjson),
+            "{\"name\":\"Portal 2\",\"appid\":620}\n{\"name\":\"Stardew, \\\"Valley\\\"\",\"appid\":413150,\"owners\":[\"a\",\"b\"]}"
+        );
+        assert_eq!(
+            output.render(OutputFormat::Csv),
+            "name,appid,owners\nPortal 2,620,\n\"Stardew, \"\"Valley\"\"\",413150,\"a, b\""
+        );
+        assert_eq!(
+            output.render(OutputFormat::Table),
+            "name               appid   owners\n-----------------  ------  ------\nPortal 2           620\nStardew, \"Valley\"  413150  a, b"
+        );
+        assert_eq!(
+            output.render(OutputFormat::Markdown),
+            "| name | appid | owners |\n| --- | --- | --- |\n| Portal 2 | 620 |  |\n| Stardew, \"Valley\" | 413150 | a, b |"
+        );
+    }
+}
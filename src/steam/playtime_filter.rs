@@ -0,0 +1,148 @@
+use super::models::{Game, OwnedGame};
+
+/// Filters games by how much the group has played them, using the playtimes from GetOwnedGames
+#[derive(Debug, Default)]
+pub struct PlaytimeFilter {
+    /// only keep games that nobody in the group has played
+    pub unplayed_by_all: bool,
+    /// only keep games that everyone in the group has played at least this many minutes
+    pub min_minutes_each: Option<u64>,
+    /// only keep games that are in this library and have been played
+    pub played_by: Option<Vec<OwnedGame>>,
+    /// only keep games that nobody in the group has played since this unix timestamp
+    pub not_played_since: Option<u64>,
+}
+
+impl PlaytimeFilter {
+    pub fn matches(&self, game: &Game, libraries: &[(u64, Vec<OwnedGame>)]) -> bool {
+        let copies = libraries
+            .iter()
+            .filter_map(|(_, games)| find_game(games, game))
+            .collect::<Vec<_>>();
+        if self.unplayed_by_all && copies.iter().any(|copy| copy.playtime_forever > 0) {
+            return false;
+        }
+        if let Some(min_minutes_each) = self.min_minutes_each {
+            // a member without a copy has played it for 0 minutes
+            if copies.len() < libraries.len()
+                || copies
+                    .iter()
+                    .any(|copy| copy.playtime_forever < min_minutes_each)
+            {
+                return false;
+            }
+        }
+        if let Some(played_by) = &self.played_by {
+            if find_game(played_by, game).is_none_or(|copy| copy.playtime_forever == 0) {
+                return false;
+            }
+        }
+        if let Some(not_played_since) = self.not_played_since {
+            if copies
+                .iter()
+                .any(|copy| copy.rtime_last_played >= not_played_since)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn find_game<'a>(games: &'a [OwnedGame], game: &Game) -> Option<&'a OwnedGame> {
+    games
+        .iter()
+        .find(|owned_game| owned_game.game.appid == game.appid)
+}
+
+#[cfg(test)]
+mod test {
+    use super::PlaytimeFilter;
+    use crate::steam::models::{Game, OwnedGame};
+
+    fn game(appid: u64) -> Game {
+        Game {
+            name: format!("game {appid}"),
+            appid,
+        }
+    }
+
+    fn matching_appids(filter: &PlaytimeFilter) -> Vec<u64> {
+        let libraries = vec![
+            (
+                1,
+                vec![
+                    OwnedGame::new(10, "game 10"),
+                    OwnedGame {
+                        playtime_forever: 300,
+                        rtime_last_played: 500,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                    OwnedGame::new(30, "game 30"),
+                ],
+            ),
+            (
+                2,
+                vec![
+                    OwnedGame::new(10, "game 10"),
+                    OwnedGame {
+                        playtime_forever: 120,
+                        rtime_last_played: 1000,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                    OwnedGame {
+                        playtime_forever: 60,
+                        rtime_last_played: 50,
+                        ..OwnedGame::new(30, "game 30")
+                    },
+                ],
+            ),
+        ];
+        [10, 20, 30]
+            .into_iter()
+            .filter(|appid| filter.matches(&game(*appid), &libraries))
+            .collect()
+    }
+
+    #[test]
+    fn unplayed_by_all() {
+        let filter = PlaytimeFilter {
+            unplayed_by_all: true,
+            ..Default::default()
+        };
+        assert_eq!(matching_appids(&filter), vec![10]);
+    }
+
+    #[test]
+    fn min_minutes_each() {
+        let filter = PlaytimeFilter {
+            min_minutes_each: Some(120),
+            ..Default::default()
+        };
+        assert_eq!(matching_appids(&filter), vec![20]);
+    }
+
+    #[test]
+    fn not_played_since() {
+        let filter = PlaytimeFilter {
+            not_played_since: Some(600),
+            ..Default::default()
+        };
+        assert_eq!(matching_appids(&filter), vec![10, 30]);
+    }
+
+    #[test]
+    fn played_by() {
+        let filter = PlaytimeFilter {
+            played_by: Some(vec![
+                OwnedGame {
+                    playtime_forever: 5,
+                    ..OwnedGame::new(10, "game 10")
+                },
+                OwnedGame::new(20, "game 20"),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(matching_appids(&filter), vec![10]);
+    }
+}
@@ -0,0 +1,228 @@
+use clap::ValueEnum;
+use std::collections::HashMap;
+
+use super::models::{Game, OwnedGame};
+
+/// The exact optimizer tries every combination of candidates, so it's only practical for small
+/// groups with a handful of candidate games
+pub const MAX_EXACT_CANDIDATES: usize = 20;
+
+/// How optimize-purchases searches for the games to buy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OptimizeMode {
+    Greedy,
+    Exact,
+}
+
+/// A game that the whole group could play if every member in buyers bought it
+#[derive(Debug, Clone)]
+pub struct Candidate {
+    pub game: Game,
+    pub buyers: Vec<u64>,
+    /// price of one copy in the smallest unit of the currency, e.g., cents
+    pub unit_price: u64,
+}
+
+impl Candidate {
+    pub fn cost(&self) -> u64 {
+        self.unit_price * self.buyers.len() as u64
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Constraints {
+    /// in the smallest unit of the currency, e.g., cents
+    pub budget: Option<u64>,
+    pub max_purchases: Option<usize>,
+}
+
+impl Constraints {
+    fn allows(&self, cost: u64, purchases: usize) -> bool {
+        self.budget.is_none_or(|budget| cost <= budget)
+            && self
+                .max_purchases
+                .is_none_or(|max_purchases| purchases <= max_purchases)
+    }
+}
+
+/// Find the games that at least one member of the group owns and that at most max_missing members
+/// are missing. Each game is returned with the members that would need to buy it
+pub fn find_candidates(
+    libraries: &[(u64, Vec<OwnedGame>)],
+    max_missing: usize,
+) -> Vec<(Game, Vec<u64>)> {
+    let mut owners_by_game = HashMap::<&Game, Vec<u64>>::new();
+    for (id, games) in libraries {
+        for owned_game in games {
+            owners_by_game
+                .entry(&owned_game.game)
+                .or_default()
+                .push(*id);
+        }
+    }
+    owners_by_game
+        .into_iter()
+        .filter_map(|(game, owners)| {
+            let buyers = libraries
+                .iter()
+                .map(|(id, _)| *id)
+                .filter(|id| !owners.contains(id))
+                .collect::<Vec<_>>();
+            if buyers.is_empty() || buyers.len() > max_missing {
+                None
+            } else {
+                Some((game.clone(), buyers))
+            }
+        })
+        .collect()
+}
+
+/// Pick the games needing the fewest purchases first, breaking ties by cost, skipping any game
+/// that doesn't fit within the constraints
+pub fn optimize_greedily(
+    mut candidates: Vec<Candidate>,
+    constraints: &Constraints,
+) -> Vec<Candidate> {
+    candidates.sort_by(|a, b| {
+        (a.buyers.len(), a.cost(), &a.game.name).cmp(&(b.buyers.len(), b.cost(), &b.game.name))
+    });
+    let mut cost = 0;
+    let mut purchases = 0;
+    candidates
+        .into_iter()
+        .filter(|candidate| {
+            let fits =
+                constraints.allows(cost + candidate.cost(), purchases + candidate.buyers.len());
+            if fits {
+                cost += candidate.cost();
+                purchases += candidate.buyers.len();
+            }
+            fits
+        })
+        .collect()
+}
+
+/// Try every combination of candidates and return the one that unlocks the most games, then
+/// needs the fewest purchases, then costs the least. Callers should keep the number of
+/// candidates at or below MAX_EXACT_CANDIDATES
+pub fn optimize_exactly(
+    mut candidates: Vec<Candidate>,
+    constraints: &Constraints,
+) -> Vec<Candidate> {
+    candidates.sort_by(|a, b| {
+        (a.buyers.len(), a.cost(), &a.game.name).cmp(&(b.buyers.len(), b.cost(), &b.game.name))
+    });
+    let mut search = ExactSearch {
+        candidates: &candidates,
+        constraints,
+        current: vec![],
+        best: vec![],
+        best_score: (0, 0, 0),
+    };
+    search.search(0, 0, 0);
+    let best = search.best;
+    best.into_iter()
+        .map(|index| candidates[index].clone())
+        .collect()
+}
+
+struct ExactSearch<'a> {
+    candidates: &'a [Candidate],
+    constraints: &'a Constraints,
+    current: Vec<usize>,
+    best: Vec<usize>,
+    /// (games unlocked, purchases, cost) of the best selection found so far
+    best_score: (usize, usize, u64),
+}
+
+impl<'a> ExactSearch<'a> {
+    fn search(&mut self, index: usize, cost: u64, purchases: usize) {
+        let score = (self.current.len(), purchases, cost);
+        if is_better(score, self.best_score) {
+            self.best_score = score;
+            self.best = self.current.clone();
+        }
+        // even taking every remaining candidate can't unlock more games than the best so far
+        if self.current.len() + (self.candidates.len() - index) < self.best_score.0 {
+            return;
+        }
+        for next in index..self.candidates.len() {
+            let candidate = &self.candidates[next];
+            let next_cost = cost + candidate.cost();
+            let next_purchases = purchases + candidate.buyers.len();
+            if self.constraints.allows(next_cost, next_purchases) {
+                self.current.push(next);
+                self.search(next + 1, next_cost, next_purchases);
+                self.current.pop();
+            }
+        }
+    }
+}
+
+fn is_better(score: (usize, usize, u64), best: (usize, usize, u64)) -> bool {
+    let (games, purchases, cost) = score;
+    let (best_games, best_purchases, best_cost) = best;
+    games > best_games || (games == best_games && (purchases, cost) < (best_purchases, best_cost))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{optimize_exactly, optimize_greedily, Candidate, Constraints};
+    use crate::steam::models::Game;
+
+    fn candidate(name: &str, buyers: Vec<u64>, unit_price: u64) -> Candidate {
+        Candidate {
+            game: Game {
+                name: name.to_string(),
+                appid: unit_price,
+            },
+            buyers,
+            unit_price,
+        }
+    }
+
+    fn names(candidates: &[Candidate]) -> Vec<&str> {
+        let mut names = candidates
+            .iter()
+            .map(|candidate| candidate.game.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn greedy_prefers_fewest_purchases_within_budget() {
+        let candidates = vec![
+            candidate("Cheap For Two", vec![1, 2], 100),
+            candidate("Pricey For One", vec![1], 900),
+            candidate("Cheap For One", vec![2], 300),
+        ];
+        let chosen = optimize_greedily(
+            candidates,
+            &Constraints {
+                budget: Some(600),
+                max_purchases: None,
+            },
+        );
+        assert_eq!(names(&chosen), vec!["Cheap For One", "Cheap For Two"]);
+    }
+
+    #[test]
+    fn exact_beats_greedy_when_the_cheapest_games_need_more_purchases() {
+        // greedy takes the single purchase game first and then can't fit either of the others
+        let candidates = vec![
+            candidate("One Buyer", vec![1], 300),
+            candidate("Two Buyers A", vec![1, 2], 100),
+            candidate("Two Buyers B", vec![2, 3], 100),
+        ];
+        let constraints = Constraints {
+            budget: Some(400),
+            max_purchases: Some(4),
+        };
+        assert!(optimize_greedily(candidates.clone(), &constraints).len() < 2);
+        assert_eq!(
+            names(&optimize_exactly(candidates, &constraints)),
+            vec!["Two Buyers A", "Two Buyers B"]
+        );
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+
+use super::{
+    models::{Game, OwnedGame},
+    service::GameMetadata,
+};
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+/// Members' total hours stop counting towards the score past this many hours each
+const HOURS_CAP: f64 = 20.0;
+/// Every game gets at least this much weight when picking randomly, so that games with a score
+/// of 0 can still come up
+const MIN_PICK_WEIGHT: f64 = 0.01;
+
+const RECENT_PLAYTIME_WEIGHT: f64 = 0.3;
+const TOTAL_HOURS_WEIGHT: f64 = 0.15;
+const REVIEW_WEIGHT: f64 = 0.25;
+const COOP_WEIGHT: f64 = 0.2;
+const LAST_PLAYED_WEIGHT: f64 = 0.1;
+
+/// Co-op, online co-op, shared/split screen co-op and LAN co-op
+const COOP_CATEGORIES: [u8; 4] = [9, 38, 39, 48];
+/// Multi-player, cross-platform multiplayer, online PvP, shared/split screen PvP, LAN PvP and PvP
+const MULTIPLAYER_CATEGORIES: [u8; 6] = [1, 27, 36, 37, 47, 49];
+
+#[derive(Debug, Clone)]
+pub struct Recommendation {
+    pub game: Game,
+    /// between 0 and 1
+    pub score: f64,
+    /// the parts of the score worth mentioning, e.g., "92% positive reviews"
+    pub reasons: Vec<String>,
+}
+
+/// Everything that has already been fetched that games are scored by
+pub struct ScoringContext<'a> {
+    pub libraries: &'a [(u64, Vec<OwnedGame>)],
+    pub metadata: &'a HashMap<u64, GameMetadata>,
+    /// unix timestamp
+    pub now: u64,
+}
+
+/// Score each game by the group's recent playtime, each member's total hours, the reviews, co-op
+/// support and how recently the group last played it. The best scoring games come first
+pub fn recommend(
+    games: impl IntoIterator<Item = Game>,
+    context: &ScoringContext,
+) -> Vec<Recommendation> {
+    let games = games.into_iter().collect::<Vec<_>>();
+    let max_recent_minutes = games
+        .iter()
+        .map(|game| context.recent_minutes(game))
+        .max()
+        .unwrap_or_default();
+    let mut recommendations = games
+        .into_iter()
+        .map(|game| context.score(game, max_recent_minutes))
+        .collect::<Vec<_>>();
+    recommendations.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.game.name.cmp(&b.game.name))
+    });
+    recommendations
+}
+
+impl<'a> ScoringContext<'a> {
+    fn copies(&self, game: &Game) -> impl Iterator<Item = &OwnedGame> {
+        let appid = game.appid;
+        self.libraries.iter().flat_map(move |(_, games)| {
+            games
+                .iter()
+                .filter(move |owned_game| owned_game.game.appid == appid)
+        })
+    }
+
+    fn recent_minutes(&self, game: &Game) -> u64 {
+        self.copies(game)
+            .map(|owned_game| owned_game.playtime_2weeks)
+            .sum()
+    }
+
+    fn score(&self, game: Game, max_recent_minutes: u64) -> Recommendation {
+        let mut reasons = vec![];
+
+        let recent_minutes = self.recent_minutes(&game);
+        let recent_score = if max_recent_minutes == 0 {
+            0.0
+        } else {
+            recent_minutes as f64 / max_recent_minutes as f64
+        };
+        if recent_minutes > 0 {
+            reasons.push(format!(
+                "{:.1}h played by the group in the last 2 weeks",
+                recent_minutes as f64 / 60.0
+            ));
+        }
+
+        // members who don't own the game, e.g., for remote play, count as 0 hours
+        let members = self.libraries.len().max(1) as f64;
+        let hours = self
+            .copies(&game)
+            .map(|owned_game| owned_game.playtime_forever as f64 / 60.0)
+            .collect::<Vec<_>>();
+        let hours_score = hours
+            .iter()
+            .map(|hours| hours.min(HOURS_CAP) / HOURS_CAP)
+            .sum::<f64>()
+            / members;
+        if hours.iter().any(|hours| *hours > 0.0) {
+            reasons.push(format!(
+                "{:.1}h played on average",
+                hours.iter().sum::<f64>() / members
+            ));
+        }
+
+        let metadata = self.metadata.get(&game.appid);
+        // games without reviews are treated as middling
+        let review_score = match metadata.and_then(|metadata| metadata.review.as_ref()) {
+            Some(review) if review.review_count > 0 => {
+                reasons.push(format!("{}% positive reviews", review.percent_positive));
+                review.percent_positive as f64 / 100.0
+            }
+            _ => 0.5,
+        };
+
+        let has_category = |categories: &[u8]| {
+            metadata.is_some_and(|metadata| {
+                categories
+                    .iter()
+                    .any(|category| metadata.categories.contains(category))
+            })
+        };
+        let coop_score = if has_category(&COOP_CATEGORIES) {
+            reasons.push("co-op".to_string());
+            1.0
+        } else if has_category(&MULTIPLAYER_CATEGORIES) {
+            reasons.push("multiplayer".to_string());
+            0.5
+        } else {
+            0.0
+        };
+
+        let last_played = self
+            .copies(&game)
+            .map(|owned_game| owned_game.rtime_last_played)
+            .max()
+            .filter(|last_played| *last_played > 0);
+        let last_played_score = match last_played {
+            None => 0.0,
+            Some(last_played) => {
+                let days = self.now.saturating_sub(last_played) / SECONDS_PER_DAY;
+                reasons.push(match days {
+                    0 => "last played today".to_string(),
+                    1 => "last played yesterday".to_string(),
+                    days => format!("last played {days} days ago"),
+                });
+                1.0 / (1.0 + days as f64 / 30.0)
+            }
+        };
+
+        Recommendation {
+            game,
+            score: RECENT_PLAYTIME_WEIGHT * recent_score
+                + TOTAL_HOURS_WEIGHT * hours_score
+                + REVIEW_WEIGHT * review_score
+                + COOP_WEIGHT * coop_score
+                + LAST_PLAYED_WEIGHT * last_played_score,
+            reasons,
+        }
+    }
+}
+
+/// Randomly pick up to count recommendations, weighted by their scores. The same seed always
+/// picks the same games
+pub fn pick_randomly(
+    mut recommendations: Vec<Recommendation>,
+    count: usize,
+    seed: u64,
+) -> Vec<Recommendation> {
+    let mut rng = SplitMix64(seed);
+    let mut picked = vec![];
+    while picked.len() < count && !recommendations.is_empty() {
+        let total = recommendations
+            .iter()
+            .map(|recommendation| recommendation.score.max(MIN_PICK_WEIGHT))
+            .sum::<f64>();
+        let mut target = rng.next_f64() * total;
+        let index = recommendations
+            .iter()
+            .position(|recommendation| {
+                target -= recommendation.score.max(MIN_PICK_WEIGHT);
+                target < 0.0
+            })
+            .unwrap_or(recommendations.len() - 1);
+        picked.push(recommendations.remove(index));
+    }
+    picked
+}
+
+/// A tiny seeded random number generator. It isn't suitable for anything that needs to be secure,
+/// but it's plenty for picking a game and it gives the same numbers on every platform
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// between 0 inclusive and 1 exclusive
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{pick_randomly, recommend, ScoringContext};
+    use crate::steam::models::{Game, OwnedGame};
+
+    fn game(appid: u64) -> Game {
+        Game {
+            name: format!("game {appid}"),
+            appid,
+        }
+    }
+
+    #[test]
+    fn recent_group_playtime_ranks_first() {
+        let libraries = vec![
+            (
+                1,
+                vec![
+                    OwnedGame {
+                        playtime_forever: 600,
+                        ..OwnedGame::new(10, "game 10")
+                    },
+                    OwnedGame {
+                        playtime_forever: 600,
+                        playtime_2weeks: 120,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                ],
+            ),
+            (
+                2,
+                vec![
+                    OwnedGame::new(10, "game 10"),
+                    OwnedGame {
+                        playtime_forever: 60,
+                        playtime_2weeks: 60,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                ],
+            ),
+        ];
+        let metadata = HashMap::new();
+        let context = ScoringContext {
+            libraries: &libraries,
+            metadata: &metadata,
+            now: 0,
+        };
+        let recommendations = recommend([game(10), game(20)], &context);
+        assert_eq!(recommendations[0].game.appid, 20);
+        assert_eq!(
+            recommendations[0].reasons[0],
+            "3.0h played by the group in the last 2 weeks"
+        );
+    }
+
+    #[test]
+    fn random_picks_are_reproducible() {
+        let libraries = vec![(
+            1,
+            (1..=20)
+                .map(|appid| OwnedGame {
+                    playtime_forever: appid,
+                    ..OwnedGame::new(appid, &format!("game {appid}"))
+                })
+                .collect(),
+        )];
+        let metadata = HashMap::new();
+        let context = ScoringContext {
+            libraries: &libraries,
+            metadata: &metadata,
+            now: 0,
+        };
+        let recommendations = recommend((1..=20).map(game), &context);
+        let appids = |seed| {
+            pick_randomly(recommendations.clone(), 3, seed)
+                .iter()
+                .map(|recommendation| recommendation.game.appid)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(appids(42), appids(42));
+        assert_eq!(appids(42).len(), 3);
+    }
+}
@@ -0,0 +1,257 @@
+use std::ffi::OsString;
+
+use clap::{ArgMatches, Command};
+use futures::future::BoxFuture;
+
+use super::{
+    alias_router::AliasCommand,
+    arg_matcher::{self, get_matches, SharedArgs},
+    config_router::{ConfigCommand, InitCommand},
+    friends_router::FriendsCommand,
+    games_router::GamesCommand,
+    group_router::GroupCommand,
+    identity_router::IdentityCommand,
+    logger::FilteringLogger,
+    output::CommandOutput,
+    router::{
+        Error, FriendsWhoOwnGameCommand, GetAvailableEndpointsCommand, GetGameInfoCommand,
+        GetPlayerSummaryCommand, GetUserFriendsListCommand, ResolveCommand,
+    },
+    wishlist_router::WishlistCommand,
+};
+use crate::util::storage;
+
+/// A top level command, e.g., games. It's run whenever its definition's name or one of its aliases
+/// is the subcommand
+pub trait SteamCommand: Send + Sync {
+    fn definition(&self, args: &SharedArgs) -> Command;
+
+    /// Whether the command reads or writes files on this machine, like the config or saved groups.
+    /// These commands don't make sense everywhere, e.g., in a chat bot that's shared with others
+    fn uses_local_files(&self) -> bool {
+        false
+    }
+
+    /// Whether running the command with these arguments touches files on this machine. Commands
+    /// that only do so for some arguments, e.g., friends cliques --save, say so here
+    fn uses_local_files_with(&self, _arguments: &ArgMatches) -> bool {
+        self.uses_local_files()
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>>;
+}
+
+struct Entry {
+    command: Box<dyn SteamCommand>,
+    definition: Command,
+    disabled: bool,
+}
+
+/// The commands a frontend offers. Disabled commands are hidden from the help, and running them is
+/// an argument error
+pub struct Registry {
+    entries: Vec<Entry>,
+    local_files: bool,
+}
+
+impl Registry {
+    /// Every command, in the order they're listed in the help. Without local files, the commands
+    /// that use them are disabled, e.g., for a chat bot that's shared with others
+    pub fn with_local_files(local_files: bool) -> Registry {
+        Registry::new(
+            vec![
+                Box::new(GamesCommand),
+                Box::new(WishlistCommand),
+                Box::new(FriendsCommand),
+                Box::new(ConfigCommand),
+                Box::new(InitCommand),
+                Box::new(GroupCommand),
+                Box::new(IdentityCommand),
+                Box::new(AliasCommand),
+                Box::new(GetAvailableEndpointsCommand),
+                Box::new(GetUserFriendsListCommand),
+                Box::new(ResolveCommand),
+                Box::new(GetPlayerSummaryCommand),
+                Box::new(FriendsWhoOwnGameCommand),
+                Box::new(GetGameInfoCommand),
+            ],
+            local_files,
+        )
+    }
+
+    pub fn new(commands: Vec<Box<dyn SteamCommand>>, local_files: bool) -> Registry {
+        let args = SharedArgs::default();
+        Registry {
+            entries: commands
+                .into_iter()
+                .map(|command| Entry {
+                    definition: command.definition(&args),
+                    disabled: !local_files && command.uses_local_files(),
+                    command,
+                })
+                .collect(),
+            local_files,
+        }
+    }
+
+    /// Whether commands may read or write files on this machine, e.g., to switch --profile
+    pub fn allows_local_files(&self) -> bool {
+        self.local_files
+    }
+
+    pub fn get_matches(
+        &self,
+        args: impl IntoIterator<Item = impl Into<OsString> + Clone>,
+    ) -> Result<ArgMatches, arg_matcher::Error> {
+        get_matches(
+            args,
+            self.entries
+                .iter()
+                .map(|entry| entry.definition.clone().hide(entry.disabled)),
+        )
+    }
+
+    pub async fn run<'a>(
+        &'a self,
+        matches: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> Result<CommandOutput, Error> {
+        let (name, arguments) = matches
+            .subcommand()
+            .ok_or(Error::Argument("a command is required".to_string()))?;
+        let entry = self
+            .entries
+            .iter()
+            .find(|entry| entry.definition.get_name() == name)
+            .ok_or(Error::Argument(format!("{name} isn't a command")))?;
+        if entry.disabled {
+            return Err(Error::Argument(format!("{name} isn't available here")));
+        }
+        if !self.local_files && entry.command.uses_local_files_with(arguments) {
+            return Err(Error::Argument(format!(
+                "{name} can't read or write local files here"
+            )));
+        }
+        // caches like the app index are written by commands that don't otherwise use local files
+        storage::with_writes_allowed(
+            self.local_files,
+            entry.command.run(arguments, user_steam_id, logger),
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use clap::{ArgMatches, Command};
+    use futures::{executor::block_on, future::BoxFuture};
+
+    use super::{Registry, SteamCommand};
+    use crate::{
+        steam::{
+            arg_matcher::SharedArgs,
+            logger::{FilteringLogger, Logger},
+            output::CommandOutput,
+            router::{run_command, Error},
+        },
+        util::storage,
+    };
+
+    struct NoLogger;
+
+    impl Logger for NoLogger {
+        fn stdout(&self, _str: String) {}
+        fn stderr(&self, _str: String) {}
+    }
+
+    /// Saves a file without saying that it uses local files, the way the caches do
+    struct CachingCommand;
+
+    impl SteamCommand for CachingCommand {
+        fn definition(&self, _args: &SharedArgs) -> Command {
+            Command::new("cache")
+        }
+
+        fn run<'a>(
+            &'a self,
+            _arguments: &'a ArgMatches,
+            _user_steam_id: Option<u64>,
+            _logger: &'a FilteringLogger<'a>,
+        ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+            Box::pin(async {
+                Ok(CommandOutput::Message(
+                    match storage::save_json("registry-test.json", &0) {
+                        Ok(()) => "saved".to_string(),
+                        Err(err) => err.to_string(),
+                    },
+                ))
+            })
+        }
+    }
+
+    #[test]
+    fn disabled_commands_are_rejected() {
+        let registry = Registry::with_local_files(false);
+        let matches = registry
+            .get_matches(["steam-cli", "alias", "list"])
+            .unwrap();
+        let logger = FilteringLogger {
+            logger: &NoLogger,
+            verbose: false,
+        };
+        match block_on(registry.run(&matches, None, &logger)) {
+            Err(Error::Argument(message)) => assert_eq!(message, "alias isn't available here"),
+            _ => panic!("alias should be disabled"),
+        }
+    }
+
+    #[test]
+    fn saving_cliques_is_rejected_without_local_files() {
+        let registry = Registry::with_local_files(false);
+        let matches = registry
+            .get_matches(["steam-cli", "friends", "cliques", "--save"])
+            .unwrap();
+        let logger = FilteringLogger {
+            logger: &NoLogger,
+            verbose: false,
+        };
+        match block_on(registry.run(&matches, None, &logger)) {
+            Err(Error::Argument(message)) => {
+                assert_eq!(message, "friends can't read or write local files here")
+            }
+            _ => panic!("saving cliques should be rejected"),
+        }
+    }
+
+    #[test]
+    fn caches_are_not_written_without_local_files() {
+        let registry = Registry::new(vec![Box::new(CachingCommand)], false);
+        let matches = registry.get_matches(["steam-cli", "cache"]).unwrap();
+        let logger = FilteringLogger {
+            logger: &NoLogger,
+            verbose: false,
+        };
+        match block_on(registry.run(&matches, None, &logger)) {
+            Ok(CommandOutput::Message(message)) => {
+                assert_eq!(message, "local files can't be written here")
+            }
+            _ => panic!("the cache should not be written"),
+        }
+    }
+
+    #[test]
+    fn profiles_cant_be_switched_without_local_files() {
+        let registry = Registry::with_local_files(false);
+        let args = ["steam-cli", "--profile", "alt", "get-game-info", "10"].map(str::to_string);
+        match block_on(run_command(&registry, args, None, &NoLogger)) {
+            Err(Error::Argument(message)) => assert_eq!(message, "--profile isn't available here"),
+            _ => panic!("--profile should be rejected"),
+        }
+    }
+}
@@ -1,25 +1,37 @@
 // TODO: the arg_matcher, router and games_router files should all be moved into their own
 // submodule
-use std::{fmt::Display, num::ParseIntError};
+use std::{collections::HashSet, fmt::Display, num::ParseIntError};
 
-use clap::ArgMatches;
+use clap::{parser::ValueSource, ArgMatches, Command};
+use futures::future::BoxFuture;
+use serde::Serialize;
 
 use super::{
-    arg_matcher::{self, get_matches},
-    client::{self, GetUserDetailsRequest, GetUserSummariesRequest},
-    games_router::run_games_command,
+    aliases, app_index,
+    arg_matcher::{self, SharedArgs},
+    client::{self, GetUserDetailsRequest, GetUserSummariesRequest, UserSummary},
+    config::{self, settings},
+    games_router, groups, identities,
     logger::{FilteringLogger, Logger},
+    name_resolver::{self, NameResolver},
+    output::{CommandOutput, OutputFormat},
+    registry::{Registry, SteamCommand},
     service,
 };
 
-const FUZZY_THRESHOLD: u32 = 50;
-
+/// Run the command and write out its result as rendered by the frontend
 pub async fn route_arguments(
+    registry: &Registry,
     args: impl IntoIterator<Item = String>,
     user_id: Option<u64>,
     logger: &dyn Logger,
+    render: impl Fn(CommandOutput, OutputFormat) -> Result<String, serde_json::Error>,
 ) -> Result<(), Error> {
-    match run_command(args, user_id, logger).await {
+    let rendered = match run_command(registry, args, user_id, logger).await {
+        Ok((output, format)) => render(output, format).map_err(Error::from),
+        Err(err) => Err(err),
+    };
+    match rendered {
         Ok(str) => logger.stdout(str),
         Err(err) => {
             logger.stderr(err.to_string());
@@ -30,88 +42,217 @@ pub async fn route_arguments(
 }
 
 pub async fn run_command(
+    registry: &Registry,
     args: impl IntoIterator<Item = String>,
     user_id: Option<u64>,
     logger: &dyn Logger,
-) -> Result<String, Error> {
-    let matches = get_matches(args)?;
+) -> Result<(CommandOutput, OutputFormat), Error> {
+    let matches = registry.get_matches(args)?;
     let verbose = matches.get_flag("verbose");
+    let format = matches
+        .get_one::<OutputFormat>("output")
+        .copied()
+        .unwrap_or(OutputFormat::Text);
+    // a profile holds its own user_steam_id, so switching profiles switches who "you" are. The
+    // settings are shared by the whole process, so frontends that serve many users can't switch
+    let user_id = match matches.get_one::<String>("profile") {
+        None => user_id,
+        Some(_) if !registry.allows_local_files() => {
+            return Err(Error::Argument(
+                "--profile isn't available here".to_string(),
+            ))
+        }
+        Some(profile) => {
+            config::use_profile(profile)?;
+            settings().values.user_steam_id
+        }
+    };
+
+    Ok((
+        registry
+            .run(&matches, user_id, &FilteringLogger { logger, verbose })
+            .await?,
+        format,
+    ))
+}
+
+pub struct GetAvailableEndpointsCommand;
 
-    run_subcommand(matches, user_id, &FilteringLogger { logger, verbose }).await
+impl SteamCommand for GetAvailableEndpointsCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::get_available_endpoints_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        _arguments: &'a ArgMatches,
+        _user_steam_id: Option<u64>,
+        _logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(async move {
+            let available_endpoints = client::get_available_endpoints().await?;
+            Ok(CommandOutput::Endpoints(available_endpoints))
+        })
+    }
 }
 
-async fn run_subcommand<'a>(
-    matches: ArgMatches,
+pub struct GetUserFriendsListCommand;
+
+impl SteamCommand for GetUserFriendsListCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::get_user_friends_list_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(get_user_friends_list(arguments, user_steam_id, logger))
+    }
+}
+
+/// The summaries of the friends of the steam id, or of the user with --self
+pub async fn get_user_friends_list<'a>(
+    arguments: &ArgMatches,
     user_steam_id: Option<u64>,
     logger: &'a FilteringLogger<'a>,
-) -> Result<String, Error> {
-    match matches.subcommand() {
-        Some(("games", arguments)) => run_games_command(arguments, user_steam_id, logger).await,
-        Some(("get-available-endpoints", _)) => {
-            let available_endpoints = client::get_available_endpoints().await?;
-            let pretty_string = serde_json::to_string_pretty(&available_endpoints)?;
-            Ok(pretty_string)
-        }
-        Some(("get-user-friends-list", arguments)) => {
-            let id = if arguments.get_flag("self") {
-                user_steam_id.ok_or(Error::Argument(
-                    "user_steam_id is required in order to resolve user_steam_ids by persona name"
-                        .to_string(),
-                ))?
-            } else {
-                arguments
-                    .get_one::<u64>("steamid")
-                    .ok_or(Error::Argument("1 arg required".to_string()))?
-                    .to_owned()
-            };
-            let friends =
-                client::get_user_friends_list(GetUserDetailsRequest { id }, logger).await?;
-
-            let summaries = client::get_user_summaries(
-                GetUserSummariesRequest {
-                    ids: friends
-                        .iter()
-                        .map(|friend| friend.steamid.parse::<u64>())
-                        .collect::<Result<Vec<u64>, ParseIntError>>()?,
-                },
-                logger,
-            )
-            .await?;
-            Ok(format!(
-                "friend summaries: {}",
-                serde_json::to_string_pretty(&summaries)?,
-            ))
-        }
-        Some(("get-player-summary", arguments)) => {
+) -> Result<CommandOutput, Error> {
+    let id = if arguments.get_flag("self") {
+        user_steam_id.ok_or(Error::Argument(
+            "user_steam_id is required in order to resolve user_steam_ids by persona name"
+                .to_string(),
+        ))?
+    } else {
+        arguments
+            .get_one::<u64>("steamid")
+            .ok_or(Error::Argument("1 arg required".to_string()))?
+            .to_owned()
+    };
+    let friends = client::get_user_friends_list(GetUserDetailsRequest { id }, logger).await?;
+
+    let summaries = client::get_user_summaries(
+        GetUserSummariesRequest {
+            ids: friends
+                .iter()
+                .map(|friend| friend.steamid.parse::<u64>())
+                .collect::<Result<Vec<u64>, ParseIntError>>()?,
+        },
+        logger,
+    )
+    .await?;
+    Ok(CommandOutput::Users(summaries))
+}
+
+pub struct ResolveCommand;
+
+impl SteamCommand for ResolveCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::resolve_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(async move {
+            let user_steam_id = user_steam_id.ok_or(Error::Argument(
+                "user_steam_id must be set to run this command".to_string(),
+            ))?;
+            let aliases = aliases::load_aliases()?;
+            let summaries = service::get_resolvable_users(user_steam_id, logger).await?;
+            let names = arguments
+                .get_many::<String>("names")
+                .into_iter()
+                .flatten()
+                .map(|name| (name.clone(), aliases.lookup(name, &summaries)))
+                .collect();
+            Ok(CommandOutput::Resolutions {
+                names,
+                summaries,
+                threshold: get_match_threshold(arguments),
+            })
+        })
+    }
+}
+
+pub struct GetPlayerSummaryCommand;
+
+impl SteamCommand for GetPlayerSummaryCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::get_player_summary_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(async move {
             let steamids = get_steam_ids(arguments, user_steam_id, "steam_ids", logger).await?;
             let friends_list =
                 client::get_user_summaries(GetUserSummariesRequest { ids: steamids }, logger)
                     .await?;
-            Ok(serde_json::to_string_pretty(&friends_list)?)
-        }
-        Some(("friends-who-own-game", arguments)) => {
-            let gameid = get_gameid(arguments)?;
+            Ok(CommandOutput::Users(friends_list))
+        })
+    }
+}
+
+pub struct FriendsWhoOwnGameCommand;
+
+impl SteamCommand for FriendsWhoOwnGameCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::friends_who_own_game_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(async move {
+            let gameid = &get_gameid(arguments, logger).await?;
 
             let user_steam_id = user_steam_id.ok_or(Error::Argument(
                 "user_steam_id must be set to run this command".to_string(),
             ))?;
 
-            let friends_list =
-                service::find_friends_who_own_game(gameid, user_steam_id, logger).await?;
+            let friends_list = service::find_friends_who_own_game(
+                gameid,
+                user_steam_id,
+                get_include_family(arguments),
+                logger,
+            )
+            .await?;
 
-            Ok(format!(
-                "{}\nTotal: {}",
-                serde_json::to_string_pretty(&friends_list)?,
-                friends_list.len()
-            ))
-        }
-        Some(("get-game-info", arguments)) => {
-            let gameid = get_gameid(arguments)?;
+            Ok(CommandOutput::FriendsWithGame(friends_list))
+        })
+    }
+}
+
+pub struct GetGameInfoCommand;
+
+impl SteamCommand for GetGameInfoCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::get_game_info_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        _user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(async move {
+            let gameid = &get_gameid(arguments, logger).await?;
             let game_info = client::get_game_info(gameid, logger).await?;
-            Ok(format!("{:?}", game_info))
-        }
-        None => Err(Error::Argument("should be unreachable".to_string())),
-        _ => unreachable!(),
+            Ok(CommandOutput::GameInfo(game_info))
+        })
     }
 }
 
@@ -131,7 +272,66 @@ impl From<client::Error> for Error {
 
 impl From<service::Error> for Error {
     fn from(value: service::Error) -> Self {
-        Error::Execution(value.to_string())
+        match value {
+            // names that don't resolve are mistakes in the arguments, not failures to run
+            service::Error::User(_) => Error::Argument(value.to_string()),
+            _ => Error::Execution(value.to_string()),
+        }
+    }
+}
+
+impl From<app_index::Error> for Error {
+    fn from(value: app_index::Error) -> Self {
+        match value {
+            app_index::Error::NotFound(_) | app_index::Error::Ambiguous(_, _) => {
+                Error::Argument(value.to_string())
+            }
+            _ => Error::Execution(value.to_string()),
+        }
+    }
+}
+
+impl From<groups::Error> for Error {
+    fn from(value: groups::Error) -> Self {
+        match value {
+            groups::Error::NotFound(_)
+            | groups::Error::AlreadyExists(_)
+            | groups::Error::Cycle(_) => Error::Argument(value.to_string()),
+            _ => Error::Execution(value.to_string()),
+        }
+    }
+}
+
+impl From<aliases::Error> for Error {
+    fn from(value: aliases::Error) -> Self {
+        match value {
+            aliases::Error::NotFound(_) | aliases::Error::Invalid(_) => {
+                Error::Argument(value.to_string())
+            }
+            aliases::Error::Storage(_) => Error::Execution(value.to_string()),
+        }
+    }
+}
+
+impl From<config::Error> for Error {
+    fn from(value: config::Error) -> Self {
+        match value {
+            config::Error::UnknownProfile(_)
+            | config::Error::UnknownKey(_)
+            | config::Error::InvalidValue(_, _) => Error::Argument(value.to_string()),
+            _ => Error::Execution(value.to_string()),
+        }
+    }
+}
+
+impl From<identities::Error> for Error {
+    fn from(value: identities::Error) -> Self {
+        match value {
+            identities::Error::NotFound(_) | identities::Error::AlreadyLinked(_, _) => {
+                Error::Argument(value.to_string())
+            }
+            identities::Error::Storage(_) => Error::Execution(value.to_string()),
+        }
     }
 }
 
@@ -164,6 +364,121 @@ impl From<ParseIntError> for Error {
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct Resolution<'a> {
+    name: &'a str,
+    steamid: Option<String>,
+    personaname: Option<String>,
+    /// how the name was matched, or why it couldn't be
+    matched: String,
+}
+
+fn compute_resolution<'a>(name: &'a str, resolver: &NameResolver) -> Resolution<'a> {
+    match resolver.resolve(name) {
+        Ok(summary) => Resolution {
+            name,
+            steamid: Some(summary.steamid.clone()),
+            personaname: Some(summary.personaname.clone()),
+            matched: match resolver.candidates(name).first() {
+                Some(best) if best.summary.steamid == summary.steamid => {
+                    format!("{} with a score of {}", best.matched, best.score)
+                }
+                _ => "exact persona name".to_string(),
+            },
+        },
+        Err(err) => Resolution {
+            name,
+            steamid: None,
+            personaname: None,
+            matched: err.to_string(),
+        },
+    }
+}
+
+/// How each name resolves, given the friend it's an alias of if it's an alias
+pub fn compute_resolution_records<'a>(
+    names: &'a [(String, Option<u64>)],
+    summaries: &[UserSummary],
+    threshold: u32,
+) -> Vec<Resolution<'a>> {
+    let resolver = NameResolver::new(summaries, threshold);
+    names
+        .iter()
+        .map(|(name, aliased)| match aliased {
+            Some(steam_id) => Resolution {
+                name,
+                steamid: Some(steam_id.to_string()),
+                personaname: Some(games_router::persona_name(summaries, *steam_id)),
+                matched: "alias".to_string(),
+            },
+            None => compute_resolution(name, &resolver),
+        })
+        .collect()
+}
+
+pub fn compute_resolutions_string(
+    names: &[(String, Option<u64>)],
+    summaries: &[UserSummary],
+    threshold: u32,
+) -> String {
+    let resolver = NameResolver::new(summaries, threshold);
+    names
+        .iter()
+        .map(|(name, aliased)| match aliased {
+            Some(steam_id) => format!(
+                "{name} -> {} ({steam_id}), matched an alias",
+                games_router::persona_name(summaries, *steam_id)
+            ),
+            None => compute_resolution_string(name, &resolver),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn compute_resolution_string(name: &str, resolver: &NameResolver) -> String {
+    let candidates = resolver.candidates(name);
+    let others = |skip: usize| {
+        candidates
+            .iter()
+            .skip(skip)
+            .map(|candidate| format!("\n\t{candidate}"))
+            .collect::<String>()
+    };
+    match resolver.resolve(name) {
+        Ok(summary) => format!(
+            "{name} -> {} ({}){}",
+            summary.personaname,
+            summary.steamid,
+            match candidates.first() {
+                Some(best) if best.summary.steamid == summary.steamid => format!(
+                    ", matched {} with a score of {}{}",
+                    best.matched,
+                    best.score,
+                    if candidates.len() > 1 {
+                        format!("\n\tother candidates:{}", others(1))
+                    } else {
+                        String::new()
+                    }
+                ),
+                _ => ", exact persona name match".to_string(),
+            }
+        ),
+        Err(name_resolver::Error::Ambiguous(_, _)) => {
+            format!("{name} is ambiguous between:{}", others(0))
+        }
+        Err(err) => err.to_string(),
+    }
+}
+
+/// The error for a subcommand that a command has no case for. clap only lets through the
+/// subcommands that are defined, so this means a definition and its router disagree
+pub fn unknown_subcommand(arguments: &ArgMatches) -> Error {
+    match arguments.subcommand_name() {
+        Some(name) => Error::Argument(format!("{name} isn't supported here")),
+        None => Error::Argument("a subcommand is required".to_string()),
+    }
+}
+
 // TODO: move into router utility class
 pub async fn get_steam_ids<'a>(
     arguments: &ArgMatches,
@@ -171,38 +486,166 @@ pub async fn get_steam_ids<'a>(
     steam_ids_key: &str,
     logger: &'a FilteringLogger<'a>,
 ) -> Result<Vec<u64>, Error> {
-    let partially_ingested_steam_ids = arguments
+    let (group_names, partially_ingested_steam_ids): (Vec<&str>, Vec<&str>) = arguments
         .get_many::<String>(steam_ids_key)
         .into_iter()
-        .flatten();
-    let steam_ids = if arguments.get_flag("use-ids") {
+        .flatten()
+        .map(String::as_str)
+        .partition(|id| groups::group_name(id).is_some());
+    let mut group_steam_ids = vec![];
+    if !group_names.is_empty() {
+        let groups = groups::load_groups()?;
+        for name in group_names.into_iter().filter_map(groups::group_name) {
+            group_steam_ids.extend(groups.expand(name)?);
+        }
+    }
+    let steam_ids = resolve_players(
+        arguments,
+        user_steam_id,
+        partially_ingested_steam_ids,
+        logger,
+    )
+    .await?;
+    let mut seen = HashSet::new();
+    Ok(group_steam_ids
+        .into_iter()
+        .chain(steam_ids)
+        .filter(|id| seen.insert(*id))
+        .collect())
+}
+
+/// Same as get_steam_ids, but for arguments that refer to a single player. Groups are only
+/// accepted if they have exactly one member
+pub async fn get_steam_id<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    steam_id_key: &str,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Option<u64>, Error> {
+    match get_steam_ids(arguments, user_steam_id, steam_id_key, logger)
+        .await?
+        .as_slice()
+    {
+        [] => Ok(None),
+        [steam_id] => Ok(Some(*steam_id)),
+        steam_ids => Err(Error::Argument(format!(
+            "{steam_id_key} must be a single player, but it's {} players",
+            steam_ids.len()
+        ))),
+    }
+}
+
+/// Turn steam ids, aliases and names into steam ids, in the same order. Groups aren't expanded, see
+/// get_steam_ids for that
+pub async fn resolve_players<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    partially_ingested_steam_ids: Vec<&str>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<u64>, Error> {
+    let partially_ingested_steam_ids = partially_ingested_steam_ids.into_iter();
+    Ok(if partially_ingested_steam_ids.len() == 0 {
+        vec![]
+    } else if arguments.get_flag("use-ids") {
         partially_ingested_steam_ids
             .map(|id| id.parse::<u64>())
             .collect::<Result<Vec<_>, ParseIntError>>()?
     } else {
-        let user_steam_id = user_steam_id.ok_or(Error::Argument(
-            "user_steam_id is required in order to resolve user_steam_ids by persona name"
-                .to_string(),
-        ))?;
-        let steam_id_strings = partially_ingested_steam_ids.map(|s| s.trim());
-        if arguments.get_flag("strict") {
-            service::resolve_usernames_strictly(steam_id_strings, user_steam_id, logger).await?
+        // aliases and identities are checked first, since they keep working when persona names
+        // change. An identity stands for its main account, whose library includes the others
+        let aliases = aliases::load_aliases()?;
+        let identities = identities::load_identities()?;
+        let mut aliased = partially_ingested_steam_ids
+            .map(|name| {
+                let name = name.trim();
+                let steam_id = aliases.lookup_alias(name).or_else(|| {
+                    identities
+                        .accounts(name)
+                        .and_then(|accounts| accounts.first().copied())
+                });
+                (name, steam_id)
+            })
+            .collect::<Vec<_>>();
+        let mut resolved = if aliased.iter().all(|(_, steam_id)| steam_id.is_some()) {
+            vec![]
         } else {
-            service::resolve_usernames_fuzzily(
-                steam_id_strings,
-                user_steam_id,
-                FUZZY_THRESHOLD,
-                logger,
-            )
-            .await?
+            let user_steam_id = user_steam_id.ok_or(Error::Argument(
+                "user_steam_id is required in order to resolve user_steam_ids by persona name"
+                    .to_string(),
+            ))?;
+            let summaries = service::get_resolvable_users(user_steam_id, logger).await?;
+            // past persona names are only checked once it's known nobody goes by the name now
+            for (name, steam_id) in aliased.iter_mut().filter(|(_, id)| id.is_none()) {
+                *steam_id = aliases.lookup(name, &summaries);
+            }
+            let unaliased = aliased
+                .iter()
+                .filter(|(_, steam_id)| steam_id.is_none())
+                .map(|(name, _)| *name);
+            if arguments.get_flag("strict") {
+                service::resolve_usernames_strictly(unaliased, &summaries)?
+            } else {
+                service::resolve_usernames_fuzzily(
+                    unaliased,
+                    &summaries,
+                    get_match_threshold(arguments),
+                    logger,
+                )?
+            }
         }
-    };
-    Ok(steam_ids)
+        .into_iter();
+        aliased
+            .into_iter()
+            .filter_map(|(_, steam_id)| steam_id.or_else(|| resolved.next()))
+            .collect()
+    })
 }
 
-fn get_gameid(arguments: &ArgMatches) -> Result<&u64, Error> {
-    let gameid = arguments
-        .get_one::<u64>("gameid")
-        .ok_or(Error::Argument("gameid must be a valid u64".to_string()))?;
-    Ok(gameid)
+fn get_match_threshold(arguments: &ArgMatches) -> u32 {
+    arguments
+        .get_one::<u32>("match-threshold")
+        .copied()
+        .or(settings().values.match_threshold)
+        .unwrap_or(name_resolver::DEFAULT_THRESHOLD)
+}
+
+/// Whether --include-family was passed or the config turns it on for every command
+pub fn get_include_family(arguments: &ArgMatches) -> bool {
+    arguments.get_flag("include-family") || settings().values.include_family.unwrap_or(false)
+}
+
+/// The --country argument, or the config's country if it wasn't given
+pub fn get_country(arguments: &ArgMatches) -> Result<String, Error> {
+    let country = arguments
+        .get_one::<String>("country")
+        .ok_or(Error::Argument("country is required".to_string()))?;
+    Ok(
+        match (
+            arguments.value_source("country"),
+            &settings().values.country,
+        ) {
+            (Some(ValueSource::DefaultValue), Some(configured)) => configured.clone(),
+            _ => country.clone(),
+        },
+    )
+}
+
+/// Get the app id of the game argument. If it isn't a number, then it's treated as a name and
+/// looked up in the app index
+async fn get_gameid<'a>(
+    arguments: &ArgMatches,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<u64, Error> {
+    let game = arguments
+        .get_many::<String>("gameid")
+        .ok_or(Error::Argument("gameid is required".to_string()))?
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if let Ok(gameid) = game.trim().parse::<u64>() {
+        return Ok(gameid);
+    }
+    let game = app_index::load_app_index(logger).await?.resolve(&game)?;
+    logger.trace(format!("resolved game to {game}"));
+    Ok(game.appid)
 }
@@ -1,161 +1,757 @@
 use super::{
-    client::{self, GetUserSummariesRequest, UserSummary},
+    aliases::remember_persona_names,
+    client::{self, GameData, GetUserSummariesRequest, PriceOverview, UserSummary, WishlistItem},
+    friend_graph::FriendGraph,
+    identities::{load_identities_for_libraries, merge_accounts, Identities},
     logger::FilteringLogger,
+    name_resolver::NameResolver,
 };
-use futures::{future::join_all, join};
-use std::{collections::HashSet, fmt::Display, num::ParseIntError};
+use futures::{future::join_all, join, stream, StreamExt};
+use serde::Serialize;
+use std::{
+    collections::{btree_map::Entry, HashMap, HashSet},
+    fmt::Display,
+    num::ParseIntError,
+};
+
+use super::models::{Game, OwnedGame};
+
+pub const DEFAULT_COUNTRY_CODE: &str = "us";
+
+/// How many apps are looked up in appdetails at once
+pub const APPDETAILS_CONCURRENCY: usize = 4;
+
+/// How many paid apps get_game_metadata looks up in appdetails to find the currency of the prices
+/// before giving up
+const CURRENCY_LOOKUP_ATTEMPTS: usize = 3;
+
+pub fn find_games_in_common_from_libraries(libraries: &[(u64, Vec<OwnedGame>)]) -> HashSet<Game> {
+    let mut games_set = HashSet::<Game>::new();
 
-use super::models::Game;
+    let mut first = true;
+    for (_, games) in libraries {
+        let games = games.iter().map(|owned_game| owned_game.game.clone());
+        if first {
+            games_set.extend(games);
+            first = false;
+        } else {
+            let curr_games: HashSet<Game> = HashSet::from_iter(games);
+            games_set.retain(|game| curr_games.contains(game));
+        }
+    }
+    games_set
+}
 
-pub async fn find_games_in_common<'a>(
+/// Fetch the owned games of every provided steam id concurrently. The libraries are returned in
+/// the same order as the steam ids were provided
+pub async fn get_owned_games_by_player<'a>(
     steam_ids: impl IntoIterator<Item = u64>,
     logger: &'a FilteringLogger<'a>,
-) -> Result<HashSet<Game>, Error> {
-    let mut games_set = HashSet::<Game>::new();
+) -> Result<Vec<(u64, Vec<OwnedGame>)>, Error> {
+    get_libraries_by_player(steam_ids, false, logger).await
+}
 
+/// Same as get_owned_games_by_player, but if include_family is set, then the games each player
+/// can borrow through their Steam Family are included and marked as borrowed
+pub async fn get_libraries_by_player<'a>(
+    steam_ids: impl IntoIterator<Item = u64>,
+    include_family: bool,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<(u64, Vec<OwnedGame>)>, Error> {
+    let steam_ids = steam_ids.into_iter().collect::<Vec<_>>();
+    let identities = load_identities_for_libraries(logger);
     let query_results = join_all(
         steam_ids
-            .into_iter()
-            .map(|id| client::get_owned_games(client::GetUserDetailsRequest { id }, logger)),
+            .iter()
+            .map(|&id| get_linked_library(id, include_family, &identities, logger)),
     )
     .await;
 
-    let mut first = true;
-    for result in query_results {
-        let games = result?;
-        if first {
-            for game in games {
-                games_set.insert(game);
+    let libraries = steam_ids
+        .into_iter()
+        .zip(query_results)
+        .map(|(id, result)| Ok((id, result?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    warn_about_unreadable_libraries(&libraries, logger).await;
+    Ok(libraries)
+}
+
+/// GetOwnedGames doesn't fail for players whose game details are private, it just doesn't return
+/// any games. So a library without any games of its own is almost always a private one
+pub fn find_unreadable_libraries(libraries: &[(u64, Vec<OwnedGame>)]) -> Vec<u64> {
+    libraries
+        .iter()
+        .filter(|(_, games)| games.iter().all(|owned_game| owned_game.borrowed))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+/// Private libraries quietly make group results empty, so name the players they belong to
+async fn warn_about_unreadable_libraries<'a>(
+    libraries: &[(u64, Vec<OwnedGame>)],
+    logger: &'a FilteringLogger<'a>,
+) {
+    let unreadable = find_unreadable_libraries(libraries);
+    if unreadable.is_empty() {
+        return;
+    }
+    let summaries = client::get_user_summaries(
+        GetUserSummariesRequest {
+            ids: unreadable.clone(),
+        },
+        logger,
+    )
+    .await
+    .unwrap_or_default();
+    let names = unreadable
+        .iter()
+        .map(|id| {
+            summaries
+                .iter()
+                .find(|summary| summary.steamid == id.to_string())
+                .map_or(id.to_string(), |summary| summary.personaname.clone())
+        })
+        .collect::<Vec<_>>();
+    logger.error(format!(
+        "warning: could not read the games of {}. Their game details are probably private, see friends privacy",
+        names.join(", ")
+    ));
+}
+
+/// Get the games the player owns, including the games owned on any accounts linked to theirs
+pub async fn get_library<'a>(
+    steam_id: u64,
+    include_family: bool,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<OwnedGame>, Error> {
+    let identities = load_identities_for_libraries(logger);
+    get_linked_library(steam_id, include_family, &identities, logger).await
+}
+
+/// Same as get_library, but with the identities already loaded, so that fetching many libraries
+/// reads them once
+async fn get_linked_library<'a>(
+    steam_id: u64,
+    include_family: bool,
+    identities: &Identities,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<OwnedGame>, Error> {
+    let linked_accounts = identities.linked_accounts(steam_id);
+    if linked_accounts.is_empty() {
+        return get_account_library(steam_id, include_family, logger).await;
+    }
+    logger.trace(format!(
+        "merging the libraries of {steam_id} and its linked accounts {linked_accounts:?}"
+    ));
+    let accounts = std::iter::once(steam_id)
+        .chain(linked_accounts)
+        .collect::<Vec<_>>();
+    let libraries = join_all(
+        accounts
+            .iter()
+            .map(|&account| get_account_library(account, include_family, logger)),
+    )
+    .await;
+    Ok(merge_accounts(
+        accounts
+            .into_iter()
+            .zip(libraries)
+            .map(|(account, library)| Ok((account, library?)))
+            .collect::<Result<Vec<_>, Error>>()?,
+    ))
+}
+
+/// Get the games owned by a single steam account, ignoring any linked accounts
+async fn get_account_library<'a>(
+    steam_id: u64,
+    include_family: bool,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<OwnedGame>, Error> {
+    let mut games =
+        client::get_owned_games(client::GetUserDetailsRequest { id: steam_id }, logger).await?;
+    if include_family {
+        let owned_appids = games
+            .iter()
+            .map(|owned_game| owned_game.game.appid)
+            .collect::<HashSet<_>>();
+        let borrowed_games = get_family_shared_games(steam_id, logger)
+            .await
+            .into_iter()
+            .filter(|game| !owned_appids.contains(&game.appid))
+            .map(|game| OwnedGame {
+                game,
+                playtime_forever: 0,
+                playtime_2weeks: 0,
+                rtime_last_played: 0,
+                borrowed: true,
+                account: None,
+            })
+            .collect::<Vec<_>>();
+        games.extend(borrowed_games);
+    }
+    Ok(games)
+}
+
+/// Get the games the user can borrow through their Steam Family. Most users won't be in the same
+/// family as the caller, so failures are logged and treated as having nothing to borrow
+pub async fn get_family_shared_games<'a>(
+    steam_id: u64,
+    logger: &'a FilteringLogger<'a>,
+) -> Vec<Game> {
+    let shared_apps = async {
+        let request = || client::GetUserDetailsRequest { id: steam_id };
+        match client::get_family_group_for_user(request(), logger).await? {
+            None => Ok(vec![]),
+            Some(family_groupid) => {
+                client::get_shared_library_apps(family_groupid, request(), logger).await
+            }
+        }
+    };
+    match shared_apps.await {
+        Ok(shared_apps) => shared_apps
+            .into_iter()
+            .map(|app| Game {
+                name: app.name,
+                appid: app.appid,
+            })
+            .collect(),
+        Err(err) => {
+            logger.trace(format!(
+                "could not get family shared games for {steam_id}: {err}"
+            ));
+            vec![]
+        }
+    }
+}
+
+/// Find, for each game, the linked accounts that own it for members who don't own it themselves
+pub fn find_linked_owners(libraries: &[(u64, Vec<OwnedGame>)]) -> HashMap<Game, Vec<u64>> {
+    let mut owners = HashMap::<Game, Vec<u64>>::new();
+    for (_, games) in libraries {
+        for owned_game in games {
+            if let Some(account) = owned_game.account {
+                owners
+                    .entry(owned_game.game.clone())
+                    .or_default()
+                    .push(account);
             }
-            first = false;
-        } else {
-            let curr_games: HashSet<Game> = HashSet::from_iter(games.into_iter());
-            games_set.retain(|game| curr_games.contains(game));
         }
     }
-    Ok(games_set)
+    owners
 }
 
-pub async fn games_missing_from_group<'a>(
-    focus_steam_id: u64,
-    other_steam_ids: impl IntoIterator<Item = u64>,
+/// Find, for each game, the members that can only play it because they borrow it through their
+/// Steam Family
+pub fn find_borrowers(libraries: &[(u64, Vec<OwnedGame>)]) -> HashMap<Game, Vec<u64>> {
+    let mut borrowers = HashMap::<Game, Vec<u64>>::new();
+    for (id, games) in libraries {
+        for owned_game in games.iter().filter(|owned_game| owned_game.borrowed) {
+            borrowers
+                .entry(owned_game.game.clone())
+                .or_default()
+                .push(*id);
+        }
+    }
+    borrowers
+}
+
+pub async fn get_ownership_matrix<'a>(
+    steam_ids: impl IntoIterator<Item = u64>,
+    include_family: bool,
     logger: &'a FilteringLogger<'a>,
-) -> Result<HashSet<Game>, Error> {
-    logger.trace("finding games missing from group...".to_string());
-    let result = join!(
-        client::get_owned_games(client::GetUserDetailsRequest { id: focus_steam_id }, logger),
-        find_games_in_common(other_steam_ids, logger)
+) -> Result<OwnershipMatrix, Error> {
+    Ok(OwnershipMatrix::from_libraries(
+        get_libraries_by_player(steam_ids, include_family, logger).await?,
+    ))
+}
+
+/// Games as rows and players as columns. Each cell holds the player's copy of the game, or None if
+/// they don't own it
+#[derive(Debug)]
+pub struct OwnershipMatrix {
+    pub steam_ids: Vec<u64>,
+    pub rows: Vec<OwnershipRow>,
+}
+
+#[derive(Debug)]
+pub struct OwnershipRow {
+    pub game: Game,
+    pub cells: Vec<Option<OwnershipCell>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnershipCell {
+    /// in minutes
+    pub playtime: u64,
+    /// the linked account that owns the game, if it isn't owned on the player's own account
+    pub account: Option<u64>,
+    /// true if the game is playable through a Steam Family instead of being owned
+    pub borrowed: bool,
+}
+
+impl OwnershipMatrix {
+    /// Build a matrix over the union of all of the provided libraries, sorted by game name
+    pub fn from_libraries(libraries: Vec<(u64, Vec<OwnedGame>)>) -> Self {
+        let mut rows_by_game = HashMap::<Game, Vec<Option<OwnershipCell>>>::new();
+        for (column, (_, games)) in libraries.iter().enumerate() {
+            for owned_game in games {
+                rows_by_game
+                    .entry(owned_game.game.clone())
+                    .or_insert_with(|| vec![None; libraries.len()])[column] = Some(OwnershipCell {
+                    playtime: owned_game.playtime_forever,
+                    account: owned_game.account,
+                    borrowed: owned_game.borrowed,
+                });
+            }
+        }
+        let mut rows = rows_by_game
+            .into_iter()
+            .map(|(game, cells)| OwnershipRow { game, cells })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| a.game.name.cmp(&b.game.name));
+        OwnershipMatrix {
+            steam_ids: libraries.into_iter().map(|(id, _)| id).collect(),
+            rows,
+        }
+    }
+
+    pub fn games(&self) -> impl Iterator<Item = &Game> {
+        self.rows.iter().map(|row| &row.game)
+    }
+
+    pub fn retain_games(&mut self, games: &HashSet<Game>) {
+        self.rows.retain(|row| games.contains(&row.game));
+    }
+
+    /// The linked accounts that own any of the games, so that they can be named
+    pub fn linked_accounts(&self) -> Vec<u64> {
+        let mut accounts = self
+            .rows
+            .iter()
+            .flat_map(|row| row.cells.iter().flatten())
+            .filter_map(|cell| cell.account)
+            .collect::<Vec<_>>();
+        accounts.sort();
+        accounts.dedup();
+        accounts
+    }
+}
+
+/// Find the games that everyone in other_libraries owns that aren't in the focus_library
+pub fn games_missing_from_group(
+    focus_library: &[OwnedGame],
+    other_libraries: &[(u64, Vec<OwnedGame>)],
+) -> HashSet<Game> {
+    let mut games_in_common_minus_focus = find_games_in_common_from_libraries(other_libraries);
+
+    for owned_game in focus_library {
+        games_in_common_minus_focus.remove(&owned_game.game);
+    }
+    games_in_common_minus_focus
+}
+
+/// Find the games that everyone in the group owns, plus the Remote Play Together games that at
+/// least one member owns since those only need one person to host
+pub async fn find_games_playable_together<'a>(
+    steam_ids: impl IntoIterator<Item = u64>,
+    include_family: bool,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<PlayableTogether, Error> {
+    let libraries = get_libraries_by_player(steam_ids, include_family, logger).await?;
+    find_games_playable_together_in_libraries(&libraries, logger).await
+}
+
+/// Same as find_games_playable_together, but using libraries that have already been fetched
+pub async fn find_games_playable_together_in_libraries<'a>(
+    libraries: &[(u64, Vec<OwnedGame>)],
+    logger: &'a FilteringLogger<'a>,
+) -> Result<PlayableTogether, Error> {
+    let in_common = find_games_in_common_from_libraries(libraries);
+    let mut hosts_by_game = find_possible_hosts(libraries, &in_common);
+    let remote_play_games = filter_games(
+        hosts_by_game.keys().cloned(),
+        HashSet::from([client::PlayStyle::RemotePlayTogether as u8]),
+        logger,
+    )
+    .await?;
+
+    Ok(PlayableTogether {
+        in_common,
+        remote_play: remote_play_games
+            .into_iter()
+            .map(|game| {
+                let hosts = hosts_by_game.remove(&game).unwrap_or_default();
+                (game, hosts)
+            })
+            .collect(),
+    })
+}
+
+/// Find, for each game that not everyone owns, the members who own it and so could host it
+pub fn find_possible_hosts(
+    libraries: &[(u64, Vec<OwnedGame>)],
+    in_common: &HashSet<Game>,
+) -> HashMap<Game, Vec<u64>> {
+    let mut hosts_by_game = HashMap::<Game, Vec<u64>>::new();
+    for (id, games) in libraries {
+        for owned_game in games {
+            if !in_common.contains(&owned_game.game) {
+                hosts_by_game
+                    .entry(owned_game.game.clone())
+                    .or_default()
+                    .push(*id);
+            }
+        }
+    }
+    hosts_by_game
+}
+
+#[derive(Debug)]
+pub struct PlayableTogether {
+    pub in_common: HashSet<Game>,
+    /// Remote Play Together games that not everyone owns, along with the members who could host
+    pub remote_play: Vec<(Game, Vec<u64>)>,
+}
+
+/// Fetch the wishlist of every provided steam id concurrently. The wishlists are returned in the
+/// same order as the steam ids were provided
+pub async fn get_wishlists_by_player<'a>(
+    steam_ids: impl IntoIterator<Item = u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<(u64, Vec<WishlistItem>)>, Error> {
+    let steam_ids = steam_ids.into_iter().collect::<Vec<_>>();
+    let query_results = join_all(
+        steam_ids
+            .iter()
+            .map(|&id| client::get_wishlist(client::GetUserDetailsRequest { id }, logger)),
+    )
+    .await;
+
+    steam_ids
+        .into_iter()
+        .zip(query_results)
+        .map(|(id, result)| Ok((id, result?)))
+        .collect()
+}
+
+/// Find the games on the wishlists of at least min_members members of the group, ranked by how
+/// many members want them and then by their current discount
+pub async fn find_wishlist_overlap<'a>(
+    steam_ids: impl IntoIterator<Item = u64>,
+    min_members: usize,
+    country_code: &str,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<WishlistOverlap>, Error> {
+    let members_by_appid = find_wishlisted_games(
+        get_wishlists_by_player(steam_ids, logger).await?,
+        min_members,
     );
-    let mut games_in_common_minus_focus = result.1?;
 
-    for game in result.0? {
-        games_in_common_minus_focus.remove(&game);
+    let game_data =
+        get_game_data(members_by_appid.keys().copied(), Some(country_code), logger).await;
+
+    let mut overlap = members_by_appid
+        .into_iter()
+        .filter_map(|(appid, wishlisted_by)| {
+            game_data.get(&appid).map(|data| WishlistOverlap {
+                game: Game {
+                    name: data.name.clone(),
+                    appid,
+                },
+                wishlisted_by,
+                price: Price::from(data),
+            })
+        })
+        .collect::<Vec<_>>();
+    rank_wishlist_overlap(&mut overlap);
+    Ok(overlap)
+}
+
+/// Find, for each game on at least min_members of the wishlists, the members who want it
+pub fn find_wishlisted_games(
+    wishlists: Vec<(u64, Vec<WishlistItem>)>,
+    min_members: usize,
+) -> HashMap<u64, Vec<u64>> {
+    let mut members_by_appid = HashMap::<u64, Vec<u64>>::new();
+    for (id, items) in wishlists {
+        for item in items {
+            members_by_appid.entry(item.appid).or_default().push(id);
+        }
     }
-    Ok(games_in_common_minus_focus)
+    members_by_appid.retain(|_, members| members.len() >= min_members);
+    members_by_appid
 }
 
-pub async fn resolve_usernames_strictly<'a>(
-    usernames: impl IntoIterator<Item = &str>,
-    my_steamid: u64,
+/// Put the games most members want first, then the biggest discounts
+pub fn rank_wishlist_overlap(overlap: &mut [WishlistOverlap]) {
+    overlap.sort_by(|a, b| {
+        b.wishlisted_by
+            .len()
+            .cmp(&a.wishlisted_by.len())
+            .then(b.price.discount_percent().cmp(&a.price.discount_percent()))
+            .then(a.game.name.cmp(&b.game.name))
+    });
+}
+
+#[derive(Debug)]
+pub struct WishlistOverlap {
+    pub game: Game,
+    pub wishlisted_by: Vec<u64>,
+    pub price: Price,
+}
+
+/// Find the games that every member of the group either owns or has on their wishlist
+pub async fn find_games_owned_or_wishlisted_by_all<'a>(
+    steam_ids: impl IntoIterator<Item = u64>,
     logger: &'a FilteringLogger<'a>,
-) -> Result<Vec<u64>, Error> {
-    resolve_username_with_mapping_function(
-        usernames,
-        my_steamid,
-        |username, user_summaries| {
-            user_summaries
-                .iter()
-                .find(|user| {
-                    user.personaname.to_ascii_lowercase() == *username.to_ascii_lowercase()
-                })
-                .ok_or(Error::User("supplied user not in list".to_string()))
-        },
+) -> Result<HashSet<Game>, Error> {
+    let steam_ids = steam_ids.into_iter().collect::<Vec<_>>();
+    let (libraries, wishlists) = join!(
+        get_owned_games_by_player(steam_ids.clone(), logger),
+        get_wishlists_by_player(steam_ids, logger)
+    );
+
+    let (appids_in_common, mut names) =
+        find_appids_owned_or_wishlisted_by_all(libraries?, wishlists?);
+
+    // games that are only on wishlists don't come with a name, so those need to be looked up
+    let unnamed_game_data = get_game_data(
+        appids_in_common
+            .iter()
+            .filter(|appid| !names.contains_key(appid))
+            .copied()
+            .collect::<Vec<_>>(),
+        None,
         logger,
     )
-    .await
+    .await;
+    names.extend(
+        unnamed_game_data
+            .into_iter()
+            .map(|(appid, data)| (appid, data.name)),
+    );
+
+    Ok(appids_in_common
+        .into_iter()
+        .filter_map(|appid| {
+            names.get(&appid).map(|name| Game {
+                name: name.clone(),
+                appid,
+            })
+        })
+        .collect())
 }
 
-pub async fn resolve_usernames_fuzzily<'a>(
-    usernames: impl IntoIterator<Item = &str>,
-    my_steamid: u64,
-    threshold: u32,
+/// Find the appids that every member owns or wishlists, along with the names of the owned ones
+pub fn find_appids_owned_or_wishlisted_by_all(
+    libraries: Vec<(u64, Vec<OwnedGame>)>,
+    wishlists: Vec<(u64, Vec<WishlistItem>)>,
+) -> (HashSet<u64>, HashMap<u64, String>) {
+    let mut names = HashMap::<u64, String>::new();
+    let mut appids_in_common: Option<HashSet<u64>> = None;
+    for ((_, games), (_, items)) in libraries.into_iter().zip(wishlists) {
+        let mut appids = items.iter().map(|item| item.appid).collect::<HashSet<_>>();
+        for owned_game in games {
+            appids.insert(owned_game.game.appid);
+            names.insert(owned_game.game.appid, owned_game.game.name);
+        }
+        appids_in_common = Some(match appids_in_common {
+            None => appids,
+            Some(appids_in_common) => appids_in_common.intersection(&appids).copied().collect(),
+        });
+    }
+    (appids_in_common.unwrap_or_default(), names)
+}
+
+/// For each member of the group, find the games that everyone else in the group owns but that
+/// member does not. The results are in the same order as the steam ids were provided
+pub async fn find_games_missing_from_each_member<'a>(
+    steam_ids: impl IntoIterator<Item = u64>,
+    include_family: bool,
     logger: &'a FilteringLogger<'a>,
-) -> Result<Vec<u64>, Error> {
-    // TODO: this involves a lot of unnecessaries recomputations around user_summaries that should
-    // be removed
-    resolve_username_with_mapping_function(
-        usernames,
-        my_steamid,
-        |username, user_summaries| {
-            let usernames = user_summaries
-                .iter()
-                .map(|summary| &summary.personaname)
-                .collect::<Vec<_>>();
-            let matches = nucleo_matcher::pattern::Pattern::parse(
-                username,
-                nucleo_matcher::pattern::CaseMatching::Ignore,
-                nucleo_matcher::pattern::Normalization::Smart,
+) -> Result<Vec<(u64, HashSet<Game>)>, Error> {
+    Ok(find_games_missing_from_each_library(
+        get_libraries_by_player(steam_ids, include_family, logger).await?,
+    ))
+}
+
+/// Same as find_games_missing_from_each_member, but using libraries that have already been fetched
+pub fn find_games_missing_from_each_library(
+    libraries: Vec<(u64, Vec<OwnedGame>)>,
+) -> Vec<(u64, HashSet<Game>)> {
+    let libraries = libraries
+        .into_iter()
+        .map(|(id, games)| {
+            (
+                id,
+                games
+                    .into_iter()
+                    .map(|owned_game| owned_game.game)
+                    .collect::<HashSet<_>>(),
             )
-            .match_list_with_index(
-                usernames.clone(),
-                &mut nucleo_matcher::Matcher::new(nucleo_matcher::Config::DEFAULT),
-            );
+        })
+        .collect::<Vec<_>>();
+
+    libraries
+        .iter()
+        .enumerate()
+        .map(|(member_index, (id, member_games))| {
+            let mut others = libraries
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| *index != member_index)
+                .map(|(_, (_, games))| games);
+            let missing = match others.next() {
+                None => HashSet::new(),
+                Some(first) => first
+                    .iter()
+                    .filter(|game| !member_games.contains(game))
+                    .filter(|game| others.clone().all(|games| games.contains(game)))
+                    .cloned()
+                    .collect(),
+            };
+            (*id, missing)
+        })
+        .collect()
+}
+
+/// Look up the store price of each game in the currency of the provided country code. Games
+/// whose price can't be fetched are marked as unavailable instead of failing the whole lookup
+pub async fn get_game_prices<'a>(
+    games: impl IntoIterator<Item = &Game>,
+    country_code: &str,
+    logger: &'a FilteringLogger<'a>,
+) -> HashMap<u64, Price> {
+    let appids = games.into_iter().map(|game| game.appid).collect::<Vec<_>>();
+    let game_data = get_game_data(appids.iter().copied(), Some(country_code), logger).await;
+    appids
+        .into_iter()
+        .map(|appid| {
+            let price = game_data
+                .get(&appid)
+                .map_or(Price::Unavailable, Price::from);
+            (appid, price)
+        })
+        .collect()
+}
 
-            if !matches.is_empty() {
-                let (_, score, index) = matches[0];
+/// Look up the store data of each app, a few at a time since appdetails is rate limited per app.
+/// Apps that fail to be fetched are left out of the result instead of failing the whole lookup
+pub async fn get_game_data<'a>(
+    appids: impl IntoIterator<Item = u64>,
+    country_code: Option<&str>,
+    logger: &'a FilteringLogger<'a>,
+) -> HashMap<u64, GameData> {
+    let appids = appids.into_iter().collect::<Vec<_>>();
+    let game_infos = stream::iter(appids.clone().into_iter().map(|appid| async move {
+        client::get_game_info_in_region(&appid, country_code, logger).await
+    }))
+    .buffered(APPDETAILS_CONCURRENCY)
+    .collect::<Vec<_>>()
+    .await;
 
-                if score > threshold {
-                    return Ok(&user_summaries[index]);
-                }
+    appids
+        .into_iter()
+        .zip(game_infos)
+        .filter_map(|(appid, game_info)| match game_info {
+            Err(err) => {
+                logger.trace(format!("failed to get game data for {appid}: {err}"));
+                None
             }
+            Ok(mut response) => response
+                .games
+                .remove(&appid)
+                .and_then(|info| info.data)
+                .map(|data| (appid, data)),
+        })
+        .collect()
+}
 
-            let realnames_indexed = user_summaries
-                .iter()
-                .enumerate()
-                .filter_map(|(index, summary)| {
-                    summary.realname.as_ref().map(|realname| (index, realname))
-                })
-                .collect::<Vec<_>>();
+#[derive(Debug, Clone)]
+pub enum Price {
+    Free,
+    Paid(PriceOverview),
+    Unavailable,
+}
 
-            let realnames = realnames_indexed
-                .iter()
-                .map(|(_, realname)| realname)
-                .collect::<Vec<_>>();
+impl From<&GameData> for Price {
+    fn from(data: &GameData) -> Self {
+        match &data.price_overview {
+            _ if data.is_free => Price::Free,
+            Some(price_overview) => Price::Paid(price_overview.to_owned()),
+            None => Price::Unavailable,
+        }
+    }
+}
 
-            let matches = nucleo_matcher::pattern::Pattern::parse(
-                username,
-                nucleo_matcher::pattern::CaseMatching::Ignore,
-                nucleo_matcher::pattern::Normalization::Smart,
-            )
-            .match_list_with_index(
-                realnames,
-                &mut nucleo_matcher::Matcher::new(nucleo_matcher::Config::DEFAULT),
-            );
+impl Price {
+    pub fn discount_percent(&self) -> u8 {
+        match self {
+            Price::Paid(price_overview) => price_overview.discount_percent,
+            Price::Free | Price::Unavailable => 0,
+        }
+    }
+
+    /// The current price in the smallest unit of the currency. Free and unavailable games cost 0
+    pub fn final_price(&self) -> u64 {
+        match self {
+            Price::Paid(price_overview) => price_overview.final_price,
+            Price::Free | Price::Unavailable => 0,
+        }
+    }
+}
 
-            if !matches.is_empty() {
-                let (_, score, index) = matches[0];
+/// What some games cost altogether. Unavailable prices can't be added up, so they're counted instead
+#[derive(Debug, PartialEq, Eq)]
+pub struct PriceTotal {
+    pub amount: u64,
+    pub currency: String,
+    pub unavailable: usize,
+}
 
-                if score > threshold {
-                    return Ok(&user_summaries[realnames_indexed[index].0]);
+pub fn total_price<'a>(prices: impl IntoIterator<Item = &'a Price>) -> PriceTotal {
+    let mut total = PriceTotal {
+        amount: 0,
+        currency: String::new(),
+        unavailable: 0,
+    };
+    for price in prices {
+        match price {
+            Price::Unavailable => total.unavailable += 1,
+            Price::Free => {}
+            Price::Paid(price_overview) => {
+                total.amount += price_overview.final_price;
+                if total.currency.is_empty() {
+                    total.currency = price_overview.currency.clone();
                 }
             }
+        }
+    }
+    total
+}
 
-            Err(Error::User(format!("Could not match {username}")))
-        },
-        logger,
-    )
-    .await
+/// Currencies that steam shows without decimals, e.g., JPY
+const WHOLE_CURRENCIES: [&str; 10] = [
+    "JPY", "KRW", "IDR", "VND", "CLP", "COP", "TWD", "KZT", "UAH", "CRC",
+];
+
+/// Format an amount the way the currency is written. Steam reports every amount in hundredths,
+/// even for currencies that aren't split into cents
+pub fn format_amount(amount: u64, currency: &str) -> String {
+    if WHOLE_CURRENCIES.contains(&currency) {
+        format!("{} {currency}", amount / 100)
+    } else {
+        format!("{:.2} {currency}", amount as f64 / 100.0)
+    }
+    .trim_end()
+    .to_string()
 }
 
-pub async fn resolve_username_with_mapping_function<'b, F>(
-    usernames: impl IntoIterator<Item = &str>,
+/// Fetch the summaries of the user and all of their friends, i.e., everyone a name can resolve to
+pub async fn get_resolvable_users<'a>(
     my_steamid: u64,
-    mapping_function: F,
-    logger: &'b FilteringLogger<'b>,
-) -> Result<Vec<u64>, Error>
-where
-    F: for<'a> Fn(&str, &'a Vec<client::UserSummary>) -> Result<&'a UserSummary, Error>,
-{
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<UserSummary>, Error> {
     let friends =
         client::get_user_friends_list(client::GetUserDetailsRequest { id: my_steamid }, logger)
             .await?;
@@ -165,69 +761,148 @@ where
         .map(|friend| friend.steamid.parse::<u64>())
         .collect::<Result<Vec<u64>, ParseIntError>>()?;
     ids.push(my_steamid);
-    let user_summaries =
-        client::get_user_summaries(client::GetUserSummariesRequest { ids }, logger).await?;
-    let steamids: Vec<u64> = usernames
+    let summaries = get_user_summaries_in_batches(&ids, logger).await?;
+    remember_persona_names(&summaries, logger);
+    Ok(summaries)
+}
+
+pub fn resolve_usernames_strictly<'a>(
+    usernames: impl IntoIterator<Item = &'a str>,
+    user_summaries: &[UserSummary],
+) -> Result<Vec<u64>, Error> {
+    usernames
         .into_iter()
-        .map(|username| mapping_function(username, &user_summaries))
-        .collect::<Result<Vec<_>, Error>>()?
-        .iter()
-        .map(|user| user.steamid.parse::<u64>())
-        .collect::<Result<Vec<u64>, ParseIntError>>()?;
-    Ok(steamids)
+        .map(|username| {
+            let summary = user_summaries
+                .iter()
+                .find(|user| user.personaname.eq_ignore_ascii_case(username))
+                .ok_or(Error::User("supplied user not in list".to_string()))?;
+            Ok(summary.steamid.parse::<u64>()?)
+        })
+        .collect()
+}
+
+pub fn resolve_usernames_fuzzily<'a>(
+    usernames: impl IntoIterator<Item = &'a str>,
+    user_summaries: &[UserSummary],
+    threshold: u32,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<u64>, Error> {
+    let resolver = NameResolver::new(user_summaries, threshold);
+    usernames
+        .into_iter()
+        .map(|username| {
+            let summary = resolver
+                .resolve(username)
+                .map_err(|err| Error::User(err.to_string()))?;
+            logger.trace(format!("resolved {username} to {}", summary.steamid));
+            Ok(summary.steamid.parse::<u64>()?)
+        })
+        .collect()
 }
 
 pub async fn find_friends_who_own_game<'a>(
     appid: &u64,
     my_steamid: u64,
+    include_family: bool,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<FriendWithGame>, Error> {
+    let (friends_libraries, my_library) = join!(
+        get_friends_libraries(my_steamid, include_family, logger),
+        get_library(my_steamid, include_family, logger)
+    );
+    let mut libraries = friends_libraries?;
+    match my_library {
+        Ok(games) => libraries.push((my_steamid, games)),
+        Err(err) => logger.trace(format!("filtered out some results due to {:?}", err)),
+    }
+
+    let friends_with_game = libraries
+        .iter()
+        .filter_map(|(steamid, games)| {
+            games
+                .iter()
+                .find(|owned_game| &owned_game.game.appid == appid)
+                .map(|owned_game| (*steamid, owned_game.borrowed))
+        })
+        .collect::<HashMap<u64, bool>>();
+
+    let user_summaries = client::get_user_summaries(
+        GetUserSummariesRequest {
+            ids: friends_with_game.keys().copied().collect(),
+        },
+        logger,
+    )
+    .await?;
+
+    Ok(user_summaries
+        .into_iter()
+        .map(|summary| FriendWithGame {
+            borrowed: summary
+                .steamid
+                .parse::<u64>()
+                .is_ok_and(|id| friends_with_game.get(&id) == Some(&true)),
+            summary,
+        })
+        .collect())
+}
+
+/// How many friends' libraries are fetched at once, so that rate limits are retried instead of
+/// every friend being requested at the same time
+const FRIENDS_LIBRARIES_CONCURRENCY: usize = 8;
+
+/// Fetch the library of every friend of the user, a few at a time. Libraries that fail to be
+/// fetched, e.g., because the friend's profile is private, are left out instead of failing the
+/// whole lookup
+pub async fn get_friends_libraries<'a>(
+    my_steamid: u64,
+    include_family: bool,
     logger: &'a FilteringLogger<'a>,
-) -> Result<Vec<client::UserSummary>, Error> {
+) -> Result<Vec<(u64, Vec<OwnedGame>)>, Error> {
     let friends =
         client::get_user_friends_list(client::GetUserDetailsRequest { id: my_steamid }, logger)
             .await?;
-
-    let steamids_iterator = friends
+    let friend_ids = friends
         .iter()
-        .map(|friend| friend.steamid.parse::<u64>().unwrap())
-        .chain(std::iter::once(my_steamid));
+        .filter_map(|friend| friend.steamid.parse::<u64>().ok())
+        .collect::<Vec<_>>();
 
-    let player_owned_games = join_all(
-        steamids_iterator
-            .clone() // We need to use this iterator again later so we can't move it here
-            .map(|id| (client::get_owned_games(client::GetUserDetailsRequest { id }, logger)))
-            .collect::<Vec<_>>(),
+    let identities = load_identities_for_libraries(logger);
+    let player_libraries = stream::iter(
+        friend_ids
+            .clone()
+            .into_iter()
+            .map(|id| get_linked_library(id, include_family, &identities, logger)),
     )
+    .buffered(FRIENDS_LIBRARIES_CONCURRENCY)
+    .collect::<Vec<_>>()
     .await;
 
     let mut errors = vec![];
-
-    let friends_with_game_ids = player_owned_games
+    let libraries = friend_ids
         .into_iter()
-        .zip(steamids_iterator)
-        .filter_map(|(result, steam_id)| match result {
-            Ok(v) => Some((v, steam_id)),
+        .zip(player_libraries)
+        .filter_map(|(steam_id, result)| match result {
+            Ok(games) => Some((steam_id, games)),
             Err(err) => {
                 errors.push(err);
                 None
             }
         })
-        .filter(|(games, _)| games.iter().any(|game| &game.appid == appid))
-        .map(|(_, steamid)| steamid)
-        .collect::<Vec<u64>>();
-
-    let user_summaries = client::get_user_summaries(
-        GetUserSummariesRequest {
-            ids: friends_with_game_ids,
-        },
-        logger,
-    )
-    .await?;
-
+        .collect();
     if !errors.is_empty() {
         logger.trace(format!("filtered out some results due to {:?}", errors));
     }
+    Ok(libraries)
+}
 
-    Ok(user_summaries)
+#[derive(Debug, Serialize)]
+pub struct FriendWithGame {
+    #[serde(flatten)]
+    pub summary: UserSummary,
+    /// true if they can only play the game through their Steam Family
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub borrowed: bool,
 }
 
 pub async fn filter_games<'a>(
@@ -237,39 +912,488 @@ pub async fn filter_games<'a>(
 ) -> Result<Vec<Game>, Error> {
     let games = games.into_iter().collect::<Vec<_>>();
 
-    // TODO: this would definitely get OOMs at scale, but for now this is fiiiiiiiiiiiiine
-    let game_infos = join_all(
-        games
-            .iter()
-            .map(|game| client::get_game_info(&game.appid, logger))
-            .collect::<Vec<_>>(),
-    )
-    .await;
+    let metadata =
+        get_game_metadata(games.iter().map(|game| game.appid), None, false, logger).await;
 
-    let filtered_games = games
-        .iter()
-        .zip(game_infos.iter())
-        .filter(|(game, game_info)| match game_info.as_ref() {
-            Err(err) => {
+    Ok(filter_games_by_metadata(
+        games,
+        &included_categories,
+        &metadata,
+        logger,
+    ))
+}
+
+/// Same as filter_games, but using metadata that has already been fetched. Games without
+/// metadata are filtered out
+pub fn filter_games_by_metadata<'a>(
+    games: impl IntoIterator<Item = Game>,
+    included_categories: &HashSet<u8>,
+    metadata: &HashMap<u64, GameMetadata>,
+    logger: &'a FilteringLogger<'a>,
+) -> Vec<Game> {
+    games
+        .into_iter()
+        .filter(|game| match metadata.get(&game.appid) {
+            None => {
                 logger.trace(format!(
-                    "ignoring game that failed to get game data for: {}: {:?}",
-                    game.appid, err
+                    "ignoring game that failed to get game data for: {}",
+                    game.appid
                 ));
                 false
             }
-            Ok(game_info_response) => game_info_response.games[&(game.appid)]
-                .data
-                .as_ref()
-                .map_or(false, |data| {
-                    data.categories
+            Some(metadata) => !metadata.categories.is_disjoint(included_categories),
+        })
+        .collect::<Vec<Game>>()
+}
+
+/// Fetch the store metadata of the apps in batches using GetItems, falling back to appdetails one
+/// app at a time for any app that GetItems doesn't know the categories of. GetItems never has pc
+/// requirements, so with_requirements looks every app up in appdetails as well. Apps that can't be
+/// fetched either way are left out of the result
+pub async fn get_game_metadata<'a>(
+    appids: impl IntoIterator<Item = u64>,
+    country_code: Option<&str>,
+    with_requirements: bool,
+    logger: &'a FilteringLogger<'a>,
+) -> HashMap<u64, GameMetadata> {
+    // appdetails is asked for the same country as GetItems so that the prices agree
+    let country_code = country_code.unwrap_or(DEFAULT_COUNTRY_CODE);
+    let appids = appids.into_iter().collect::<Vec<_>>();
+    let batches = appids
+        .chunks(client::STORE_ITEMS_BATCH_SIZE)
+        .collect::<Vec<_>>();
+    let store_items = join_all(
+        batches
+            .iter()
+            .map(|batch| client::get_store_items(batch, country_code, logger)),
+    )
+    .await;
+
+    let mut metadata = HashMap::<u64, GameMetadata>::new();
+    for (batch, result) in batches.iter().zip(store_items) {
+        match result {
+            Ok(store_items) => metadata.extend(
+                store_items
+                    .iter()
+                    .filter(|item| item.success == 1 && item.categories.is_some())
+                    .filter_map(|item| {
+                        item.appid
+                            .map(|appid| (appid, GameMetadata::from_store_item(appid, item)))
+                    }),
+            ),
+            Err(err) => logger.trace(format!(
+                "failed to get store items for {} apps, falling back to appdetails: {err}",
+                batch.len()
+            )),
+        }
+    }
+
+    let missing_appids = appids
+        .into_iter()
+        .filter(|appid| match metadata.get(appid) {
+            None => true,
+            Some(metadata) => with_requirements && metadata.pc_requirements.is_none(),
+        })
+        .collect::<Vec<_>>();
+    if !missing_appids.is_empty() {
+        logger.trace(format!(
+            "falling back to appdetails for {} apps",
+            missing_appids.len()
+        ));
+        let from_appdetails =
+            get_game_metadata_from_appdetails(missing_appids, Some(country_code), logger).await;
+        for (appid, from_appdetails) in from_appdetails {
+            match metadata.get_mut(&appid) {
+                Some(metadata) => metadata.fill_in_from(from_appdetails),
+                None => {
+                    metadata.insert(appid, from_appdetails);
+                }
+            }
+        }
+    }
+    fill_in_currency(&mut metadata, country_code, logger).await;
+    metadata
+}
+
+/// GetItems only returns formatted prices, so the currency of its prices isn't known. Every price
+/// from one country is in the same currency though, so it's taken from an app that was looked up
+/// in appdetails, or else from looking up one of the paid apps there
+async fn fill_in_currency<'a>(
+    metadata: &mut HashMap<u64, GameMetadata>,
+    country_code: &str,
+    logger: &'a FilteringLogger<'a>,
+) {
+    let lacking_currency = metadata
+        .values()
+        .filter_map(|metadata| match &metadata.price {
+            Price::Paid(price_overview) if price_overview.currency.is_empty() => {
+                Some(metadata.game.appid)
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if lacking_currency.is_empty() {
+        return;
+    }
+    let mut currency = metadata
+        .values()
+        .find_map(|metadata| match &metadata.price {
+            Price::Paid(price_overview) if !price_overview.currency.is_empty() => {
+                Some(price_overview.currency.clone())
+            }
+            _ => None,
+        });
+    for appid in lacking_currency.iter().take(CURRENCY_LOOKUP_ATTEMPTS) {
+        if currency.is_some() {
+            break;
+        }
+        currency = get_game_data([*appid], Some(country_code), logger)
+            .await
+            .remove(appid)
+            .and_then(|data| data.price_overview)
+            .map(|price_overview| price_overview.currency);
+    }
+    let Some(currency) = currency else {
+        logger.trace(format!(
+            "failed to find the currency of the prices in {country_code}"
+        ));
+        return;
+    };
+    for appid in lacking_currency {
+        if let Some(Price::Paid(price_overview)) =
+            metadata.get_mut(&appid).map(|metadata| &mut metadata.price)
+        {
+            price_overview.currency = currency.clone();
+        }
+    }
+}
+
+/// Fetch the store metadata of the apps through appdetails, one app at a time. Slower than
+/// get_game_metadata, but it's the only source of metacritic scores
+pub async fn get_game_metadata_from_appdetails<'a>(
+    appids: impl IntoIterator<Item = u64>,
+    country_code: Option<&str>,
+    logger: &'a FilteringLogger<'a>,
+) -> HashMap<u64, GameMetadata> {
+    get_game_data(appids, country_code, logger)
+        .await
+        .iter()
+        .map(|(appid, data)| (*appid, GameMetadata::from(data)))
+        .collect()
+}
+
+/// The store metadata of a game, pulled together from GetItems or appdetails. Fields that the
+/// source didn't provide are left empty
+#[derive(Debug, Clone)]
+pub struct GameMetadata {
+    pub game: Game,
+    pub categories: HashSet<u8>,
+    pub platforms: Option<client::StorePlatforms>,
+    pub review: Option<client::ReviewSummary>,
+    pub price: Price,
+    /// unix timestamp
+    pub release_date: Option<u64>,
+    pub metacritic: Option<u8>,
+    /// the recommended pc requirements, which only appdetails has
+    pub pc_requirements: Option<String>,
+}
+
+impl GameMetadata {
+    fn from_store_item(appid: u64, item: &client::StoreItem) -> Self {
+        GameMetadata {
+            game: Game {
+                name: item.name.clone(),
+                appid,
+            },
+            categories: item
+                .categories
+                .iter()
+                .flat_map(|categories| {
+                    categories
+                        .supported_player_categoryids
                         .iter()
-                        .any(|category| included_categories.contains(&(category.id)))
+                        .chain(categories.feature_categoryids.iter())
+                        .chain(categories.controller_categoryids.iter())
+                        .copied()
+                })
+                .collect(),
+            platforms: item.platforms.clone(),
+            review: item
+                .reviews
+                .as_ref()
+                .and_then(|reviews| reviews.summary_filtered.clone()),
+            price: match &item.best_purchase_option {
+                _ if item.is_free => Price::Free,
+                None => Price::Unavailable,
+                Some(purchase_option) => Price::Paid(PriceOverview {
+                    // GetItems only returns formatted prices, so get_game_metadata fills this in
+                    currency: String::new(),
+                    initial: purchase_option
+                        .original_price_in_cents
+                        .max(purchase_option.final_price_in_cents),
+                    final_price: purchase_option.final_price_in_cents,
+                    discount_percent: purchase_option.discount_pct,
+                    initial_formatted: purchase_option
+                        .formatted_original_price
+                        .clone()
+                        .unwrap_or(purchase_option.formatted_final_price.clone()),
+                    final_formatted: purchase_option.formatted_final_price.clone(),
                 }),
+            },
+            release_date: item
+                .release
+                .as_ref()
+                .and_then(|release| release.steam_release_date),
+            metacritic: None,
+            pc_requirements: None,
+        }
+    }
+
+    /// Fill in what GetItems doesn't provide from the metadata appdetails has for the same app
+    fn fill_in_from(&mut self, from_appdetails: GameMetadata) {
+        self.metacritic = self.metacritic.or(from_appdetails.metacritic);
+        self.pc_requirements = self
+            .pc_requirements
+            .take()
+            .or(from_appdetails.pc_requirements);
+        if let (Price::Paid(price_overview), Price::Paid(from_appdetails)) =
+            (&mut self.price, from_appdetails.price)
+        {
+            if price_overview.currency.is_empty() {
+                price_overview.currency = from_appdetails.currency;
+            }
+        }
+    }
+}
+
+impl From<&GameData> for GameMetadata {
+    fn from(data: &GameData) -> Self {
+        GameMetadata {
+            game: Game {
+                name: data.name.clone(),
+                appid: data.steam_appid,
+            },
+            categories: data.categories.iter().map(|category| category.id).collect(),
+            platforms: None,
+            review: None,
+            price: Price::from(data),
+            release_date: None,
+            metacritic: data.metacritic.as_ref().map(|metacritic| metacritic.score),
+            pc_requirements: data
+                .pc_requirements
+                .as_ref()
+                .and_then(|requirements| requirements.recommended.clone()),
+        }
+    }
+}
+
+/// Same as client::get_user_summaries, but for any number of steam ids
+pub async fn get_user_summaries_in_batches<'a>(
+    steam_ids: &[u64],
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<UserSummary>, Error> {
+    let summaries = join_all(
+        steam_ids
+            .chunks(client::USER_SUMMARIES_BATCH_SIZE)
+            .map(|batch| {
+                client::get_user_summaries(
+                    GetUserSummariesRequest {
+                        ids: batch.to_vec(),
+                    },
+                    logger,
+                )
+            }),
+    )
+    .await;
+    let mut all_summaries = vec![];
+    for batch in summaries {
+        all_summaries.extend(batch?);
+    }
+    Ok(all_summaries)
+}
+
+/// A friend along with their player summary, if it could be fetched
+#[derive(Debug, Clone)]
+pub struct FriendDetails {
+    pub steam_id: u64,
+    pub friend: client::Friend,
+    pub summary: Option<UserSummary>,
+}
+
+/// Fetch the friends list of the user along with the summary of every friend
+pub async fn get_friend_details<'a>(
+    my_steamid: u64,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<Vec<FriendDetails>, Error> {
+    let friends =
+        client::get_user_friends_list(client::GetUserDetailsRequest { id: my_steamid }, logger)
+            .await?;
+    let friends = friends
+        .into_iter()
+        .map(|friend| Ok((friend.steamid.parse::<u64>()?, friend)))
+        .collect::<Result<Vec<_>, ParseIntError>>()?;
+    let summaries = get_user_summaries_in_batches(
+        &friends.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+        logger,
+    )
+    .await?;
+    Ok(friends
+        .into_iter()
+        .map(|(steam_id, friend)| FriendDetails {
+            steam_id,
+            summary: summaries
+                .iter()
+                .find(|summary| summary.steamid == friend.steamid)
+                .cloned(),
+            friend,
+        })
+        .collect())
+}
+
+/// Split the friends into the ones who haven't been online since offline_since, longest offline
+/// first, and the ones whose last time online isn't known, e.g., because their profile is private
+pub fn find_inactive_friends(
+    friends: &[FriendDetails],
+    offline_since: u64,
+) -> (Vec<&FriendDetails>, Vec<&FriendDetails>) {
+    let last_online = |friend: &FriendDetails| {
+        friend
+            .summary
+            .as_ref()
+            .and_then(|summary| summary.lastlogoff)
+    };
+    let (mut inactive, unknown): (Vec<_>, Vec<_>) = friends
+        .iter()
+        .filter(|friend| last_online(friend).is_none_or(|last| last < offline_since))
+        .partition(|friend| last_online(friend).is_some());
+    inactive.sort_by_key(|friend| last_online(friend));
+    (inactive, unknown)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrivacyIssue {
+    PrivateProfile,
+    PrivateGameDetails,
+    /// GetOwnedGames failed outright
+    Unreachable,
+}
+
+impl Display for PrivacyIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PrivacyIssue::PrivateProfile => write!(f, "profile is private"),
+            PrivacyIssue::PrivateGameDetails => write!(f, "game details are private"),
+            PrivacyIssue::Unreachable => write!(f, "library could not be fetched"),
+        }
+    }
+}
+
+/// Work out why a friend's library can't be read, if it can't be. library is None if fetching it
+/// failed
+pub fn find_privacy_issue(
+    friend: &FriendDetails,
+    library: Option<&[OwnedGame]>,
+) -> Option<PrivacyIssue> {
+    let public = friend.summary.as_ref().is_some_and(|summary| {
+        summary.communityvisibilitystate == Some(client::PUBLIC_VISIBILITY_STATE)
+    });
+    match library {
+        None => Some(PrivacyIssue::Unreachable),
+        Some([]) if !public => Some(PrivacyIssue::PrivateProfile),
+        Some([]) => Some(PrivacyIssue::PrivateGameDetails),
+        Some(_) => None,
+    }
+}
+
+/// Check which of the user's friends have libraries that can't be read
+pub async fn audit_friends_privacy<'a>(
+    my_steamid: u64,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<(usize, Vec<(FriendDetails, PrivacyIssue)>), Error> {
+    let friends = get_friend_details(my_steamid, logger).await?;
+    // 429s are retried, so fetching every library at once would just keep tripping the rate limit
+    let steam_ids = friends
+        .iter()
+        .map(|friend| friend.steam_id)
+        .collect::<Vec<_>>();
+    let libraries = stream::iter(
+        steam_ids
+            .into_iter()
+            .map(|id| client::get_owned_games(client::GetUserDetailsRequest { id }, logger)),
+    )
+    .buffered(FRIENDS_LIBRARIES_CONCURRENCY)
+    .collect::<Vec<_>>()
+    .await;
+    let total = friends.len();
+    let issues = friends
+        .into_iter()
+        .zip(libraries)
+        .filter_map(|(friend, library)| {
+            find_privacy_issue(&friend, library.as_deref().ok()).map(|issue| (friend, issue))
         })
-        .map(|(game, _)| game.to_owned())
-        .collect::<Vec<Game>>();
+        .collect();
+    Ok((total, issues))
+}
+
+/// Crawl the friends lists outwards from the root, fetching at most concurrency lists at a time.
+/// The players found at max_depth are added to the graph, but their friends lists aren't fetched.
+/// Private lists are marked as private instead of failing the crawl, unless it's the root's list.
+/// Other failures are logged and the player is left without friends, rather than marked private
+pub async fn crawl_friend_graph<'a>(
+    root: u64,
+    max_depth: usize,
+    concurrency: usize,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<FriendGraph, Error> {
+    let mut graph = FriendGraph::new(root);
+    let mut frontier = vec![root];
+    for depth in 1..=max_depth {
+        logger.trace(format!(
+            "fetching {} friends lists at depth {}",
+            frontier.len(),
+            depth - 1
+        ));
+        // buffered keeps the results in order so that the crawl is the same every time
+        let friends_lists = stream::iter(frontier.into_iter().map(|steam_id| async move {
+            let friends = client::get_user_friends_list(
+                client::GetUserDetailsRequest { id: steam_id },
+                logger,
+            )
+            .await;
+            (steam_id, friends)
+        }))
+        .buffered(concurrency.max(1))
+        .collect::<Vec<_>>()
+        .await;
 
-    Ok(filtered_games)
+        let mut next_frontier = vec![];
+        for (steam_id, friends) in friends_lists {
+            match friends {
+                Err(err) if steam_id == root => return Err(err.into()),
+                Err(err) if client::is_private_friends_list(&err) => {
+                    logger.trace(format!("the friends list of {steam_id} is private"));
+                    graph.private.insert(steam_id);
+                }
+                Err(err) => {
+                    logger.error(format!("could not get the friends of {steam_id}: {err}"));
+                }
+                Ok(friends) => {
+                    for friend in friends {
+                        let Ok(friend_id) = friend.steamid.parse::<u64>() else {
+                            continue;
+                        };
+                        graph.add_friendship(steam_id, friend_id);
+                        if let Entry::Vacant(entry) = graph.depths.entry(friend_id) {
+                            entry.insert(depth);
+                            next_frontier.push(friend_id);
+                        }
+                    }
+                }
+            }
+        }
+        frontier = next_frontier;
+    }
+    Ok(graph)
 }
 
 #[derive(Debug)]
@@ -300,3 +1424,359 @@ impl Display for Error {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{
+        find_appids_owned_or_wishlisted_by_all, find_borrowers,
+        find_games_in_common_from_libraries, find_games_missing_from_each_library,
+        find_inactive_friends, find_possible_hosts, find_privacy_issue, find_wishlisted_games,
+        format_amount, rank_wishlist_overlap, total_price, FriendDetails, GameMetadata,
+        OwnershipMatrix, Price, PriceTotal, PrivacyIssue, WishlistOverlap,
+    };
+    use crate::steam::{
+        client::{Friend, PriceOverview, UserSummary, WishlistItem},
+        models::{Game, OwnedGame},
+    };
+
+    fn friend(steam_id: u64, lastlogoff: Option<u64>) -> FriendDetails {
+        FriendDetails {
+            steam_id,
+            friend: Friend {
+                steamid: steam_id.to_string(),
+                relationship: "friend".to_string(),
+                friend_since: 0,
+            },
+            summary: Some(UserSummary {
+                steamid: steam_id.to_string(),
+                personaname: format!("player {steam_id}"),
+                realname: None,
+                lastlogoff,
+                communityvisibilitystate: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn privacy_issues() {
+        let mut private = friend(1, None);
+        if let Some(summary) = private.summary.as_mut() {
+            summary.communityvisibilitystate = Some(1);
+        }
+        let mut public = friend(2, None);
+        if let Some(summary) = public.summary.as_mut() {
+            summary.communityvisibilitystate = Some(3);
+        }
+        let games = vec![OwnedGame::new(10, "Portal")];
+        assert_eq!(
+            find_privacy_issue(&private, Some(&[])),
+            Some(PrivacyIssue::PrivateProfile)
+        );
+        assert_eq!(
+            find_privacy_issue(&public, Some(&[])),
+            Some(PrivacyIssue::PrivateGameDetails)
+        );
+        assert_eq!(
+            find_privacy_issue(&public, None),
+            Some(PrivacyIssue::Unreachable)
+        );
+        assert_eq!(find_privacy_issue(&public, Some(&games)), None);
+    }
+
+    #[test]
+    fn inactive_friends_are_longest_offline_first() {
+        let friends = vec![
+            friend(1, Some(500)),
+            friend(2, Some(100)),
+            friend(3, None),
+            friend(4, Some(1000)),
+            friend(5, Some(300)),
+        ];
+        let (inactive, unknown) = find_inactive_friends(&friends, 600);
+        let ids = |friends: Vec<&FriendDetails>| {
+            friends
+                .iter()
+                .map(|friend| friend.steam_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(inactive), vec![2, 5, 1]);
+        assert_eq!(ids(unknown), vec![3]);
+    }
+
+    #[test]
+    fn ownership_matrix_is_union_of_libraries() {
+        let matrix = OwnershipMatrix::from_libraries(vec![
+            (
+                1,
+                vec![
+                    OwnedGame {
+                        playtime_forever: 60,
+                        ..OwnedGame::new(10, "Portal")
+                    },
+                    OwnedGame::new(20, "Dota"),
+                ],
+            ),
+            (
+                2,
+                vec![
+                    OwnedGame {
+                        playtime_forever: 30,
+                        ..OwnedGame::new(10, "Portal")
+                    },
+                    OwnedGame {
+                        playtime_forever: 5,
+                        account: Some(3),
+                        ..OwnedGame::new(30, "Celeste")
+                    },
+                ],
+            ),
+        ]);
+
+        assert_eq!(matrix.steam_ids, vec![1, 2]);
+        let rows = matrix
+            .rows
+            .iter()
+            .map(|row| {
+                (
+                    row.game.name.as_str(),
+                    row.cells
+                        .iter()
+                        .map(|cell| cell.as_ref().map(|cell| (cell.playtime, cell.account)))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                ("Celeste", vec![None, Some((5, Some(3)))]),
+                ("Dota", vec![Some((0, None)), None]),
+                ("Portal", vec![Some((60, None)), Some((30, None))]),
+            ]
+        );
+        assert_eq!(matrix.linked_accounts(), vec![3]);
+    }
+
+    fn paid(final_price: u64, currency: &str) -> Price {
+        Price::Paid(PriceOverview {
+            currency: currency.to_string(),
+            initial: final_price,
+            final_price,
+            discount_percent: 0,
+            initial_formatted: String::new(),
+            final_formatted: String::new(),
+        })
+    }
+
+    #[test]
+    fn totals_prices_and_counts_unavailable_ones() {
+        let prices = [
+            paid(1999, "EUR"),
+            Price::Free,
+            Price::Unavailable,
+            paid(500, "EUR"),
+            Price::Unavailable,
+        ];
+        assert_eq!(
+            total_price(&prices),
+            PriceTotal {
+                amount: 2499,
+                currency: "EUR".to_string(),
+                unavailable: 2,
+            }
+        );
+        assert_eq!(format_amount(2499, "EUR"), "24.99 EUR");
+        assert_eq!(format_amount(398000, "JPY"), "3980 JPY");
+        assert_eq!(format_amount(0, ""), "0.00");
+    }
+
+    #[test]
+    fn store_items_are_filled_in_from_appdetails() {
+        let metadata = |price, pc_requirements: Option<&str>| GameMetadata {
+            game: Game {
+                name: "Portal".to_string(),
+                appid: 400,
+            },
+            categories: Default::default(),
+            platforms: None,
+            review: None,
+            price,
+            release_date: None,
+            metacritic: None,
+            pc_requirements: pc_requirements.map(str::to_string),
+        };
+        let mut from_store_items = metadata(paid(999, ""), None);
+        from_store_items.fill_in_from(metadata(paid(1099, "EUR"), Some("a gpu")));
+        assert_eq!(from_store_items.pc_requirements.as_deref(), Some("a gpu"));
+        match from_store_items.price {
+            Price::Paid(price_overview) => {
+                assert_eq!(price_overview.final_price, 999);
+                assert_eq!(price_overview.currency, "EUR");
+            }
+            _ => panic!("the price should still be paid"),
+        }
+    }
+
+    #[test]
+    fn games_missing_from_each_member_are_owned_by_everyone_else() {
+        let missing = find_games_missing_from_each_library(vec![
+            (
+                1,
+                vec![OwnedGame::new(10, "Portal"), OwnedGame::new(20, "Dota")],
+            ),
+            (
+                2,
+                vec![OwnedGame::new(10, "Portal"), OwnedGame::new(30, "Celeste")],
+            ),
+            (
+                3,
+                vec![OwnedGame::new(20, "Dota"), OwnedGame::new(30, "Celeste")],
+            ),
+        ]);
+        let appids = missing
+            .into_iter()
+            .map(|(id, games)| {
+                let mut appids = games.iter().map(|game| game.appid).collect::<Vec<_>>();
+                appids.sort();
+                (id, appids)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(appids, vec![(1, vec![30]), (2, vec![20]), (3, vec![10])]);
+        assert_eq!(
+            find_games_missing_from_each_library(vec![(1, vec![OwnedGame::new(10, "Portal")])]),
+            vec![(1, Default::default())]
+        );
+    }
+
+    #[test]
+    fn games_not_owned_by_everyone_can_be_hosted_by_their_owners() {
+        let libraries = vec![
+            (
+                1,
+                vec![OwnedGame::new(10, "Portal"), OwnedGame::new(20, "Dota")],
+            ),
+            (
+                2,
+                vec![OwnedGame::new(10, "Portal"), OwnedGame::new(20, "Dota")],
+            ),
+            (
+                3,
+                vec![OwnedGame::new(10, "Portal"), OwnedGame::new(30, "Celeste")],
+            ),
+        ];
+        let in_common = find_games_in_common_from_libraries(&libraries);
+        let mut hosts = find_possible_hosts(&libraries, &in_common)
+            .into_iter()
+            .map(|(game, hosts)| (game.appid, hosts))
+            .collect::<Vec<_>>();
+        hosts.sort();
+        assert_eq!(
+            in_common.iter().map(|game| game.appid).collect::<Vec<_>>(),
+            vec![10]
+        );
+        assert_eq!(hosts, vec![(20, vec![1, 2]), (30, vec![3])]);
+    }
+
+    fn wishlist(appids: &[u64]) -> Vec<WishlistItem> {
+        appids
+            .iter()
+            .map(|appid| WishlistItem {
+                appid: *appid,
+                priority: 0,
+                date_added: 0,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn wishlisted_games_need_enough_members() {
+        let mut wishlisted = find_wishlisted_games(
+            vec![
+                (1, wishlist(&[10, 20])),
+                (2, wishlist(&[10, 30])),
+                (3, wishlist(&[10, 20])),
+            ],
+            2,
+        )
+        .into_iter()
+        .collect::<Vec<_>>();
+        wishlisted.sort();
+        assert_eq!(wishlisted, vec![(10, vec![1, 2, 3]), (20, vec![1, 3])]);
+    }
+
+    #[test]
+    fn wishlist_overlap_ranks_by_members_then_discount() {
+        let overlap = |appid: u64, wishlisted_by: Vec<u64>, discount_percent: u8| {
+            let mut price = paid(1000, "EUR");
+            if let Price::Paid(price_overview) = &mut price {
+                price_overview.discount_percent = discount_percent;
+            }
+            WishlistOverlap {
+                game: Game {
+                    name: appid.to_string(),
+                    appid,
+                },
+                wishlisted_by,
+                price,
+            }
+        };
+        let mut ranked = vec![
+            overlap(10, vec![1, 2], 0),
+            overlap(20, vec![1, 2, 3], 0),
+            overlap(30, vec![1, 2], 50),
+        ];
+        rank_wishlist_overlap(&mut ranked);
+        assert_eq!(
+            ranked
+                .iter()
+                .map(|overlap| overlap.game.appid)
+                .collect::<Vec<_>>(),
+            vec![20, 30, 10]
+        );
+    }
+
+    #[test]
+    fn games_can_be_owned_or_wishlisted() {
+        let (appids, names) = find_appids_owned_or_wishlisted_by_all(
+            vec![
+                (
+                    1,
+                    vec![OwnedGame::new(10, "Portal"), OwnedGame::new(20, "Dota")],
+                ),
+                (2, vec![OwnedGame::new(10, "Portal")]),
+            ],
+            vec![(1, wishlist(&[30])), (2, wishlist(&[20, 30]))],
+        );
+        let mut appids = appids.into_iter().collect::<Vec<_>>();
+        appids.sort();
+        assert_eq!(appids, vec![10, 20, 30]);
+        assert_eq!(names.get(&20).map(String::as_str), Some("Dota"));
+        assert_eq!(names.get(&30), None);
+    }
+
+    #[test]
+    fn borrowed_games_match_owned_ones_by_appid() {
+        let borrowed = OwnedGame {
+            borrowed: true,
+            ..OwnedGame::new(10, "Portal (Family)")
+        };
+        let libraries = vec![
+            (
+                1,
+                vec![OwnedGame::new(10, "Portal"), OwnedGame::new(20, "Dota")],
+            ),
+            (2, vec![borrowed, OwnedGame::new(20, "Dota")]),
+        ];
+        let mut in_common = find_games_in_common_from_libraries(&libraries)
+            .iter()
+            .map(|game| game.appid)
+            .collect::<Vec<_>>();
+        in_common.sort();
+        assert_eq!(in_common, vec![10, 20]);
+        let borrowers = find_borrowers(&libraries)
+            .into_iter()
+            .map(|(game, borrowers)| (game.appid, borrowers))
+            .collect::<Vec<_>>();
+        assert_eq!(borrowers, vec![(10, vec![2])]);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use clap::ValueEnum;
+
+use super::models::OwnedGame;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SimilarityMetric {
+    /// the share of the two libraries' games that both own
+    Jaccard,
+    /// like jaccard, but each game counts for as many minutes as each player has put into it, so
+    /// that the games both players actually play matter the most
+    Playtime,
+}
+
+#[derive(Debug, Clone)]
+pub struct Similarity {
+    pub steam_id: u64,
+    pub shared_games: usize,
+    /// between 0 and 1
+    pub jaccard: f64,
+    /// between 0 and 1
+    pub playtime: f64,
+    /// jaccard of just the games each player has played in the last 2 weeks, between 0 and 1
+    pub recent: f64,
+}
+
+impl Similarity {
+    pub fn between(steam_id: u64, mine: &[OwnedGame], theirs: &[OwnedGame]) -> Self {
+        let my_playtimes = playtimes(mine);
+        let their_playtimes = playtimes(theirs);
+        let shared_games = my_playtimes
+            .keys()
+            .filter(|appid| their_playtimes.contains_key(appid))
+            .count();
+        let all_games = my_playtimes.len() + their_playtimes.len() - shared_games;
+
+        // weighted jaccard: the sum of the smaller playtimes over the sum of the larger ones
+        let (mut smaller, mut larger) = (0, 0);
+        for (appid, my_playtime) in &my_playtimes {
+            let their_playtime = their_playtimes.get(appid).copied().unwrap_or_default();
+            smaller += (*my_playtime).min(their_playtime);
+            larger += (*my_playtime).max(their_playtime);
+        }
+        larger += their_playtimes
+            .iter()
+            .filter(|(appid, _)| !my_playtimes.contains_key(appid))
+            .map(|(_, playtime)| playtime)
+            .sum::<u64>();
+
+        let my_recent = recently_played(mine);
+        let their_recent = recently_played(theirs);
+        let recent_shared = my_recent
+            .iter()
+            .filter(|appid| their_recent.contains(appid))
+            .count();
+
+        Similarity {
+            steam_id,
+            shared_games,
+            jaccard: ratio(shared_games as u64, all_games as u64),
+            playtime: ratio(smaller, larger),
+            recent: ratio(
+                recent_shared as u64,
+                (my_recent.len() + their_recent.len() - recent_shared) as u64,
+            ),
+        }
+    }
+
+    pub fn score(&self, metric: SimilarityMetric) -> f64 {
+        match metric {
+            SimilarityMetric::Jaccard => self.jaccard,
+            SimilarityMetric::Playtime => self.playtime,
+        }
+    }
+}
+
+fn playtimes(games: &[OwnedGame]) -> HashMap<u64, u64> {
+    games
+        .iter()
+        .map(|owned_game| (owned_game.game.appid, owned_game.playtime_forever))
+        .collect()
+}
+
+fn recently_played(games: &[OwnedGame]) -> HashSet<u64> {
+    games
+        .iter()
+        .filter(|owned_game| owned_game.playtime_2weeks > 0)
+        .map(|owned_game| owned_game.game.appid)
+        .collect()
+}
+
+fn ratio(numerator: u64, denominator: u64) -> f64 {
+    if denominator == 0 {
+        0.0
+    } else {
+        numerator as f64 / denominator as f64
+    }
+}
+
+/// Rank the other libraries by how similar they are to mine, most similar first. Empty libraries,
+/// which are usually private profiles, are left out since there's nothing to compare
+pub fn rank_by_similarity(
+    my_library: &[OwnedGame],
+    libraries: &[(u64, Vec<OwnedGame>)],
+    metric: SimilarityMetric,
+) -> Vec<Similarity> {
+    let mut similarities = libraries
+        .iter()
+        .filter(|(_, games)| !games.is_empty())
+        .map(|(steam_id, games)| Similarity::between(*steam_id, my_library, games))
+        .collect::<Vec<_>>();
+    similarities.sort_by(|a, b| {
+        b.score(metric)
+            .total_cmp(&a.score(metric))
+            .then_with(|| b.shared_games.cmp(&a.shared_games))
+    });
+    similarities
+}
+
+/// Compare every pair of libraries. Row i, column j holds the similarity between library i and
+/// library j, so the matrix is symmetric
+pub fn pairwise_similarities(libraries: &[(u64, Vec<OwnedGame>)]) -> Vec<Vec<Similarity>> {
+    libraries
+        .iter()
+        .map(|(_, mine)| {
+            libraries
+                .iter()
+                .map(|(steam_id, theirs)| Similarity::between(*steam_id, mine, theirs))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rank_by_similarity, Similarity, SimilarityMetric};
+    use crate::steam::models::OwnedGame;
+
+    #[test]
+    fn jaccard_and_playtime_similarity() {
+        let mine = vec![
+            OwnedGame {
+                playtime_forever: 100,
+                ..OwnedGame::new(1, "game 1")
+            },
+            OwnedGame::new(2, "game 2"),
+            OwnedGame {
+                playtime_forever: 50,
+                ..OwnedGame::new(3, "game 3")
+            },
+        ];
+        let theirs = vec![
+            OwnedGame {
+                playtime_forever: 60,
+                ..OwnedGame::new(1, "game 1")
+            },
+            OwnedGame {
+                playtime_forever: 50,
+                ..OwnedGame::new(3, "game 3")
+            },
+            OwnedGame {
+                playtime_forever: 10,
+                ..OwnedGame::new(4, "game 4")
+            },
+        ];
+        let similarity = Similarity::between(7, &mine, &theirs);
+        assert_eq!(similarity.shared_games, 2);
+        assert_eq!(similarity.jaccard, 0.5);
+        // (60 + 0 + 50 + 0) / (100 + 0 + 50 + 10)
+        assert_eq!(similarity.playtime, 110.0 / 160.0);
+    }
+
+    #[test]
+    fn ranking_depends_on_the_metric() {
+        let mine = vec![
+            OwnedGame {
+                playtime_forever: 600,
+                ..OwnedGame::new(1, "game 1")
+            },
+            OwnedGame::new(2, "game 2"),
+            OwnedGame::new(3, "game 3"),
+        ];
+        let libraries = vec![
+            // owns more of the same games, but never plays them
+            (
+                10,
+                vec![OwnedGame::new(2, "game 2"), OwnedGame::new(3, "game 3")],
+            ),
+            (
+                20,
+                vec![
+                    OwnedGame {
+                        playtime_forever: 500,
+                        ..OwnedGame::new(1, "game 1")
+                    },
+                    OwnedGame::new(4, "game 4"),
+                ],
+            ),
+            (30, vec![]),
+        ];
+        let ids = |metric| {
+            rank_by_similarity(&mine, &libraries, metric)
+                .iter()
+                .map(|similarity| similarity.steam_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids(SimilarityMetric::Jaccard), vec![10, 20]);
+        assert_eq!(ids(SimilarityMetric::Playtime), vec![20, 10]);
+    }
+}
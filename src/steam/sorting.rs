@@ -0,0 +1,182 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use clap::ValueEnum;
+
+use super::{
+    models::{Game, OwnedGame},
+    service::{GameMetadata, Price},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortKey {
+    Name,
+    #[value(name = "appid")]
+    AppId,
+    GroupPlaytime,
+    MyPlaytime,
+    LastPlayed,
+    ReleaseDate,
+    Metacritic,
+    ReviewScore,
+    Price,
+}
+
+impl SortKey {
+    /// Names, app ids and prices read most naturally from smallest to largest, while everything
+    /// else is most useful with the most played or best reviewed games first
+    pub fn descending_by_default(&self) -> bool {
+        !matches!(self, SortKey::Name | SortKey::AppId | SortKey::Price)
+    }
+
+    pub fn needs_metadata(&self) -> bool {
+        matches!(
+            self,
+            SortKey::ReleaseDate | SortKey::Metacritic | SortKey::ReviewScore | SortKey::Price
+        )
+    }
+}
+
+/// Everything that has already been fetched that games can be sorted by
+pub struct SortContext<'a> {
+    pub libraries: &'a [(u64, Vec<OwnedGame>)],
+    pub my_library: Option<&'a [OwnedGame]>,
+    pub metadata: &'a HashMap<u64, GameMetadata>,
+}
+
+/// Sort the games by the key. Games that are missing a value for the key, e.g., games without a
+/// metacritic score, always go last. Ties are broken by name
+pub fn sort_games(games: &mut [Game], key: SortKey, descending: bool, context: &SortContext) {
+    games.sort_by(|a, b| {
+        let ordering = match key {
+            SortKey::Name if descending => b.name.cmp(&a.name),
+            SortKey::Name => a.name.cmp(&b.name),
+            _ => compare_missing_last(context.value(key, a), context.value(key, b), descending),
+        };
+        ordering.then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+fn compare_missing_last(a: Option<u64>, b: Option<u64>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) if descending => b.cmp(&a),
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl<'a> SortContext<'a> {
+    fn value(&self, key: SortKey, game: &Game) -> Option<u64> {
+        let owned_copies = || {
+            self.libraries.iter().flat_map(|(_, games)| {
+                games
+                    .iter()
+                    .filter(|owned_game| owned_game.game.appid == game.appid)
+            })
+        };
+        let metadata = self.metadata.get(&game.appid);
+        match key {
+            SortKey::Name | SortKey::AppId => Some(game.appid),
+            SortKey::GroupPlaytime => Some(
+                owned_copies()
+                    .map(|owned_game| owned_game.playtime_forever)
+                    .sum(),
+            ),
+            SortKey::MyPlaytime => self.my_library.map(|my_library| {
+                my_library
+                    .iter()
+                    .find(|owned_game| owned_game.game.appid == game.appid)
+                    .map_or(0, |owned_game| owned_game.playtime_forever)
+            }),
+            SortKey::LastPlayed => owned_copies()
+                .map(|owned_game| owned_game.rtime_last_played)
+                .max()
+                .filter(|last_played| *last_played > 0),
+            SortKey::ReleaseDate => metadata.and_then(|metadata| metadata.release_date),
+            SortKey::Metacritic => metadata
+                .and_then(|metadata| metadata.metacritic)
+                .map(u64::from),
+            SortKey::ReviewScore => metadata
+                .and_then(|metadata| metadata.review.as_ref())
+                .map(|review| review.percent_positive.into()),
+            SortKey::Price => metadata.and_then(|metadata| match &metadata.price {
+                Price::Unavailable => None,
+                price => Some(price.final_price()),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::{sort_games, SortContext, SortKey};
+    use crate::steam::models::{Game, OwnedGame};
+
+    fn game(appid: u64) -> Game {
+        Game {
+            name: format!("game {appid}"),
+            appid,
+        }
+    }
+
+    fn sorted_appids(key: SortKey, descending: bool) -> Vec<u64> {
+        let libraries = vec![
+            (
+                1,
+                vec![
+                    OwnedGame {
+                        playtime_forever: 60,
+                        ..OwnedGame::new(10, "game 10")
+                    },
+                    OwnedGame {
+                        playtime_forever: 5,
+                        rtime_last_played: 300,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                    OwnedGame {
+                        rtime_last_played: 100,
+                        ..OwnedGame::new(30, "game 30")
+                    },
+                ],
+            ),
+            (
+                2,
+                vec![
+                    OwnedGame::new(10, "game 10"),
+                    OwnedGame {
+                        playtime_forever: 100,
+                        rtime_last_played: 200,
+                        ..OwnedGame::new(20, "game 20")
+                    },
+                ],
+            ),
+        ];
+        let metadata = HashMap::new();
+        let context = SortContext {
+            libraries: &libraries,
+            my_library: Some(&libraries[0].1),
+            metadata: &metadata,
+        };
+        let mut games = vec![game(30), game(10), game(20)];
+        sort_games(&mut games, key, descending, &context);
+        games.iter().map(|game| game.appid).collect()
+    }
+
+    #[test]
+    fn sorts_by_playtime() {
+        assert_eq!(
+            sorted_appids(SortKey::GroupPlaytime, true),
+            vec![20, 10, 30]
+        );
+        assert_eq!(sorted_appids(SortKey::MyPlaytime, false), vec![30, 20, 10]);
+    }
+
+    #[test]
+    fn never_played_games_go_last_in_either_order() {
+        assert_eq!(sorted_appids(SortKey::LastPlayed, true), vec![20, 30, 10]);
+        assert_eq!(sorted_appids(SortKey::LastPlayed, false), vec![30, 20, 10]);
+    }
+}
@@ -0,0 +1,112 @@
+use clap::{ArgMatches, Command};
+use futures::future::BoxFuture;
+use serde::Serialize;
+
+use super::{
+    arg_matcher::{self, SharedArgs},
+    client::{self, GetUserSummariesRequest, UserSummary},
+    games_router::{compute_price_string, persona_name, price_amount, price_currency},
+    logger::FilteringLogger,
+    output::CommandOutput,
+    registry::SteamCommand,
+    router::{get_country, get_steam_ids, unknown_subcommand, Error},
+    service::{find_wishlist_overlap, WishlistOverlap},
+};
+
+pub struct WishlistCommand;
+
+impl SteamCommand for WishlistCommand {
+    fn definition(&self, args: &SharedArgs) -> Command {
+        arg_matcher::wishlist_command(args)
+    }
+
+    fn run<'a>(
+        &'a self,
+        arguments: &'a ArgMatches,
+        user_steam_id: Option<u64>,
+        logger: &'a FilteringLogger<'a>,
+    ) -> BoxFuture<'a, Result<CommandOutput, Error>> {
+        Box::pin(run_wishlist_command(arguments, user_steam_id, logger))
+    }
+}
+
+pub async fn run_wishlist_command<'a>(
+    arguments: &ArgMatches,
+    user_steam_id: Option<u64>,
+    logger: &'a FilteringLogger<'a>,
+) -> Result<CommandOutput, Error> {
+    match arguments.subcommand() {
+        Some(("overlap", arguments)) => {
+            let steam_ids = get_steam_ids(arguments, user_steam_id, "steam_ids", logger).await?;
+            let min_members = arguments
+                .get_one::<usize>("min-members")
+                .ok_or(Error::Argument("min-members is required".to_string()))?;
+            let country_code = &get_country(arguments)?;
+            let overlap =
+                find_wishlist_overlap(steam_ids.clone(), *min_members, country_code, logger)
+                    .await?;
+            let summaries =
+                client::get_user_summaries(GetUserSummariesRequest { ids: steam_ids }, logger)
+                    .await?;
+            Ok(CommandOutput::WishlistOverlap { overlap, summaries })
+        }
+        _ => Err(unknown_subcommand(arguments)),
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct WishlistRecord<'a> {
+    appid: u64,
+    name: &'a str,
+    wishlisted_by: Vec<String>,
+    price: Option<f64>,
+    currency: Option<&'a str>,
+    discount_percent: u8,
+}
+
+pub fn compute_wishlist_records<'a>(
+    overlap: &'a [WishlistOverlap],
+    summaries: &[UserSummary],
+) -> Vec<WishlistRecord<'a>> {
+    overlap
+        .iter()
+        .map(|entry| WishlistRecord {
+            appid: entry.game.appid,
+            name: &entry.game.name,
+            wishlisted_by: entry
+                .wishlisted_by
+                .iter()
+                .map(|id| persona_name(summaries, *id))
+                .collect(),
+            price: price_amount(&entry.price),
+            currency: price_currency(&entry.price),
+            discount_percent: entry.price.discount_percent(),
+        })
+        .collect()
+}
+
+pub fn compute_wishlist_overlap_string(
+    overlap: &[WishlistOverlap],
+    summaries: &[UserSummary],
+) -> String {
+    format!(
+        "{games}\n\tTotal: {total}\n",
+        games = overlap
+            .iter()
+            .map(|entry| format!(
+                "{game}: wanted by {count} ({members}), {price}",
+                game = entry.game,
+                count = entry.wishlisted_by.len(),
+                members = entry
+                    .wishlisted_by
+                    .iter()
+                    .map(|id| persona_name(summaries, *id))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                price = compute_price_string(&entry.price),
+            ))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        total = overlap.len()
+    )
+}
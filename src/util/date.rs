@@ -0,0 +1,33 @@
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Format a unix timestamp as a UTC date, e.g., 2024-03-09
+pub fn format_unix_date(timestamp: u64) -> String {
+    // days to civil date from http://howardhinnant.github.io/date_algorithms.html
+    let days = (timestamp / SECONDS_PER_DAY) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_unix_date;
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_unix_date(0), "1970-01-01");
+        assert_eq!(format_unix_date(951_782_400), "2000-02-29");
+        assert_eq!(format_unix_date(1_709_942_399), "2024-03-08");
+    }
+}
@@ -1,2 +1,4 @@
 pub mod async_help;
+pub mod date;
+pub mod storage;
 pub mod string_parser;
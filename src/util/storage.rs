@@ -0,0 +1,94 @@
+use std::{
+    fmt::Display,
+    fs,
+    future::Future,
+    io::{self, ErrorKind},
+    path::PathBuf,
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+const APP_DIRECTORY: &str = "steam-cli";
+
+tokio::task_local! {
+    /// Whether the data directory can be written to by the command that's running. It can be when
+    /// this isn't set
+    static WRITES_ALLOWED: bool;
+}
+
+/// Run the future with writes to the data directory allowed or not. When they aren't, e.g., in a
+/// chat bot that's shared with others, the files on this machine are only ever read
+pub async fn with_writes_allowed<F: Future>(allowed: bool, future: F) -> F::Output {
+    WRITES_ALLOWED.scope(allowed, future).await
+}
+
+/// Whether the data directory can be written to, so that caches can skip saving when it can't
+pub fn writes_allowed() -> bool {
+    WRITES_ALLOWED.try_with(|allowed| *allowed).unwrap_or(true)
+}
+
+fn data_directory() -> Result<PathBuf, Error> {
+    Ok(dirs::data_dir()
+        .ok_or(Error::NoDataDirectory)?
+        .join(APP_DIRECTORY))
+}
+
+/// Get the path of a file in the steam-cli data directory, e.g., ~/.local/share/steam-cli on linux,
+/// creating the directory if it doesn't exist yet
+pub fn data_file_path(file_name: &str) -> Result<PathBuf, Error> {
+    let directory = data_directory()?;
+    fs::create_dir_all(&directory)?;
+    Ok(directory.join(file_name))
+}
+
+/// Load a json file from the data directory. If the file doesn't exist yet, then the default value
+/// is returned instead
+pub fn load_json<T: DeserializeOwned + Default>(file_name: &str) -> Result<T, Error> {
+    match fs::read_to_string(data_directory()?.join(file_name)) {
+        Ok(contents) => Ok(serde_json::from_str(&contents)?),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(T::default()),
+        Err(err) => Err(err.into()),
+    }
+}
+
+pub fn save_json<T: Serialize>(file_name: &str, value: &T) -> Result<(), Error> {
+    if !writes_allowed() {
+        return Err(Error::WritesNotAllowed);
+    }
+    fs::write(
+        data_file_path(file_name)?,
+        serde_json::to_string_pretty(value)?,
+    )?;
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Error {
+    NoDataDirectory,
+    WritesNotAllowed,
+    Io(io::Error),
+    Json(serde_json::Error),
+}
+
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Self {
+        Error::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Self {
+        Error::Json(value)
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::NoDataDirectory => write!(f, "could not find a data directory for this user"),
+            Error::WritesNotAllowed => write!(f, "local files can't be written here"),
+            Error::Io(err) => write!(f, "IoError: {}", err),
+            Error::Json(err) => write!(f, "JsonError: {}", err),
+        }
+    }
+}
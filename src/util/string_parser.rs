@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 // NOTE: something weird is going on and the compiling the steam-cli bin doesn't actually sees this
 // function as unused wheras the discord-steam-cli sees that function as used and so we get
 // warnings when compiling one and not the other
@@ -31,6 +33,47 @@ pub fn batch_string(input: &str, max_size: usize, separator: char) -> Result<Vec
     Ok(batches)
 }
 
+/// Parse a duration like 90d, 2w or 1y into a std Duration. Used as a clap value parser, so the
+/// error is a message for the user
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let unit_index = input.find(|c: char| !c.is_ascii_digit()).ok_or(format!(
+        "{input} is missing a unit, expected one of h, d, w or y"
+    ))?;
+    let (amount, unit) = input.split_at(unit_index);
+    let amount = amount
+        .parse::<u64>()
+        .map_err(|_| format!("{input} does not start with a number"))?;
+    let hours = match unit {
+        "h" => 1,
+        "d" => 24,
+        "w" => 24 * 7,
+        "y" => 24 * 365,
+        other => {
+            return Err(format!(
+                "unknown unit {other}, expected one of h, d, w or y"
+            ))
+        }
+    };
+    amount
+        .checked_mul(hours * 60 * 60)
+        .map(Duration::from_secs)
+        .ok_or(format!("{input} is too long"))
+}
+
+/// Parse an amount of money like 29.99 into the smallest unit of the currency, e.g., cents. Used
+/// as a clap value parser, so the error is a message for the user
+pub fn parse_amount(input: &str) -> Result<u64, String> {
+    let amount = input
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("{input} isn't a number"))?;
+    if !amount.is_finite() || amount < 0.0 || amount * 100.0 > u64::MAX as f64 {
+        return Err(format!("{input} isn't an amount of money"));
+    }
+    Ok((amount * 100.0).round() as u64)
+}
+
 #[derive(Debug)]
 pub enum Error {
     SeparatorNotFound,
@@ -55,9 +98,37 @@ mod tests {
         }
     }
 
-    use super::batch_string;
+    use std::time::Duration;
+
+    use super::{batch_string, parse_amount, parse_duration};
     use itertools::izip;
 
+    #[test]
+    fn parse_duration_units() {
+        assert_eq!(
+            parse_duration("90d"),
+            Ok(Duration::from_secs(90 * 24 * 60 * 60))
+        );
+        assert_eq!(
+            parse_duration("2w"),
+            Ok(Duration::from_secs(14 * 24 * 60 * 60))
+        );
+        assert!(parse_duration("90").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("3 months").is_err());
+        assert!(parse_duration("18446744073709551615y").is_err());
+    }
+
+    #[test]
+    fn parse_amount_in_cents() {
+        assert_eq!(parse_amount("29.99"), Ok(2999));
+        assert_eq!(parse_amount("0"), Ok(0));
+        assert!(parse_amount("-5").is_err());
+        assert!(parse_amount("NaN").is_err());
+        assert!(parse_amount("inf").is_err());
+        assert!(parse_amount("five").is_err());
+    }
+
     batch_string_tests! {
         batch_string_0: (("something", 10, ' '), vec!["something"]),
         batch_string_1: (("something else", 10, ' '), vec!["something", "else"]),